@@ -1,11 +1,20 @@
 //! `Ipld` codecs.
-use alloc::{string::String, vec::Vec};
+#[cfg(target_has_atomic = "ptr")]
+use alloc::sync::Arc;
+use alloc::{
+    borrow::{Cow, ToOwned},
+    boxed::Box,
+    rc::Rc,
+    string::String,
+    vec::Vec,
+};
 use core::{convert::TryFrom, fmt::Write as _};
 
 use crate::cid::Cid;
 use crate::error::{Result, UnsupportedCodec};
 use crate::io::{Cursor, Read, Seek, Write};
 use crate::ipld::Ipld;
+use crate::token::Token;
 
 /// Codec trait.
 pub trait Codec:
@@ -23,6 +32,11 @@ pub trait Codec:
         T::decode(*self, &mut Cursor::new(bytes))
     }
 
+    /// Decodes a type borrowing from `bytes`.
+    fn decode_ref<'a, T: DecodeRef<'a, Self>>(&self, bytes: &'a [u8]) -> Result<T> {
+        T::decode_ref(*self, bytes)
+    }
+
     /// Scrapes the references.
     fn references<T: References<Self>, E: Extend<Cid>>(
         &self,
@@ -45,12 +59,37 @@ pub trait Encode<C: Codec> {
     fn encode<W: Write>(&self, c: C, w: &mut W) -> Result<()>;
 }
 
-impl<C: Codec, T: Encode<C>> Encode<C> for &T {
+impl<C: Codec, T: Encode<C> + ?Sized> Encode<C> for &T {
     fn encode<W: Write>(&self, c: C, w: &mut W) -> Result<()> {
         T::encode(*self, c, w)
     }
 }
 
+impl<C: Codec, T: Encode<C> + ?Sized> Encode<C> for Box<T> {
+    fn encode<W: Write>(&self, c: C, w: &mut W) -> Result<()> {
+        T::encode(self, c, w)
+    }
+}
+
+impl<C: Codec, T: Encode<C> + ?Sized> Encode<C> for Rc<T> {
+    fn encode<W: Write>(&self, c: C, w: &mut W) -> Result<()> {
+        T::encode(self, c, w)
+    }
+}
+
+#[cfg(target_has_atomic = "ptr")]
+impl<C: Codec, T: Encode<C> + ?Sized> Encode<C> for Arc<T> {
+    fn encode<W: Write>(&self, c: C, w: &mut W) -> Result<()> {
+        T::encode(self, c, w)
+    }
+}
+
+impl<C: Codec, T: Encode<C> + ToOwned + ?Sized> Encode<C> for Cow<'_, T> {
+    fn encode<W: Write>(&self, c: C, w: &mut W) -> Result<()> {
+        T::encode(self, c, w)
+    }
+}
+
 /// Decode trait.
 ///
 /// This trait is generic over a codec, so that different codecs can be implemented for the same
@@ -63,6 +102,44 @@ pub trait Decode<C: Codec>: Sized {
     fn decode<R: Read + Seek>(c: C, r: &mut R) -> Result<Self>;
 }
 
+impl<C: Codec, T: Decode<C>> Decode<C> for Box<T> {
+    fn decode<R: Read + Seek>(c: C, r: &mut R) -> Result<Self> {
+        T::decode(c, r).map(Box::new)
+    }
+}
+
+impl<C: Codec, T: Decode<C>> Decode<C> for Rc<T> {
+    fn decode<R: Read + Seek>(c: C, r: &mut R) -> Result<Self> {
+        T::decode(c, r).map(Rc::new)
+    }
+}
+
+#[cfg(target_has_atomic = "ptr")]
+impl<C: Codec, T: Decode<C>> Decode<C> for Arc<T> {
+    fn decode<R: Read + Seek>(c: C, r: &mut R) -> Result<Self> {
+        T::decode(c, r).map(Arc::new)
+    }
+}
+
+/// Always decodes into an owned value.
+impl<C: Codec, T: ToOwned + ?Sized> Decode<C> for Cow<'_, T>
+where
+    T::Owned: Decode<C>,
+{
+    fn decode<R: Read + Seek>(c: C, r: &mut R) -> Result<Self> {
+        T::Owned::decode(c, r).map(Cow::Owned)
+    }
+}
+
+/// Borrowing decode trait.
+///
+/// Like [`Decode`], but decodes from a byte slice, so that the decoded value can borrow strings
+/// and bytes from it instead of copying them.
+pub trait DecodeRef<'a, C: Codec>: Sized {
+    /// Decode from a byte slice.
+    fn decode_ref(c: C, bytes: &'a [u8]) -> Result<Self>;
+}
+
 /// References trait.
 ///
 /// This trait is generic over a codec, so that different codecs can be implemented for the same
@@ -75,6 +152,23 @@ pub trait References<C: Codec>: Sized {
     fn references<R: Read + Seek, E: Extend<Cid>>(c: C, r: &mut R, set: &mut E) -> Result<()>;
 }
 
+/// Tokens trait.
+///
+/// Streams an encoded value as [`Token`]s instead of decoding it, see the [`token`](crate::token)
+/// module. This trait is generic over a codec, so that different codecs can be implemented for the
+/// same type.
+pub trait Tokens<C: Codec>: Sized {
+    /// Passes the tokens read from an `impl Read` to `f`, stopping at the first error it returns.
+    ///
+    /// It takes a specific codec as parameter, so that the [`Tokens`] can be generic over an enum
+    /// that contains multiple codecs.
+    fn tokens<R: Read + Seek, F: FnMut(Token) -> Result<()>>(
+        c: C,
+        r: &mut R,
+        f: &mut F,
+    ) -> Result<()>;
+}
+
 /// Utility for testing codecs.
 ///
 /// Encodes the `data` using the codec `c` and checks that it matches the `ipld`.
@@ -11,6 +11,8 @@ use crate::{cid::Cid, ipld::Ipld};
 
 #[cfg(feature = "std")]
 use crate::error::{Error, TypeError, TypeErrorType};
+#[cfg(feature = "std")]
+use crate::number::{FromNumber, Number, NumberPolicy};
 
 #[cfg(feature = "std")]
 impl TryFrom<Ipld> for () {
@@ -69,6 +71,34 @@ macro_rules! derive_try_from_ipld {
     };
 }
 
+// Integers are converted with the strict number policy.
+#[cfg(feature = "std")]
+macro_rules! derive_try_from_ipld_int {
+    ($ty:ty) => {
+        impl TryFrom<Ipld> for $ty {
+            type Error = Error;
+
+            fn try_from(ipld: Ipld) -> Result<Self, Self::Error> {
+                match ipld {
+                    Ipld::Integer(i) => Ok(Number::from(i).to(NumberPolicy::Strict)?),
+                    _ => Err(TypeError::new(<$ty as FromNumber>::KIND, ipld).into()),
+                }
+            }
+        }
+
+        impl TryFrom<Ipld> for Option<$ty> {
+            type Error = Error;
+
+            fn try_from(ipld: Ipld) -> Result<Self, Self::Error> {
+                match ipld {
+                    Ipld::Null => Ok(None),
+                    ipld => Ok(Some(ipld.try_into()?)),
+                }
+            }
+        }
+    };
+}
+
 macro_rules! derive_into_ipld_prim {
     ($enum:ident, $ty:ty, $fn:ident) => {
         impl From<$ty> for Ipld {
@@ -116,29 +146,29 @@ derive_into_ipld!(Link, &Cid, to_owned);
 #[cfg(feature = "std")]
 derive_try_from_ipld!(Bool, bool);
 #[cfg(feature = "std")]
-derive_try_from_ipld!(Integer, i8);
+derive_try_from_ipld_int!(i8);
 #[cfg(feature = "std")]
-derive_try_from_ipld!(Integer, i16);
+derive_try_from_ipld_int!(i16);
 #[cfg(feature = "std")]
-derive_try_from_ipld!(Integer, i32);
+derive_try_from_ipld_int!(i32);
 #[cfg(feature = "std")]
-derive_try_from_ipld!(Integer, i64);
+derive_try_from_ipld_int!(i64);
 #[cfg(feature = "std")]
-derive_try_from_ipld!(Integer, i128);
+derive_try_from_ipld_int!(i128);
 #[cfg(feature = "std")]
-derive_try_from_ipld!(Integer, isize);
+derive_try_from_ipld_int!(isize);
 #[cfg(feature = "std")]
-derive_try_from_ipld!(Integer, u8);
+derive_try_from_ipld_int!(u8);
 #[cfg(feature = "std")]
-derive_try_from_ipld!(Integer, u16);
+derive_try_from_ipld_int!(u16);
 #[cfg(feature = "std")]
-derive_try_from_ipld!(Integer, u32);
+derive_try_from_ipld_int!(u32);
 #[cfg(feature = "std")]
-derive_try_from_ipld!(Integer, u64);
+derive_try_from_ipld_int!(u64);
 #[cfg(feature = "std")]
-derive_try_from_ipld!(Integer, u128);
+derive_try_from_ipld_int!(u128);
 #[cfg(feature = "std")]
-derive_try_from_ipld!(Integer, usize);
+derive_try_from_ipld_int!(usize);
 
 //derive_from_ipld!(Float, f32); // User explicit conversion is prefered. Would implicitly lossily convert from f64.
 
@@ -157,30 +187,6 @@ derive_try_from_ipld!(Link, Cid);
 
 #[cfg(feature = "std")]
 derive_try_from_ipld_option!(Bool, bool);
-#[cfg(feature = "std")]
-derive_try_from_ipld_option!(Integer, i8);
-#[cfg(feature = "std")]
-derive_try_from_ipld_option!(Integer, i16);
-#[cfg(feature = "std")]
-derive_try_from_ipld_option!(Integer, i32);
-#[cfg(feature = "std")]
-derive_try_from_ipld_option!(Integer, i64);
-#[cfg(feature = "std")]
-derive_try_from_ipld_option!(Integer, i128);
-#[cfg(feature = "std")]
-derive_try_from_ipld_option!(Integer, isize);
-#[cfg(feature = "std")]
-derive_try_from_ipld_option!(Integer, u8);
-#[cfg(feature = "std")]
-derive_try_from_ipld_option!(Integer, u16);
-#[cfg(feature = "std")]
-derive_try_from_ipld_option!(Integer, u32);
-#[cfg(feature = "std")]
-derive_try_from_ipld_option!(Integer, u64);
-#[cfg(feature = "std")]
-derive_try_from_ipld_option!(Integer, u128);
-#[cfg(feature = "std")]
-derive_try_from_ipld_option!(Integer, usize);
 
 //derive_from_ipld_option!(Float, f32); // User explicit conversion is prefered. Would implicitly lossily convert from f64.
 
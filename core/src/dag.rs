@@ -0,0 +1,61 @@
+//! Access to the blocks of a dag.
+//!
+//! The functions that follow links, like [`crate::patch::apply_dag`], load and store blocks
+//! through closures instead of a store. [`Blocks`] holds these closures while walking the dag.
+use crate::cid::Cid;
+use crate::error::{Error, Result};
+use crate::ipld::Ipld;
+
+/// Loads the block with the given CID.
+pub type Loader<'a> = &'a mut dyn FnMut(&Cid) -> Result<Ipld>;
+
+/// Stores a block and returns its CID.
+pub type Storer<'a> = &'a mut dyn FnMut(Ipld) -> Result<Cid>;
+
+/// The loader and storer of a dag walk.
+///
+/// Without a loader, links are plain values that aren't followed.
+#[derive(Default)]
+pub struct Blocks<'a> {
+    load: Option<Loader<'a>>,
+    store: Option<Storer<'a>>,
+}
+
+impl<'a> Blocks<'a> {
+    /// Creates blocks that are only loaded.
+    pub fn load_only(load: Loader<'a>) -> Self {
+        Self {
+            load: Some(load),
+            store: None,
+        }
+    }
+
+    /// Creates blocks that are loaded and stored.
+    pub fn new(load: Loader<'a>, store: Storer<'a>) -> Self {
+        Self {
+            load: Some(load),
+            store: Some(store),
+        }
+    }
+
+    /// Returns true if links are followed.
+    pub fn is_loading(&self) -> bool {
+        self.load.is_some()
+    }
+
+    /// Loads a block, or returns `None` if links aren't followed.
+    pub fn load(&mut self, cid: &Cid) -> Result<Option<Ipld>> {
+        match &mut self.load {
+            Some(load) => load(cid).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Stores a block. Fails if there is no storer.
+    pub fn store(&mut self, ipld: Ipld) -> Result<Cid> {
+        match &mut self.store {
+            Some(store) => store(ipld),
+            None => Err(Error::msg("blocks can't be stored")),
+        }
+    }
+}
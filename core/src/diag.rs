@@ -0,0 +1,154 @@
+//! CBOR diagnostic notation.
+//!
+//! Renders [`Ipld`] in the textual notation described in RFC 8949 section 8. This is also what
+//! the `Display` impl of [`Ipld`] prints. The dag-cbor crate parses it back.
+use alloc::{string::ToString, vec, vec::Vec};
+use core::cmp::Ordering;
+use core::fmt::{self, Write};
+
+use crate::ipld::Ipld;
+
+/// How links are rendered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LinkFormat {
+    /// Renders links the way they are encoded, e.g. `42(h'0001711220...')`.
+    #[default]
+    Bytes,
+    /// Renders links as a tagged CID string, e.g. `42("bafy...")`.
+    Cid,
+}
+
+/// Displays an [`Ipld`] in CBOR diagnostic notation.
+///
+/// Map entries are printed in dag-cbor canonical order, so the output matches what tools like
+/// cbor.me show for the encoded block. The alternate form `{:#}` spreads maps and lists over
+/// multiple lines.
+#[derive(Clone, Copy, Debug)]
+pub struct Diagnostic<'a> {
+    ipld: &'a Ipld,
+    links: LinkFormat,
+}
+
+impl<'a> Diagnostic<'a> {
+    /// Creates a new diagnostic notation formatter.
+    pub fn new(ipld: &'a Ipld) -> Self {
+        Self {
+            ipld,
+            links: LinkFormat::default(),
+        }
+    }
+
+    /// Sets the link format.
+    pub fn links(mut self, links: LinkFormat) -> Self {
+        self.links = links;
+        self
+    }
+
+    fn write<W: Write>(&self, ipld: &Ipld, w: &mut W, pretty: bool, depth: usize) -> fmt::Result {
+        let newline = |w: &mut W, depth: usize| {
+            if pretty {
+                write!(w, "\n{:1$}", "", depth * 2)
+            } else {
+                Ok(())
+            }
+        };
+        let separator = if pretty { "," } else { ", " };
+        match ipld {
+            Ipld::Null => w.write_str("null"),
+            Ipld::Bool(b) => write!(w, "{}", b),
+            Ipld::Integer(i) => write!(w, "{}", i),
+            Ipld::Float(f) => write_float(*f, w),
+            Ipld::String(s) => write_str(s, w),
+            Ipld::Bytes(b) => write_bytes(b, w),
+            Ipld::List(l) if l.is_empty() => w.write_str("[]"),
+            Ipld::List(l) => {
+                w.write_char('[')?;
+                for (i, ipld) in l.iter().enumerate() {
+                    if i > 0 {
+                        w.write_str(separator)?;
+                    }
+                    newline(w, depth + 1)?;
+                    self.write(ipld, w, pretty, depth + 1)?;
+                }
+                newline(w, depth)?;
+                w.write_char(']')
+            }
+            Ipld::Map(m) if m.is_empty() => w.write_str("{}"),
+            Ipld::Map(m) => {
+                let mut cbor_order: Vec<_> = m.iter().collect();
+                cbor_order.sort_unstable_by(|&(key_a, _), &(key_b, _)| {
+                    match key_a.len().cmp(&key_b.len()) {
+                        Ordering::Equal => key_a.cmp(key_b),
+                        ordering => ordering,
+                    }
+                });
+                w.write_char('{')?;
+                for (i, (key, value)) in cbor_order.into_iter().enumerate() {
+                    if i > 0 {
+                        w.write_str(separator)?;
+                    }
+                    newline(w, depth + 1)?;
+                    write_str(key, w)?;
+                    w.write_str(": ")?;
+                    self.write(value, w, pretty, depth + 1)?;
+                }
+                newline(w, depth)?;
+                w.write_char('}')
+            }
+            Ipld::Link(cid) => {
+                w.write_str("42(")?;
+                match self.links {
+                    LinkFormat::Bytes => {
+                        let mut bytes = vec![0];
+                        bytes.extend(cid.to_bytes());
+                        write_bytes(&bytes, w)?;
+                    }
+                    LinkFormat::Cid => write_str(&cid.to_string(), w)?,
+                }
+                w.write_char(')')
+            }
+        }
+    }
+}
+
+impl<'a> fmt::Display for Diagnostic<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pretty = f.alternate();
+        self.write(self.ipld, f, pretty, 0)
+    }
+}
+
+fn write_float<W: Write>(f: f64, w: &mut W) -> fmt::Result {
+    if f.is_nan() {
+        w.write_str("NaN")
+    } else if f.is_infinite() {
+        w.write_str(if f > 0.0 { "Infinity" } else { "-Infinity" })
+    } else {
+        // `Debug` always prints a decimal point or exponent, which keeps floats apart from ints.
+        write!(w, "{:?}", f)
+    }
+}
+
+fn write_str<W: Write>(s: &str, w: &mut W) -> fmt::Result {
+    w.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => w.write_str("\\\"")?,
+            '\\' => w.write_str("\\\\")?,
+            '\n' => w.write_str("\\n")?,
+            '\r' => w.write_str("\\r")?,
+            '\t' => w.write_str("\\t")?,
+            c if c.is_control() => write!(w, "\\u{:04x}", c as u32)?,
+            c => w.write_char(c)?,
+        }
+    }
+    w.write_char('"')
+}
+
+fn write_bytes<W: Write>(bytes: &[u8], w: &mut W) -> fmt::Result {
+    w.write_str("h'")?;
+    for byte in bytes {
+        write!(w, "{:02x}", byte)?;
+    }
+    w.write_char('\'')
+}
@@ -5,6 +5,7 @@ use alloc::{string::String, vec::Vec};
 
 use crate::cid::Cid;
 use crate::ipld::{Ipld, IpldIndex};
+use crate::number::Number;
 pub use anyhow::{Error, Result};
 #[cfg(feature = "std")]
 use thiserror::Error;
@@ -67,6 +68,11 @@ impl serde::ser::Error for SerdeError {
 #[cfg(feature = "serde-codec")]
 impl serde::ser::StdError for SerdeError {}
 
+/// The number can't be converted to the target type under the chosen policy.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "std", derive(Error), error("Can't convert {0:?} to {1}."))]
+pub struct NumberConversionError(pub Number, pub &'static str);
+
 /// Type error.
 #[derive(Clone, Debug)]
 #[cfg_attr(
@@ -98,8 +104,41 @@ impl core::fmt::Display for TypeError {
     }
 }
 
+/// A patch operation failed.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(
+    feature = "std",
+    derive(Error),
+    error("Patch operation {index} failed: {reason}.")
+)]
+pub struct PatchError {
+    /// The index of the failed operation.
+    pub index: usize,
+    /// Why it failed.
+    pub reason: &'static str,
+}
+
+#[cfg(not(feature = "std"))]
+impl core::fmt::Display for PatchError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "Patch operation {} failed: {}.", self.index, self.reason)
+    }
+}
+
+/// A value that could only be partially decoded, returned by the lenient decoders of the codecs.
+#[derive(Debug)]
+pub struct PartialIpld {
+    /// Everything decoded before the error. Lists and maps contain the items read up to the
+    /// failure, including a partially decoded last item. `None` if nothing could be decoded.
+    pub ipld: Option<Ipld>,
+    /// The error that stopped decoding.
+    pub error: Error,
+    /// Byte offset at which decoding stopped.
+    pub offset: u64,
+}
+
 /// Type error type.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum TypeErrorType {
     /// Null type.
     Null,
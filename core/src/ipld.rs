@@ -1,6 +1,6 @@
 //! Ipld representation.
 use alloc::{
-    borrow::ToOwned,
+    borrow::{Cow, ToOwned},
     boxed::Box,
     collections::BTreeMap,
     string::{String, ToString},
@@ -8,9 +8,15 @@ use alloc::{
     vec::Vec,
 };
 use core::fmt;
+use core::ops::{Index, IndexMut};
 
 use crate::cid::Cid;
-use crate::error::TypeError;
+use crate::diag::{Diagnostic, LinkFormat};
+use crate::error::{TypeError, TypeErrorType};
+use crate::number::Number;
+#[cfg(feature = "std")]
+use crate::number::{FromNumber, NumberPolicy};
+use crate::patch;
 
 /// Ipld
 #[derive(Clone, PartialEq)]
@@ -65,6 +71,15 @@ impl fmt::Debug for Ipld {
     }
 }
 
+/// Renders ipld in CBOR diagnostic notation for humans, with links as CID strings, e.g.
+/// `{"a": 42("bafy...")}`. The alternate form `{:#}` spreads maps and lists over multiple lines.
+/// See [`Diagnostic`] for other link formats.
+impl fmt::Display for Ipld {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&Diagnostic::new(self).links(LinkFormat::Cid), f)
+    }
+}
+
 /// An index into ipld
 pub enum IpldIndex<'a> {
     /// An index into an ipld list.
@@ -142,6 +157,66 @@ impl Ipld {
             .ok_or_else(|| TypeError::new(index, self))
     }
 
+    /// Mutably indexes into an ipld list or map.
+    pub fn get_mut<'a, T: Into<IpldIndex<'a>>>(
+        &mut self,
+        index: T,
+    ) -> Result<&mut Self, TypeError> {
+        let index = index.into();
+        let found = TypeErrorType::from(&*self);
+        let ipld = match self {
+            Ipld::List(l) => match index {
+                IpldIndex::List(i) => Some(i),
+                IpldIndex::Map(ref key) => key.parse().ok(),
+                IpldIndex::MapRef(key) => key.parse().ok(),
+            }
+            .and_then(|i| l.get_mut(i)),
+            Ipld::Map(m) => match index {
+                IpldIndex::Map(ref key) => m.get_mut(key),
+                IpldIndex::MapRef(key) => m.get_mut(key),
+                IpldIndex::List(i) => m.get_mut(&i.to_string()),
+            },
+            _ => None,
+        };
+        ipld.ok_or_else(|| TypeError::new(index, found))
+    }
+
+    /// Follows a JSON pointer (RFC 6901) of map keys and list indices, e.g. `"/users/3/name"`.
+    ///
+    /// The paths are the ones used by [`crate::patch`]: `~1` stands for a `/` and `~0` for a `~` in
+    /// a key, the empty path is the whole value and `/` is the empty key. The leading `/` may be
+    /// left out.
+    pub fn get_path(&self, path: &str) -> Result<&Self, TypeError> {
+        segments(path, self)?
+            .into_iter()
+            .try_fold(self, |ipld, segment| ipld.get(segment.as_ref()))
+    }
+
+    /// Destructs an ipld along a JSON pointer, see [`Ipld::get_path`].
+    pub fn take_path(self, path: &str) -> Result<Self, TypeError> {
+        segments(path, &self)?
+            .into_iter()
+            .try_fold(self, |ipld, segment| ipld.take(segment.as_ref()))
+    }
+
+    /// Returns the value of an integer or float.
+    pub fn as_number(&self) -> Option<Number> {
+        match self {
+            Self::Integer(i) => Some(Number::from(*i)),
+            Self::Float(f) => Some(Number::F64(*f)),
+            _ => None,
+        }
+    }
+
+    /// Converts an integer or float to `T` as directed by `policy`.
+    #[cfg(feature = "std")]
+    pub fn to_number<T: FromNumber>(&self, policy: NumberPolicy) -> crate::error::Result<T> {
+        match self.as_number() {
+            Some(number) => Ok(number.to(policy)?),
+            None => Err(TypeError::new(T::KIND, self).into()),
+        }
+    }
+
     /// Returns an iterator.
     pub fn iter(&self) -> IpldIter<'_> {
         IpldIter {
@@ -159,6 +234,31 @@ impl Ipld {
     }
 }
 
+/// Indexes into an ipld list or map like [`Ipld::get`], so `ipld["users"][3]["name"]` works.
+///
+/// # Panics
+///
+/// Panics if the value isn't a list or map or doesn't contain the index.
+impl<'a, T: Into<IpldIndex<'a>>> Index<T> for Ipld {
+    type Output = Ipld;
+
+    fn index(&self, index: T) -> &Self::Output {
+        match self.get(index) {
+            Ok(ipld) => ipld,
+            Err(err) => panic!("{}", err),
+        }
+    }
+}
+
+impl<'a, T: Into<IpldIndex<'a>>> IndexMut<T> for Ipld {
+    fn index_mut(&mut self, index: T) -> &mut Self::Output {
+        match self.get_mut(index) {
+            Ok(ipld) => ipld,
+            Err(err) => panic!("{}", err),
+        }
+    }
+}
+
 /// Ipld iterator.
 pub struct IpldIter<'a> {
     stack: Vec<Box<dyn Iterator<Item = &'a Ipld> + 'a>>,
@@ -191,6 +291,11 @@ impl<'a> Iterator for IpldIter<'a> {
     }
 }
 
+/// Splits a path into its segments, reporting an invalid escape as a type error for the whole path.
+fn segments<'a>(path: &'a str, ipld: &Ipld) -> Result<Vec<Cow<'a, str>>, TypeError> {
+    patch::segments(path).map_err(|_| TypeError::new(TypeErrorType::Key(path.into()), ipld))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -281,4 +386,83 @@ mod tests {
         let ipld = Ipld::Map(map);
         assert_eq!(ipld.get("a").unwrap(), &Ipld::Integer(0));
     }
+
+    #[test]
+    fn test_display() {
+        let cid = Cid::new_v1(0x55, Code::Blake3_256.digest(b"cid"));
+        let mut map = BTreeMap::new();
+        map.insert("bytes".to_string(), Ipld::Bytes(vec![1, 0xff]));
+        map.insert(
+            "list".to_string(),
+            Ipld::List(vec![Ipld::Float(1.0), Ipld::Null, Ipld::List(vec![])]),
+        );
+        map.insert("link".to_string(), Ipld::Link(cid));
+        let ipld = Ipld::Map(map);
+        assert_eq!(
+            ipld.to_string(),
+            format!(
+                "{{\"link\": 42(\"{}\"), \"list\": [1.0, null, []], \"bytes\": h'01ff'}}",
+                cid
+            )
+        );
+        assert_eq!(
+            format!("{:#}", ipld),
+            format!(
+                "{{\n  \"link\": 42(\"{}\"),\n  \"list\": [\n    1.0,\n    null,\n    []\n  ],\n  \"bytes\": h'01ff'\n}}",
+                cid
+            )
+        );
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut ipld = Ipld::List(vec![Ipld::Integer(0), Ipld::Integer(1)]);
+        *ipld.get_mut(1).unwrap() = Ipld::Null;
+        assert_eq!(ipld.get(1).unwrap(), &Ipld::Null);
+        assert!(ipld.get_mut(2).is_err());
+        assert!(Ipld::Null.get_mut("a").is_err());
+    }
+
+    #[test]
+    fn test_index() {
+        let mut user = BTreeMap::new();
+        user.insert("name".to_string(), Ipld::String("a".into()));
+        let mut map = BTreeMap::new();
+        map.insert(
+            "users".to_string(),
+            Ipld::List(vec![Ipld::Null, Ipld::Map(user)]),
+        );
+        let mut ipld = Ipld::Map(map);
+        assert_eq!(ipld["users"][1]["name"], Ipld::String("a".into()));
+        ipld["users"][1]["name"] = Ipld::String("b".into());
+
+        assert_eq!(
+            ipld.get_path("users/1/name").unwrap(),
+            &Ipld::String("b".into())
+        );
+        assert_eq!(ipld.get_path("/users/0").unwrap(), &Ipld::Null);
+        assert!(ipld.get_path("/users/0/").is_err());
+        assert_eq!(ipld.get_path("").unwrap(), &ipld);
+        assert!(ipld.get_path("users/2").is_err());
+        assert!(ipld.get_path("users/x").is_err());
+        assert_eq!(
+            ipld.take_path("users/1/name").unwrap(),
+            Ipld::String("b".into())
+        );
+
+        let mut map = BTreeMap::new();
+        map.insert("a/b~".to_string(), Ipld::Integer(1));
+        map.insert("".to_string(), Ipld::Integer(2));
+        let ipld = Ipld::Map(map);
+        assert_eq!(ipld.get_path("/a~1b~0").unwrap(), &Ipld::Integer(1));
+        assert_eq!(ipld.get_path("/").unwrap(), &Ipld::Integer(2));
+        assert!(ipld.get_path("/a/b~").is_err());
+        assert!(ipld.get_path("/a~2").is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_panics() {
+        let _ = &Ipld::List(vec![])[0];
+    }
 }
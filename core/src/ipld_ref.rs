@@ -0,0 +1,113 @@
+//! Borrowed ipld representation.
+use alloc::{borrow::ToOwned, collections::BTreeMap, string::String, vec::Vec};
+
+use crate::cid::Cid;
+use crate::ipld::Ipld;
+
+/// An [`Ipld`] borrowing its strings and bytes from the encoded block.
+///
+/// Decoding into an `IpldRef` with [`DecodeRef`](crate::codec::DecodeRef) doesn't copy any
+/// strings or byte buffers, which makes inspecting large blocks cheap.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IpldRef<'a> {
+    /// Represents the absence of a value or the value undefined.
+    Null,
+    /// Represents a boolean value.
+    Bool(bool),
+    /// Represents an integer.
+    Integer(i128),
+    /// Represents a floating point value.
+    Float(f64),
+    /// Represents an UTF-8 string.
+    String(&'a str),
+    /// Represents a sequence of bytes.
+    Bytes(&'a [u8]),
+    /// Represents a list.
+    List(Vec<IpldRef<'a>>),
+    /// Represents a map of strings.
+    Map(BTreeMap<&'a str, IpldRef<'a>>),
+    /// Represents a link.
+    Link(Cid),
+}
+
+impl<'a> IpldRef<'a> {
+    /// Copies the borrowed data into an owned [`Ipld`].
+    pub fn to_ipld(&self) -> Ipld {
+        match self {
+            Self::Null => Ipld::Null,
+            Self::Bool(b) => Ipld::Bool(*b),
+            Self::Integer(i) => Ipld::Integer(*i),
+            Self::Float(f) => Ipld::Float(*f),
+            Self::String(s) => Ipld::String((*s).to_owned()),
+            Self::Bytes(b) => Ipld::Bytes(b.to_vec()),
+            Self::List(list) => Ipld::List(list.iter().map(Self::to_ipld).collect()),
+            Self::Map(map) => Ipld::Map(
+                map.iter()
+                    .map(|(k, v)| (String::from(*k), v.to_ipld()))
+                    .collect(),
+            ),
+            Self::Link(cid) => Ipld::Link(*cid),
+        }
+    }
+
+    /// Returns the references to other blocks.
+    pub fn references<E: Extend<Cid>>(&self, set: &mut E) {
+        match self {
+            Self::List(list) => list.iter().for_each(|ipld| ipld.references(set)),
+            Self::Map(map) => map.values().for_each(|ipld| ipld.references(set)),
+            Self::Link(cid) => set.extend(core::iter::once(*cid)),
+            _ => {}
+        }
+    }
+}
+
+impl<'a> From<&'a Ipld> for IpldRef<'a> {
+    fn from(ipld: &'a Ipld) -> Self {
+        match ipld {
+            Ipld::Null => Self::Null,
+            Ipld::Bool(b) => Self::Bool(*b),
+            Ipld::Integer(i) => Self::Integer(*i),
+            Ipld::Float(f) => Self::Float(*f),
+            Ipld::String(s) => Self::String(s),
+            Ipld::Bytes(b) => Self::Bytes(b),
+            Ipld::List(list) => Self::List(list.iter().map(Self::from).collect()),
+            Ipld::Map(map) => Self::Map(map.iter().map(|(k, v)| (&**k, v.into())).collect()),
+            Ipld::Link(cid) => Self::Link(*cid),
+        }
+    }
+}
+
+impl From<IpldRef<'_>> for Ipld {
+    fn from(ipld: IpldRef<'_>) -> Self {
+        ipld.to_ipld()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::multihash::{Code, MultihashDigest};
+    use alloc::vec;
+
+    #[test]
+    fn test_roundtrip() {
+        let cid = Cid::new_v1(0x55, Code::Blake3_256.digest(b"cid"));
+        let ipld = Ipld::Map(
+            [
+                ("bytes".into(), Ipld::Bytes(vec![1, 2, 3])),
+                (
+                    "list".into(),
+                    Ipld::List(vec![Ipld::Link(cid), Ipld::String("a".into())]),
+                ),
+                ("link".into(), Ipld::Link(cid)),
+            ]
+            .into(),
+        );
+        let ipld_ref = IpldRef::from(&ipld);
+        assert_eq!(ipld_ref.to_ipld(), ipld);
+
+        let mut refs = Vec::new();
+        ipld_ref.references(&mut refs);
+        assert_eq!(refs, vec![cid, cid]);
+    }
+}
@@ -7,13 +7,20 @@ extern crate alloc;
 
 pub mod codec;
 pub mod convert;
+pub mod dag;
+pub mod diag;
 pub mod error;
 pub mod ipld;
+pub mod ipld_ref;
 pub mod link;
+pub mod number;
+pub mod patch;
 pub mod raw;
 pub mod raw_value;
 #[cfg(feature = "serde-codec")]
 pub mod serde;
+pub mod token;
+pub mod typed_map;
 
 #[cfg(feature = "arb")]
 mod arb;
@@ -7,10 +7,15 @@ use core::{
     ops::Deref,
 };
 
+use alloc::vec::Vec;
+
 use crate::cid::Cid;
 use crate::codec::{Codec, Decode, Encode};
 use crate::error::Result;
+#[cfg(feature = "std")]
+use crate::error::{InvalidMultihash, UnsupportedMultihash};
 use crate::io::{Read, Seek, Write};
+use crate::multihash::MultihashDigest;
 
 /// Typed cid.
 #[derive(Debug)]
@@ -32,6 +37,42 @@ impl<T> Link<T> {
     pub fn cid(&self) -> &Cid {
         &self.cid
     }
+
+    /// Fetches the block with `get`, verifies it against the cid with the hashes of `H` and
+    /// decodes it with the codec of the cid.
+    #[cfg(feature = "std")]
+    pub fn load<C, H, F>(&self, get: F) -> Result<T>
+    where
+        C: Codec,
+        H: MultihashDigest<64>,
+        T: Decode<C>,
+        F: FnOnce(&Cid) -> Result<Vec<u8>>,
+    {
+        let codec = C::try_from(self.cid.codec())?;
+        let data = get(&self.cid)?;
+        let code = self.cid.hash().code();
+        let mh = H::try_from(code)
+            .map_err(|_| UnsupportedMultihash(code))?
+            .digest(&data);
+        if mh.digest() != self.cid.hash().digest() {
+            return Err(InvalidMultihash(mh.to_bytes()).into());
+        }
+        codec.decode(&data)
+    }
+
+    /// Encodes `value`, passes the block to `put` and returns a link to it.
+    pub fn store<C, H, F>(codec: C, hash: H, value: &T, put: F) -> Result<Self>
+    where
+        C: Codec,
+        H: MultihashDigest<64>,
+        T: Encode<C>,
+        F: FnOnce(Cid, Vec<u8>) -> Result<()>,
+    {
+        let data = codec.encode(value)?;
+        let cid = Cid::new_v1(codec.into(), hash.digest(&data));
+        put(cid, data)?;
+        Ok(Self::new(cid))
+    }
 }
 
 impl<T> fmt::Display for Link<T> {
@@ -0,0 +1,263 @@
+//! Numeric conversions.
+//!
+//! IPLD has an integer and a float kind. [`Number`] holds the value of either, and a
+//! [`NumberPolicy`] decides what happens when converting it to a Rust type of another kind or a
+//! smaller range. [`Ipld::to_number`] converts with an explicit policy, the `TryFrom<Ipld>`
+//! conversions of the number types and their dag-cbor and dag-json decoders use
+//! [`NumberPolicy::Strict`]. The codecs decode a [`Number`] as is, to convert it under another
+//! policy.
+use crate::error::{NumberConversionError, TypeErrorType};
+use crate::ipld::Ipld;
+
+/// An IPLD number, in the smallest representation holding it exactly.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Number {
+    /// An integer in the range of `i64`.
+    I64(i64),
+    /// An integer larger than `i64::MAX` in the range of `u64`.
+    U64(u64),
+    /// An integer outside of the range of `i64` and `u64`.
+    I128(i128),
+    /// A float.
+    F64(f64),
+}
+
+/// How a [`Number`] is converted when its kind or range doesn't match the target type.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NumberPolicy {
+    /// Integers only convert to integer types and floats only to `f64`. Out of range values are
+    /// an error. This is what the `TryFrom<Ipld>` conversions do.
+    #[default]
+    Strict,
+    /// Floats convert to the nearest integer, rounding half away from zero, and integers to the
+    /// nearest float. Out of range values, NaN and infinities are an error.
+    Round,
+    /// Never fails. Floats are truncated toward zero, out of range values saturate and NaN
+    /// becomes zero, like an `as` cast.
+    Lossy,
+}
+
+/// Rounds half away from zero. `f` must be finite and in the range of `i128`.
+fn round(f: f64) -> i128 {
+    let truncated = f as i128;
+    let fract = f - truncated as f64;
+    if fract >= 0.5 {
+        truncated + 1
+    } else if fract <= -0.5 {
+        truncated - 1
+    } else {
+        truncated
+    }
+}
+
+impl Number {
+    fn to_int(
+        self,
+        policy: NumberPolicy,
+        min: i128,
+        max: i128,
+        ty: &'static str,
+    ) -> Result<i128, NumberConversionError> {
+        let err = NumberConversionError(self, ty);
+        let int = match (self, policy) {
+            (Self::I64(i), _) => i as i128,
+            (Self::U64(u), _) => u as i128,
+            (Self::I128(i), _) => i,
+            (Self::F64(_), NumberPolicy::Strict) => return Err(err),
+            (Self::F64(f), NumberPolicy::Round) => {
+                // 2^127, the smallest float out of range.
+                if !f.is_finite() || f.abs() >= 170141183460469231731687303715884105728.0 {
+                    return Err(err);
+                }
+                round(f)
+            }
+            (Self::F64(f), NumberPolicy::Lossy) => f as i128,
+        };
+        if policy == NumberPolicy::Lossy {
+            Ok(int.clamp(min, max))
+        } else if (min..=max).contains(&int) {
+            Ok(int)
+        } else {
+            Err(err)
+        }
+    }
+
+    /// Converts to an `i64`.
+    pub fn to_i64(self, policy: NumberPolicy) -> Result<i64, NumberConversionError> {
+        self.to_int(policy, i64::MIN as i128, i64::MAX as i128, "i64")
+            .map(|i| i as i64)
+    }
+
+    /// Converts to an `u64`.
+    pub fn to_u64(self, policy: NumberPolicy) -> Result<u64, NumberConversionError> {
+        self.to_int(policy, 0, u64::MAX as i128, "u64")
+            .map(|i| i as u64)
+    }
+
+    /// Converts to an `i128`.
+    pub fn to_i128(self, policy: NumberPolicy) -> Result<i128, NumberConversionError> {
+        self.to_int(policy, i128::MIN, i128::MAX, "i128")
+    }
+
+    /// Converts to an `f64`.
+    pub fn to_f64(self, policy: NumberPolicy) -> Result<f64, NumberConversionError> {
+        match (self, policy) {
+            (Self::F64(f), _) => Ok(f),
+            (_, NumberPolicy::Strict) => Err(NumberConversionError(self, "f64")),
+            (Self::I64(i), _) => Ok(i as f64),
+            (Self::U64(u), _) => Ok(u as f64),
+            (Self::I128(i), _) => Ok(i as f64),
+        }
+    }
+
+    /// Converts to any [`FromNumber`] type.
+    pub fn to<T: FromNumber>(self, policy: NumberPolicy) -> Result<T, NumberConversionError> {
+        T::from_number(self, policy)
+    }
+}
+
+/// A type a [`Number`] converts to.
+pub trait FromNumber: Sized {
+    /// The ipld kind the type corresponds to.
+    const KIND: TypeErrorType;
+
+    /// Converts the number as directed by the policy.
+    fn from_number(number: Number, policy: NumberPolicy) -> Result<Self, NumberConversionError>;
+}
+
+macro_rules! from_number_int {
+    ($($ty:ident),*) => {
+        $(
+            impl FromNumber for $ty {
+                const KIND: TypeErrorType = TypeErrorType::Integer;
+
+                fn from_number(
+                    number: Number,
+                    policy: NumberPolicy,
+                ) -> Result<Self, NumberConversionError> {
+                    // `u128::MAX` doesn't fit, but ipld integers are `i128` anyway.
+                    let max = i128::try_from($ty::MAX).unwrap_or(i128::MAX);
+                    number
+                        .to_int(policy, $ty::MIN as i128, max, stringify!($ty))
+                        .map(|i| i as $ty)
+                }
+            }
+        )*
+    };
+}
+
+from_number_int!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl FromNumber for f64 {
+    const KIND: TypeErrorType = TypeErrorType::Float;
+
+    fn from_number(number: Number, policy: NumberPolicy) -> Result<Self, NumberConversionError> {
+        number.to_f64(policy)
+    }
+}
+
+impl From<i128> for Number {
+    fn from(i: i128) -> Self {
+        if let Ok(i) = i64::try_from(i) {
+            Self::I64(i)
+        } else if let Ok(u) = u64::try_from(i) {
+            Self::U64(u)
+        } else {
+            Self::I128(i)
+        }
+    }
+}
+
+impl From<f64> for Number {
+    fn from(f: f64) -> Self {
+        Self::F64(f)
+    }
+}
+
+impl From<Number> for Ipld {
+    fn from(number: Number) -> Self {
+        match number {
+            Number::I64(i) => Self::Integer(i as i128),
+            Number::U64(u) => Self::Integer(u as i128),
+            Number::I128(i) => Self::Integer(i),
+            Number::F64(f) => Self::Float(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use NumberPolicy::*;
+
+    #[test]
+    fn test_as_number() {
+        assert_eq!(Ipld::Integer(-1).as_number(), Some(Number::I64(-1)));
+        assert_eq!(
+            Ipld::Integer(u64::MAX as i128).as_number(),
+            Some(Number::U64(u64::MAX))
+        );
+        assert_eq!(
+            Ipld::Integer(i128::MIN).as_number(),
+            Some(Number::I128(i128::MIN))
+        );
+        assert_eq!(Ipld::Float(1.5).as_number(), Some(Number::F64(1.5)));
+        assert_eq!(Ipld::String("1".into()).as_number(), None);
+        assert_eq!(
+            Ipld::from(Number::U64(u64::MAX)),
+            Ipld::Integer(u64::MAX as i128)
+        );
+    }
+
+    #[test]
+    fn test_integers() {
+        let big = Number::U64(u64::MAX);
+        assert!(big.to_i64(Strict).is_err());
+        assert!(big.to_i64(Round).is_err());
+        assert_eq!(big.to_i64(Lossy).unwrap(), i64::MAX);
+        assert_eq!(big.to_u64(Strict).unwrap(), u64::MAX);
+        assert_eq!(Number::I64(-1).to_u64(Lossy).unwrap(), 0);
+        assert!(Number::I64(1).to_f64(Strict).is_err());
+        assert_eq!(Number::I64(1).to_f64(Round).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_to_number() {
+        assert_eq!(Ipld::Integer(255).to_number::<u8>(Strict).unwrap(), 255);
+        assert!(Ipld::Integer(256).to_number::<u8>(Strict).is_err());
+        assert_eq!(Ipld::Integer(256).to_number::<u8>(Lossy).unwrap(), 255);
+        assert_eq!(Ipld::Float(2.5).to_number::<i16>(Round).unwrap(), 3);
+        assert!(Ipld::Float(2.5).to_number::<i16>(Strict).is_err());
+        assert_eq!(Ipld::Integer(2).to_number::<f64>(Lossy).unwrap(), 2.0);
+        assert_eq!(
+            Ipld::Integer(i128::MAX).to_number::<u128>(Strict).unwrap(),
+            i128::MAX as u128
+        );
+        assert!(Ipld::Integer(-1).to_number::<u128>(Strict).is_err());
+        assert!(Ipld::String("1".into()).to_number::<u8>(Lossy).is_err());
+    }
+
+    #[test]
+    fn test_floats() {
+        let f = Number::F64(-2.5);
+        assert!(f.to_i64(Strict).is_err());
+        assert_eq!(f.to_i64(Round).unwrap(), -3);
+        assert_eq!(f.to_i64(Lossy).unwrap(), -2);
+        assert_eq!(Number::F64(0.49999999999999994).to_i64(Round).unwrap(), 0);
+        assert!(f.to_u64(Round).is_err());
+        assert_eq!(f.to_u64(Lossy).unwrap(), 0);
+        assert_eq!(f.to_f64(Strict).unwrap(), -2.5);
+
+        let nan = Number::F64(f64::NAN);
+        assert!(nan.to_i64(Round).is_err());
+        assert_eq!(nan.to_i64(Lossy).unwrap(), 0);
+        let inf = Number::F64(f64::INFINITY);
+        assert!(inf.to_i128(Round).is_err());
+        assert_eq!(inf.to_i128(Lossy).unwrap(), i128::MAX);
+        assert!(Number::F64(1e40).to_i128(Round).is_err());
+        assert_eq!(
+            Number::F64(1e18).to_i64(Round).unwrap(),
+            1_000_000_000_000_000_000
+        );
+    }
+}
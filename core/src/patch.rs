@@ -0,0 +1,534 @@
+//! Patching ipld, modelled after JSON Patch (RFC 6902).
+//!
+//! Operations address values by JSON pointers (RFC 6901): `/` separated map keys and list
+//! indices, where `~1` stands for a `/` and `~0` for a `~` in a key. The empty path is the whole
+//! value and `/` is the empty key. The leading `/` may be left out, like in [`Ipld::get_path`].
+//! When adding to a list, the last segment may be `-` to append.
+//!
+//! [`apply`] patches a single value, [`apply_dag`] follows links into other blocks, loading and
+//! storing them through closures.
+use alloc::{borrow::Cow, string::String, vec::Vec};
+
+use crate::cid::Cid;
+use crate::dag::Blocks;
+use crate::error::{Error, PatchError};
+use crate::ipld::Ipld;
+
+/// A patch operation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operation {
+    /// Inserts a value into a list, or adds or replaces a map entry.
+    Add {
+        /// Where to add the value.
+        path: String,
+        /// The value.
+        value: Ipld,
+    },
+    /// Removes a value.
+    Remove {
+        /// The value to remove.
+        path: String,
+    },
+    /// Replaces an existing value.
+    Replace {
+        /// The value to replace.
+        path: String,
+        /// The new value.
+        value: Ipld,
+    },
+    /// Removes a value and adds it at another path.
+    Move {
+        /// The value to move.
+        from: String,
+        /// Where to add the value.
+        path: String,
+    },
+    /// Adds a copy of a value at another path.
+    Copy {
+        /// The value to copy.
+        from: String,
+        /// Where to add the copy.
+        path: String,
+    },
+    /// Checks that a value is equal to the given one.
+    Test {
+        /// The value to check.
+        path: String,
+        /// The expected value.
+        value: Ipld,
+    },
+}
+
+const NOT_FOUND: &str = "path not found";
+const INVALID_INDEX: &str = "invalid list index";
+const INVALID_ESCAPE: &str = "invalid escape";
+
+/// Escapes a map key for use as a path segment.
+pub fn escape(key: &str) -> Cow<'_, str> {
+    if key.contains(['~', '/']) {
+        Cow::Owned(key.replace('~', "~0").replace('/', "~1"))
+    } else {
+        Cow::Borrowed(key)
+    }
+}
+
+fn unescape(segment: &str) -> Result<Cow<'_, str>, &'static str> {
+    if !segment.contains('~') {
+        return Ok(Cow::Borrowed(segment));
+    }
+    let mut key = String::with_capacity(segment.len());
+    let mut chars = segment.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '~' => match chars.next() {
+                Some('0') => key.push('~'),
+                Some('1') => key.push('/'),
+                _ => return Err(INVALID_ESCAPE),
+            },
+            c => key.push(c),
+        }
+    }
+    Ok(Cow::Owned(key))
+}
+
+/// Splits a path into its unescaped segments.
+pub(crate) fn segments(path: &str) -> Result<Vec<Cow<'_, str>>, &'static str> {
+    if path.is_empty() {
+        return Ok(Vec::new());
+    }
+    let path = path.strip_prefix('/').unwrap_or(path);
+    path.split('/').map(unescape).collect()
+}
+
+/// Why an operation failed.
+enum Failure {
+    Patch(&'static str),
+    Dag(Error),
+}
+
+impl From<&'static str> for Failure {
+    fn from(reason: &'static str) -> Self {
+        Self::Patch(reason)
+    }
+}
+
+struct Patcher<'a> {
+    blocks: Blocks<'a>,
+}
+
+impl Patcher<'_> {
+    fn load(&mut self, cid: &Cid) -> Result<Option<Ipld>, Failure> {
+        self.blocks.load(cid).map_err(Failure::Dag)
+    }
+
+    fn store(&mut self, ipld: Ipld) -> Result<Cid, Failure> {
+        self.blocks.store(ipld).map_err(Failure::Dag)
+    }
+
+    /// Calls `f` with the value at `segments`. Links on the way are loaded, and the changed blocks
+    /// are stored and linked to instead. With `expand`, a link at the end of the path is loaded as
+    /// well.
+    fn modify<T, F>(
+        &mut self,
+        ipld: &mut Ipld,
+        segments: &[Cow<str>],
+        expand: bool,
+        f: F,
+    ) -> Result<T, Failure>
+    where
+        F: FnOnce(&mut Ipld) -> Result<T, Failure>,
+    {
+        if let Ipld::Link(cid) = ipld {
+            if expand || !segments.is_empty() {
+                if let Some(mut block) = self.load(cid)? {
+                    let res = self.modify(&mut block, segments, expand, f)?;
+                    *ipld = Ipld::Link(self.store(block)?);
+                    return Ok(res);
+                }
+            }
+        }
+        match segments.split_first() {
+            None => f(ipld),
+            Some((segment, rest)) => {
+                let child = ipld.get_mut(&**segment).map_err(|_| NOT_FOUND)?;
+                self.modify(child, rest, expand, f)
+            }
+        }
+    }
+
+    /// Returns a copy of the value at `segments`, loading the links on the way.
+    fn get(&mut self, ipld: &Ipld, segments: &[Cow<str>]) -> Result<Ipld, Failure> {
+        match (segments.split_first(), ipld) {
+            (None, ipld) => Ok(ipld.clone()),
+            (Some(_), Ipld::Link(cid)) => match self.load(cid)? {
+                Some(block) => self.get(&block, segments),
+                None => Err(NOT_FOUND.into()),
+            },
+            (Some((segment, rest)), ipld) => {
+                let child = ipld.get(&**segment).map_err(|_| NOT_FOUND)?;
+                self.get(child, rest)
+            }
+        }
+    }
+
+    fn add(&mut self, ipld: &mut Ipld, path: &[Cow<str>], value: Ipld) -> Result<(), Failure> {
+        let (last, parent) = match path.split_last() {
+            Some(split) => split,
+            None => {
+                *ipld = value;
+                return Ok(());
+            }
+        };
+        self.modify(ipld, parent, true, |parent| {
+            match parent {
+                Ipld::Map(map) => {
+                    map.insert(last.clone().into_owned(), value);
+                }
+                Ipld::List(list) => {
+                    let index = if last == "-" {
+                        list.len()
+                    } else {
+                        last.parse()
+                            .ok()
+                            .filter(|index| *index <= list.len())
+                            .ok_or(INVALID_INDEX)?
+                    };
+                    list.insert(index, value);
+                }
+                _ => return Err(NOT_FOUND.into()),
+            }
+            Ok(())
+        })
+    }
+
+    fn remove(&mut self, ipld: &mut Ipld, path: &[Cow<str>]) -> Result<Ipld, Failure> {
+        let (last, parent) = path.split_last().ok_or("can't remove the root")?;
+        self.modify(ipld, parent, true, |parent| match parent {
+            Ipld::Map(map) => Ok(map.remove(&**last).ok_or(NOT_FOUND)?),
+            Ipld::List(list) => {
+                let index = last
+                    .parse()
+                    .ok()
+                    .filter(|index| *index < list.len())
+                    .ok_or(INVALID_INDEX)?;
+                Ok(list.remove(index))
+            }
+            _ => Err(NOT_FOUND.into()),
+        })
+    }
+
+    fn apply_one(&mut self, ipld: &mut Ipld, op: &Operation) -> Result<(), Failure> {
+        match op {
+            Operation::Add { path, value } => self.add(ipld, &segments(path)?, value.clone()),
+            Operation::Remove { path } => self.remove(ipld, &segments(path)?).map(drop),
+            Operation::Replace { path, value } => {
+                self.modify(ipld, &segments(path)?, false, |ipld| {
+                    *ipld = value.clone();
+                    Ok(())
+                })
+            }
+            Operation::Move { from, path } => {
+                let (from, path) = (segments(from)?, segments(path)?);
+                if path.starts_with(&from) && from != path {
+                    return Err("can't move a value into itself".into());
+                }
+                let value = self.remove(ipld, &from)?;
+                self.add(ipld, &path, value)
+            }
+            Operation::Copy { from, path } => {
+                let value = self.get(ipld, &segments(from)?)?;
+                self.add(ipld, &segments(path)?, value)
+            }
+            Operation::Test { path, value } => {
+                if self.get(ipld, &segments(path)?)? == *value {
+                    Ok(())
+                } else {
+                    Err("test failed".into())
+                }
+            }
+        }
+    }
+
+    fn apply(&mut self, ipld: &Ipld, ops: &[Operation]) -> Result<Ipld, (usize, Failure)> {
+        let mut patched = ipld.clone();
+        for (index, op) in ops.iter().enumerate() {
+            self.apply_one(&mut patched, op)
+                .map_err(|failure| (index, failure))?;
+        }
+        Ok(patched)
+    }
+}
+
+/// Applies the operations in order and returns the patched value.
+///
+/// Either all operations succeed or the error of the first failing one is returned.
+pub fn apply(ipld: &Ipld, ops: &[Operation]) -> Result<Ipld, PatchError> {
+    Patcher {
+        blocks: Blocks::default(),
+    }
+    .apply(ipld, ops)
+    .map_err(|(index, failure)| match failure {
+        Failure::Patch(reason) => PatchError { index, reason },
+        Failure::Dag(_) => unreachable!("no blocks are loaded"),
+    })
+}
+
+/// Applies the operations to the dag rooted at `ipld`, see [`apply`].
+///
+/// Paths continue into linked blocks, which are loaded with `load`. Every operation that changes a
+/// linked block passes the new block to `store`, together with every block on the way to it, and
+/// replaces the links with the returned CIDs. A link at the end of a path is the value itself, so
+/// replacing or removing it doesn't load the block. Patch failures are returned as a
+/// [`PatchError`], errors of `load` and `store` as they are.
+pub fn apply_dag<L, S>(
+    ipld: &Ipld,
+    ops: &[Operation],
+    mut load: L,
+    mut store: S,
+) -> crate::error::Result<Ipld>
+where
+    L: FnMut(&Cid) -> crate::error::Result<Ipld>,
+    S: FnMut(Ipld) -> crate::error::Result<Cid>,
+{
+    Patcher {
+        blocks: Blocks::new(&mut load, &mut store),
+    }
+    .apply(ipld, ops)
+    .map_err(|(index, failure)| match failure {
+        Failure::Patch(reason) => Error::msg(PatchError { index, reason }),
+        Failure::Dag(err) => err,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{collections::BTreeMap, vec};
+
+    fn map(entries: Vec<(&str, Ipld)>) -> Ipld {
+        Ipld::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.into(), value))
+                .collect::<BTreeMap<_, _>>(),
+        )
+    }
+
+    fn int(i: i128) -> Ipld {
+        Ipld::Integer(i)
+    }
+
+    #[test]
+    fn test_apply() {
+        let ipld = map(vec![
+            ("a", Ipld::List(vec![int(1), int(2)])),
+            ("b", map(vec![("c", int(3))])),
+        ]);
+        let ops = [
+            Operation::Test {
+                path: "b/c".into(),
+                value: int(3),
+            },
+            Operation::Add {
+                path: "a/0".into(),
+                value: int(0),
+            },
+            Operation::Add {
+                path: "a/-".into(),
+                value: int(3),
+            },
+            Operation::Remove { path: "a/1".into() },
+            Operation::Replace {
+                path: "b/c".into(),
+                value: int(4),
+            },
+            Operation::Move {
+                from: "b/c".into(),
+                path: "d".into(),
+            },
+            Operation::Copy {
+                from: "d".into(),
+                path: "b/e".into(),
+            },
+        ];
+        assert_eq!(
+            apply(&ipld, &ops).unwrap(),
+            map(vec![
+                ("a", Ipld::List(vec![int(0), int(2), int(3)])),
+                ("b", map(vec![("e", int(4))])),
+                ("d", int(4)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let ipld = map(vec![("a", Ipld::List(vec![int(1)]))]);
+        let fails = |op: Operation| apply(&ipld, &[op]).unwrap_err().reason;
+        assert_eq!(
+            fails(Operation::Test {
+                path: "a/0".into(),
+                value: int(2)
+            }),
+            "test failed"
+        );
+        assert_eq!(
+            fails(Operation::Add {
+                path: "a/2".into(),
+                value: int(2)
+            }),
+            INVALID_INDEX
+        );
+        assert_eq!(fails(Operation::Remove { path: "b".into() }), NOT_FOUND);
+        assert_eq!(
+            fails(Operation::Replace {
+                path: "x/y".into(),
+                value: Ipld::Null
+            }),
+            NOT_FOUND
+        );
+        assert_eq!(
+            fails(Operation::Move {
+                from: "a".into(),
+                path: "a/0".into()
+            }),
+            "can't move a value into itself"
+        );
+
+        let err = apply(
+            &ipld,
+            &[
+                Operation::Remove { path: "a".into() },
+                Operation::Remove { path: "a".into() },
+            ],
+        )
+        .unwrap_err();
+        assert_eq!(err.index, 1);
+    }
+
+    #[test]
+    fn test_escaping() {
+        let ipld = map(vec![
+            ("a/b", int(1)),
+            ("m~n", int(2)),
+            ("", map(vec![("", int(3))])),
+            ("a", map(vec![("", map(vec![("b", int(4))]))])),
+        ]);
+        let test = |path: &str, value: i128| Operation::Test {
+            path: path.into(),
+            value: int(value),
+        };
+        let ops = [
+            test("/a~1b", 1),
+            test("/m~0n", 2),
+            test("//", 3),
+            test("/a//b", 4),
+            Operation::Add {
+                path: alloc::format!("/{}", escape("x/~y")),
+                value: int(5),
+            },
+        ];
+        let patched = apply(&ipld, &ops).unwrap();
+        assert_eq!(patched.get("x/~y").unwrap(), &int(5));
+        assert_eq!(escape("x/~y"), "x~1~0y");
+
+        let fails = |op: Operation| apply(&ipld, &[op]).unwrap_err().reason;
+        assert_eq!(fails(test("/a/b", 4)), NOT_FOUND);
+        assert_eq!(fails(test("/a~2b", 1)), INVALID_ESCAPE);
+        assert_eq!(fails(test("/a~", 1)), INVALID_ESCAPE);
+    }
+
+    #[test]
+    fn test_apply_dag() {
+        use crate::multihash::{Code, MultihashDigest};
+        use core::cell::RefCell;
+
+        let blocks = RefCell::new(BTreeMap::new());
+        let insert = |ipld: Ipld| {
+            let hash = Code::Blake3_256.digest(alloc::format!("{:?}", ipld).as_bytes());
+            let cid = Cid::new_v1(0x71, hash);
+            blocks.borrow_mut().insert(cid, ipld);
+            cid
+        };
+        let child = insert(map(vec![("x", int(1))]));
+        let other = insert(int(0));
+        let root = map(vec![
+            ("child", Ipld::Link(child)),
+            ("other", Ipld::Link(other)),
+        ]);
+        let load = |cid: &Cid| Ok(blocks.borrow()[cid].clone());
+
+        let ops = [
+            Operation::Replace {
+                path: "/child/x".into(),
+                value: int(2),
+            },
+            Operation::Add {
+                path: "/child/y".into(),
+                value: int(3),
+            },
+            Operation::Copy {
+                from: "/child/y".into(),
+                path: "/copy".into(),
+            },
+            Operation::Test {
+                path: "/child/x".into(),
+                value: int(2),
+            },
+        ];
+        let mut stored = Vec::new();
+        let patched = apply_dag(&root, &ops, load, |ipld| {
+            stored.push(ipld.clone());
+            Ok(insert(ipld))
+        })
+        .unwrap();
+        let patched_child = insert(map(vec![("x", int(2)), ("y", int(3))]));
+        assert_eq!(
+            patched,
+            map(vec![
+                ("child", Ipld::Link(patched_child)),
+                ("copy", int(3)),
+                ("other", Ipld::Link(other)),
+            ])
+        );
+        assert_eq!(
+            stored,
+            vec![
+                map(vec![("x", int(2))]),
+                map(vec![("x", int(2)), ("y", int(3))]),
+            ]
+        );
+
+        // A link at the end of the path is replaced without loading it.
+        let ops = [Operation::Replace {
+            path: "/other".into(),
+            value: int(1),
+        }];
+        let patched = apply_dag(&root, &ops, |_| panic!("load"), |_| panic!("store")).unwrap();
+        assert_eq!(patched.get("other").unwrap(), &int(1));
+
+        let ops = [Operation::Remove {
+            path: "/child/z".into(),
+        }];
+        let err = apply_dag(&root, &ops, load, |_| panic!("store")).unwrap_err();
+        assert_eq!(err.downcast_ref::<PatchError>().unwrap().reason, NOT_FOUND);
+        let err = apply_dag(
+            &root,
+            &ops,
+            |_| Err(Error::msg("missing")),
+            |_| panic!("store"),
+        )
+        .unwrap_err();
+        assert!(err.downcast_ref::<PatchError>().is_none());
+    }
+
+    #[test]
+    fn test_root() {
+        let ops = [Operation::Add {
+            path: "".into(),
+            value: int(1),
+        }];
+        assert_eq!(apply(&Ipld::Null, &ops).unwrap(), int(1));
+        assert!(apply(&int(1), &[Operation::Remove { path: "/".into() }]).is_err());
+    }
+}
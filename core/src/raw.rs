@@ -3,10 +3,11 @@ use alloc::{boxed::Box, vec, vec::Vec};
 use core::{convert::TryFrom, iter::Extend};
 
 use crate::cid::Cid;
-use crate::codec::{Codec, Decode, Encode, References};
+use crate::codec::{Codec, Decode, Encode, References, Tokens};
 use crate::error::{Result, UnsupportedCodec};
 use crate::io::{Read, Seek, Write};
 use crate::ipld::Ipld;
+use crate::token::Token;
 
 /// Raw codec.
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
@@ -34,12 +35,6 @@ impl Encode<RawCodec> for [u8] {
     }
 }
 
-impl Encode<RawCodec> for Box<[u8]> {
-    fn encode<W: Write>(&self, _: RawCodec, w: &mut W) -> Result<()> {
-        w.write_all(&self[..]).map_err(anyhow::Error::msg)
-    }
-}
-
 impl Encode<RawCodec> for Vec<u8> {
     fn encode<W: Write>(&self, _: RawCodec, w: &mut W) -> Result<()> {
         w.write_all(&self[..]).map_err(anyhow::Error::msg)
@@ -87,6 +82,16 @@ impl<T> References<RawCodec> for T {
     }
 }
 
+impl Tokens<RawCodec> for Ipld {
+    fn tokens<R: Read + Seek, F: FnMut(Token) -> Result<()>>(
+        c: RawCodec,
+        r: &mut R,
+        f: &mut F,
+    ) -> Result<()> {
+        Self::decode(c, r)?.into_tokens(f)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -105,4 +110,17 @@ mod tests {
         let ipld2: Ipld = RawCodec.decode(&bytes).unwrap();
         assert_eq!(ipld, ipld2);
     }
+
+    #[test]
+    fn test_pointers() {
+        use alloc::{borrow::Cow, rc::Rc, sync::Arc};
+
+        let data: &[u8] = &[0, 1, 2, 3];
+        assert_eq!(RawCodec.encode(&Box::<[u8]>::from(data)).unwrap(), data);
+        assert_eq!(RawCodec.encode(&Rc::<[u8]>::from(data)).unwrap(), data);
+        assert_eq!(RawCodec.encode(&Arc::<[u8]>::from(data)).unwrap(), data);
+        assert_eq!(RawCodec.encode(&Cow::Borrowed(data)).unwrap(), data);
+        let decoded: Cow<[u8]> = RawCodec.decode(data).unwrap();
+        assert_eq!(&*decoded, data);
+    }
 }
@@ -0,0 +1,107 @@
+//! Tokens of a streaming decoder.
+//!
+//! The codecs can decode a block into a sequence of [`Token`]s passed to a callback instead of
+//! building an [`Ipld`]. This makes it possible to process blocks that are too large to hold in
+//! memory as a tree, e.g. to extract a single field or to count the links.
+use alloc::{string::String, vec::Vec};
+
+use crate::cid::Cid;
+use crate::ipld::Ipld;
+
+/// An event emitted while decoding.
+///
+/// Lists are emitted as `ListStart`, the tokens of every item and `ListEnd`. Maps are emitted as
+/// `MapStart`, a `Key` followed by the tokens of its value for every entry, and `MapEnd`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    /// A null value.
+    Null,
+    /// A boolean value.
+    Bool(bool),
+    /// An integer.
+    Integer(i128),
+    /// A floating point value.
+    Float(f64),
+    /// An UTF-8 string.
+    String(String),
+    /// A sequence of bytes.
+    Bytes(Vec<u8>),
+    /// A link.
+    Link(Cid),
+    /// The start of a list.
+    ListStart,
+    /// The end of a list.
+    ListEnd,
+    /// The start of a map.
+    MapStart,
+    /// The key of the next map entry.
+    Key(String),
+    /// The end of a map.
+    MapEnd,
+}
+
+impl Ipld {
+    /// Passes the tokens of the value to `f`, in the order a streaming decoder emits them.
+    /// Stops at the first error returned by `f`.
+    pub fn into_tokens<E, F: FnMut(Token) -> Result<(), E>>(self, f: &mut F) -> Result<(), E> {
+        match self {
+            Self::Null => f(Token::Null),
+            Self::Bool(b) => f(Token::Bool(b)),
+            Self::Integer(i) => f(Token::Integer(i)),
+            Self::Float(float) => f(Token::Float(float)),
+            Self::String(s) => f(Token::String(s)),
+            Self::Bytes(b) => f(Token::Bytes(b)),
+            Self::Link(cid) => f(Token::Link(cid)),
+            Self::List(list) => {
+                f(Token::ListStart)?;
+                for ipld in list {
+                    ipld.into_tokens(f)?;
+                }
+                f(Token::ListEnd)
+            }
+            Self::Map(map) => {
+                f(Token::MapStart)?;
+                for (key, ipld) in map {
+                    f(Token::Key(key))?;
+                    ipld.into_tokens(f)?;
+                }
+                f(Token::MapEnd)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{collections::BTreeMap, vec};
+
+    #[test]
+    fn test_into_tokens() {
+        let ipld = Ipld::Map(BTreeMap::from([
+            ("a".into(), Ipld::List(vec![Ipld::Integer(1), Ipld::Null])),
+            ("b".into(), Ipld::Map(BTreeMap::new())),
+        ]));
+        let mut tokens = Vec::new();
+        ipld.into_tokens(&mut |token| {
+            tokens.push(token);
+            Ok::<_, ()>(())
+        })
+        .unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::MapStart,
+                Token::Key("a".into()),
+                Token::ListStart,
+                Token::Integer(1),
+                Token::Null,
+                Token::ListEnd,
+                Token::Key("b".into()),
+                Token::MapStart,
+                Token::MapEnd,
+                Token::MapEnd,
+            ]
+        );
+    }
+}
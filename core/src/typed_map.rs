@@ -0,0 +1,104 @@
+//! Maps with typed keys and values.
+use alloc::{collections::BTreeMap, string::ToString};
+use core::fmt::Display;
+use core::ops::{Deref, DerefMut};
+
+use crate::ipld::Ipld;
+
+/// A map with keys of type `K` and values of type `V`.
+///
+/// IPLD maps only have string keys. A `TypedMap` is encoded as such a map, the keys are converted
+/// with `Display` and parsed back with `FromStr`. Decoding rejects keys that don't parse or that
+/// wouldn't format to the same string again, so a decoded map always encodes to the same bytes.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TypedMap<K, V>(BTreeMap<K, V>);
+
+impl<K: Ord, V> TypedMap<K, V> {
+    /// Creates an empty map.
+    pub fn new() -> Self {
+        Self(BTreeMap::new())
+    }
+
+    /// Returns the underlying map.
+    pub fn into_inner(self) -> BTreeMap<K, V> {
+        self.0
+    }
+}
+
+impl<K: Ord, V> Default for TypedMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Deref for TypedMap<K, V> {
+    type Target = BTreeMap<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<K, V> DerefMut for TypedMap<K, V> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<K, V> From<BTreeMap<K, V>> for TypedMap<K, V> {
+    fn from(map: BTreeMap<K, V>) -> Self {
+        Self(map)
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for TypedMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl<K, V> IntoIterator for TypedMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = alloc::collections::btree_map::IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a TypedMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = alloc::collections::btree_map::Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<K: Display, V: Into<Ipld>> From<TypedMap<K, V>> for Ipld {
+    fn from(map: TypedMap<K, V>) -> Self {
+        Ipld::Map(
+            map.into_iter()
+                .map(|(key, value)| (key.to_string(), value.into()))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_into_ipld() {
+        let map: TypedMap<u32, bool> = [(10, true), (9, false)].into_iter().collect();
+        assert_eq!(map.keys().copied().collect::<alloc::vec::Vec<_>>(), [9, 10]);
+        assert_eq!(
+            Ipld::from(map),
+            Ipld::Map(BTreeMap::from([
+                ("10".into(), Ipld::Bool(true)),
+                ("9".into(), Ipld::Bool(false)),
+            ]))
+        );
+    }
+}
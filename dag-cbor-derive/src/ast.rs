@@ -28,7 +28,9 @@ pub enum SchemaType {
 pub struct Struct {
     pub name: syn::Ident,
     pub generics: Option<syn::Generics>,
+    pub bound: Option<Vec<syn::WherePredicate>>,
     pub rename: Option<String>,
+    pub value: Option<i128>,
     pub fields: Vec<StructField>,
     pub repr: StructRepr,
     pub pat: TokenStreamEq,
@@ -40,6 +42,8 @@ pub struct StructField {
     pub name: syn::Member,
     pub rename: Option<String>,
     pub default: Option<Box<syn::Expr>>,
+    pub skip: bool,
+    pub optional: bool,
     pub binding: syn::Ident,
 }
 
@@ -55,6 +59,7 @@ pub enum StructRepr {
 pub struct Union {
     pub name: syn::Ident,
     pub generics: syn::Generics,
+    pub bound: Option<Vec<syn::WherePredicate>>,
     pub variants: Vec<Struct>,
     pub repr: UnionRepr,
 }
@@ -5,9 +5,13 @@ mod kw {
     use syn::custom_keyword;
 
     custom_keyword!(repr);
+    custom_keyword!(bound);
+    custom_keyword!(rename_all);
 
     custom_keyword!(rename);
     custom_keyword!(default);
+    custom_keyword!(skip);
+    custom_keyword!(value);
 }
 
 #[derive(Debug)]
@@ -42,15 +46,33 @@ impl<K: Parse, V: Parse> Parse for Attr<K, V> {
     }
 }
 
+/// An integer literal, optionally negative.
+#[derive(Debug)]
+pub struct IntValue(pub i128);
+
+impl Parse for IntValue {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let negative = input.parse::<Option<syn::Token![-]>>()?.is_some();
+        let value: i128 = input.parse::<syn::LitInt>()?.base10_parse()?;
+        Ok(Self(if negative { -value } else { value }))
+    }
+}
+
 #[derive(Debug)]
 pub enum DeriveAttr {
     Repr(Attr<kw::repr, syn::LitStr>),
+    Bound(Attr<kw::bound, syn::LitStr>),
+    RenameAll(Attr<kw::rename_all, syn::LitStr>),
 }
 
 impl Parse for DeriveAttr {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         if input.peek(kw::repr) {
             Ok(DeriveAttr::Repr(input.parse()?))
+        } else if input.peek(kw::bound) {
+            Ok(DeriveAttr::Bound(input.parse()?))
+        } else if input.peek(kw::rename_all) {
+            Ok(DeriveAttr::RenameAll(input.parse()?))
         } else {
             Err(syn::Error::new(input.span(), "unknown attribute"))
         }
@@ -61,14 +83,25 @@ impl Parse for DeriveAttr {
 pub enum FieldAttr {
     Rename(Attr<kw::rename, syn::LitStr>),
     Default(Attr<kw::default, Box<syn::Expr>>),
+    DefaultTrait,
+    Skip,
+    Value(Attr<kw::value, IntValue>),
 }
 
 impl Parse for FieldAttr {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         if input.peek(kw::rename) {
             Ok(FieldAttr::Rename(input.parse()?))
-        } else if input.peek(kw::default) {
+        } else if input.peek(kw::default) && input.peek2(syn::Token![=]) {
             Ok(FieldAttr::Default(input.parse()?))
+        } else if input.peek(kw::default) {
+            input.parse::<kw::default>()?;
+            Ok(FieldAttr::DefaultTrait)
+        } else if input.peek(kw::skip) {
+            input.parse::<kw::skip>()?;
+            Ok(FieldAttr::Skip)
+        } else if input.peek(kw::value) {
+            Ok(FieldAttr::Value(input.parse()?))
         } else {
             Err(syn::Error::new(input.span(), "unknown attribute"))
         }
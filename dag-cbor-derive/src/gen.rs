@@ -1,16 +1,45 @@
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 
 use crate::ast::*;
 use proc_macro2::TokenStream;
 use quote::quote;
 
+/// Adds the `bound` attribute to the where clause, or `T: #trait_name` for every type parameter if
+/// there is none.
+fn add_bounds(
+    generics: &syn::Generics,
+    bound: Option<&Vec<syn::WherePredicate>>,
+    trait_name: &TokenStream,
+) -> syn::Generics {
+    let mut generics = generics.clone();
+    let predicates: Vec<syn::WherePredicate> = match bound {
+        Some(bound) => bound.clone(),
+        None => generics
+            .type_params()
+            .map(|param| {
+                let ident = &param.ident;
+                syn::parse_quote!(#ident: #trait_name)
+            })
+            .collect(),
+    };
+    generics.make_where_clause().predicates.extend(predicates);
+    generics
+}
+
 pub fn gen_encode(ast: &SchemaType, libipld: &syn::Ident) -> TokenStream {
-    let (ident, generics, body) = match ast {
-        SchemaType::Struct(s) => (&s.name, s.generics.as_ref().unwrap(), gen_encode_struct(s)),
-        SchemaType::Union(u) => (&u.name, &u.generics, gen_encode_union(u)),
+    let (ident, generics, bound, body) = match ast {
+        SchemaType::Struct(s) => (
+            &s.name,
+            s.generics.as_ref().unwrap(),
+            s.bound.as_ref(),
+            gen_encode_struct(s),
+        ),
+        SchemaType::Union(u) => (&u.name, &u.generics, u.bound.as_ref(), gen_encode_union(u)),
     };
-    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
     let trait_name = quote!(#libipld::codec::Encode<#libipld::cbor::DagCborCodec>);
+    let generics = add_bounds(generics, bound, &trait_name);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
     quote! {
         impl #impl_generics #trait_name for #ident #ty_generics #where_clause {
@@ -28,13 +57,95 @@ pub fn gen_encode(ast: &SchemaType, libipld: &syn::Ident) -> TokenStream {
     }
 }
 
+pub fn gen_json(ast: &SchemaType, libipld: &syn::Ident) -> TokenStream {
+    let (ident, generics, bound, to_ipld, from_ipld) = match ast {
+        SchemaType::Struct(s) => (
+            &s.name,
+            s.generics.as_ref().unwrap(),
+            s.bound.as_ref(),
+            gen_to_ipld_struct(s),
+            gen_from_ipld_struct(s),
+        ),
+        SchemaType::Union(u) => (
+            &u.name,
+            &u.generics,
+            u.bound.as_ref(),
+            gen_to_ipld_union(u),
+            gen_from_ipld_union(u),
+        ),
+    };
+    let json = quote!(#libipld::json::DagJsonCodec);
+    let to_trait = quote!(#libipld::json::ToIpld);
+    let from_trait = quote!(#libipld::json::FromIpld);
+    let to_generics = add_bounds(generics, bound, &to_trait);
+    let from_generics = add_bounds(generics, bound, &from_trait);
+    let mut encode = generics.clone();
+    encode
+        .make_where_clause()
+        .predicates
+        .push(syn::parse_quote!(Self: #to_trait));
+    let mut decode = generics.clone();
+    decode
+        .make_where_clause()
+        .predicates
+        .push(syn::parse_quote!(Self: #from_trait));
+    let (impl_generics, ty_generics, to_where) = to_generics.split_for_impl();
+    let (_, _, from_where) = from_generics.split_for_impl();
+    let (_, _, encode_where) = encode.split_for_impl();
+    let (_, _, decode_where) = decode.split_for_impl();
+
+    quote! {
+        impl #impl_generics #to_trait for #ident #ty_generics #to_where {
+            fn to_ipld(&self) -> #libipld::Result<#libipld::Ipld> {
+                use #libipld::json::ToIpld;
+                use #libipld::Ipld;
+                use std::collections::BTreeMap;
+                #to_ipld
+            }
+        }
+
+        impl #impl_generics #from_trait for #ident #ty_generics #from_where {
+            fn from_ipld(ipld: #libipld::Ipld) -> #libipld::Result<Self> {
+                use #libipld::cbor::error::{LengthOutOfRange, MissingKey, UnexpectedKey};
+                use #libipld::error::{Result, TypeError, TypeErrorType};
+                use #libipld::json::FromIpld;
+                use #libipld::Ipld;
+                #from_ipld
+            }
+        }
+
+        impl #impl_generics #libipld::codec::Encode<#json> for #ident #ty_generics #encode_where {
+            fn encode<W: std::io::Write>(&self, c: #json, w: &mut W) -> #libipld::Result<()> {
+                use #libipld::codec::Encode;
+                #to_trait::to_ipld(self)?.encode(c, w)
+            }
+        }
+
+        impl #impl_generics #libipld::codec::Decode<#json> for #ident #ty_generics #decode_where {
+            fn decode<R: std::io::Read + std::io::Seek>(
+                c: #json,
+                r: &mut R,
+            ) -> #libipld::Result<Self> {
+                use #libipld::codec::Decode;
+                #from_trait::from_ipld(#libipld::Ipld::decode(c, r)?)
+            }
+        }
+    }
+}
+
 pub fn gen_decode(ast: &SchemaType, libipld: &syn::Ident) -> TokenStream {
-    let (ident, generics, body) = match ast {
-        SchemaType::Struct(s) => (&s.name, s.generics.as_ref().unwrap(), gen_decode_struct(s)),
-        SchemaType::Union(u) => (&u.name, &u.generics, gen_decode_union(u)),
+    let (ident, generics, bound, body) = match ast {
+        SchemaType::Struct(s) => (
+            &s.name,
+            s.generics.as_ref().unwrap(),
+            s.bound.as_ref(),
+            gen_decode_struct(s),
+        ),
+        SchemaType::Union(u) => (&u.name, &u.generics, u.bound.as_ref(), gen_decode_union(u)),
     };
-    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
     let trait_name = quote!(#libipld::codec::Decode<#libipld::cbor::DagCborCodec>);
+    let generics = add_bounds(generics, bound, &trait_name);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
     quote! {
         impl #impl_generics #trait_name for #ident #ty_generics #where_clause {
@@ -47,6 +158,7 @@ pub fn gen_decode(ast: &SchemaType, libipld: &syn::Ident) -> TokenStream {
                 use #libipld::cbor::error::{LengthOutOfRange, MissingKey, UnexpectedCode, UnexpectedKey};
                 use #libipld::codec::Decode;
                 use #libipld::error::Result;
+                use #libipld::raw_value::IgnoredAny;
                 use std::io::SeekFrom;
                 #body
             }
@@ -78,6 +190,15 @@ fn default(binding: &syn::Ident, default: Option<&syn::Expr>, tokens: TokenStrea
     }
 }
 
+/// Returns the condition under which a map field is left out of the encoded map: it equals its
+/// default. `None` options without a default are encoded as null, `#[ipld(default = None)]` leaves
+/// them out.
+fn omit(field: &StructField) -> Option<TokenStream> {
+    let binding = &field.binding;
+    let default = field.default.as_ref()?;
+    Some(quote!(#binding == &#default))
+}
+
 fn gen_encode_match(arms: impl Iterator<Item = TokenStream>) -> TokenStream {
     quote! {
         match *self {
@@ -96,33 +217,34 @@ fn gen_encode_struct(s: &Struct) -> TokenStream {
 fn gen_encode_struct_body(s: &Struct) -> TokenStream {
     match s.repr {
         StructRepr::Map => {
-            let len = s.fields.len() as u64;
-            let dfields = s.fields.iter().filter_map(|field| {
-                if let Some(default) = field.default.as_ref() {
-                    let binding = &field.binding;
-                    Some(quote! {
-                        if #binding == &#default {
+            let fields: Vec<_> = s.fields.iter().filter(|field| !field.skip).collect();
+            let len = fields.len() as u64;
+            let dfields = fields.iter().filter_map(|field| {
+                omit(field).map(|omit| {
+                    quote! {
+                        if #omit {
                             len -= 1;
                         }
-                    })
-                } else {
-                    None
-                }
+                    }
+                })
             });
-            let mut cbor_order = s
-                .fields
+            let mut cbor_order = fields
                 .iter()
                 .map(|field| {
                     let key = rename(&field.name, field.rename.as_ref());
                     let binding = &field.binding;
-                    let field = default(
-                        binding,
-                        field.default.as_deref(),
-                        quote! {
-                            Encode::encode(#key, c, w)?;
-                            Encode::encode(#binding, c, w)?;
+                    let encode = quote! {
+                        Encode::encode(#key, c, w)?;
+                        Encode::encode(#binding, c, w)?;
+                    };
+                    let field = match omit(field) {
+                        Some(omit) => quote! {
+                            if !(#omit) {
+                                #encode
+                            }
                         },
-                    );
+                        None => encode,
+                    };
                     (key.to_string(), field)
                 })
                 .collect::<Vec<(String, _)>>();
@@ -145,8 +267,9 @@ fn gen_encode_struct_body(s: &Struct) -> TokenStream {
             }
         }
         StructRepr::Tuple => {
-            let len = s.fields.len() as u64;
-            let fields = s.fields.iter().map(|field| {
+            let fields: Vec<_> = s.fields.iter().filter(|field| !field.skip).collect();
+            let len = fields.len() as u64;
+            let fields = fields.iter().map(|field| {
                 let binding = &field.binding;
                 quote! {
                     Encode::encode(#binding, c, w)?;
@@ -205,7 +328,8 @@ fn gen_encode_union(u: &Union) -> TokenStream {
                 }
                 UnionRepr::Int => {
                     assert_eq!(s.repr, StructRepr::Null);
-                    quote!()
+                    let value = int_value(s);
+                    quote!(#pat => Encode::encode(&#value, c, w)?)
                 }
                 UnionRepr::IntTuple => {
                     quote! {
@@ -219,32 +343,80 @@ fn gen_encode_union(u: &Union) -> TokenStream {
             }
         })
         .collect::<Vec<_>>();
-    if u.repr == UnionRepr::Int {
-        quote!(Encode::encode(&(*self as u64), c, w))
-    } else {
-        gen_encode_match(arms.into_iter())
+    gen_encode_match(arms.into_iter())
+}
+
+/// The integer a variant of an int enum is represented as.
+fn int_value(s: &Struct) -> TokenStream {
+    let pat = &*s.pat;
+    match s.value {
+        Some(value) => quote!(#value),
+        None => quote!((#pat as i128)),
     }
 }
 
+/// Reports the variants of an int enum that have the same value as an earlier variant.
+pub fn gen_errors(ast: &SchemaType) -> TokenStream {
+    let mut errors = TokenStream::new();
+    let u = match ast {
+        SchemaType::Union(u) if u.repr == UnionRepr::Int => u,
+        _ => return errors,
+    };
+    let mut seen = BTreeMap::new();
+    for v in &u.variants {
+        let value = match v.value {
+            Some(value) => value,
+            None => continue,
+        };
+        match seen.get(&value) {
+            Some(first) => {
+                let msg = format!(
+                    "variants `{}` and `{}` have the same value {}",
+                    first, v.name, value
+                );
+                errors.extend(syn::Error::new(v.name.span(), msg).to_compile_error());
+            }
+            None => {
+                seen.insert(value, &v.name);
+            }
+        }
+    }
+    errors
+}
+
+/// Binds the skipped fields of a struct to their default value.
+fn gen_decode_skipped(s: &Struct) -> TokenStream {
+    let skipped = s.fields.iter().filter(|field| field.skip).map(|field| {
+        let binding = &field.binding;
+        match field.default.as_ref() {
+            Some(default) => quote!(let #binding = #default;),
+            None => quote!(let #binding = Default::default();),
+        }
+    });
+    quote!(#(#skipped)*)
+}
+
 fn gen_decode_struct(s: &Struct) -> TokenStream {
-    let len = s.fields.len() as u64;
+    let decoded: Vec<_> = s.fields.iter().filter(|field| !field.skip).collect();
+    let len = decoded.len() as u64;
+    let skipped = gen_decode_skipped(s);
     let construct = &*s.construct;
     match s.repr {
         StructRepr::Map => {
-            let binding: Vec<_> = s.fields.iter().map(|field| &field.binding).collect();
-            let key: Vec<_> = s
-                .fields
+            let binding: Vec<_> = decoded.iter().map(|field| &field.binding).collect();
+            let key: Vec<_> = decoded
                 .iter()
                 .map(|field| rename(&field.name, field.rename.as_ref()))
                 .collect();
-            let fields: Vec<_> = s
-                .fields
+            let fields: Vec<_> = decoded
                 .iter()
                 .map(|field| {
                     let binding = &field.binding;
                     let key = rename(&field.name, field.rename.as_ref());
                     if let Some(default) = field.default.as_ref() {
                         quote!(let #binding = #binding.unwrap_or(#default);)
+                    } else if field.optional {
+                        quote!(let #binding = #binding.unwrap_or(None);)
                     } else {
                         quote!(let #binding = #binding.ok_or(MissingKey::new::<Self>(#key))?;)
                     }
@@ -264,12 +436,13 @@ fn gen_decode_struct(s: &Struct) -> TokenStream {
                             match key.as_str() {
                                 #(#key => { #binding = Some(Decode::decode(c, r)?); })*
                                 _ => {
-                                    Decode::decode(c, r)?;
+                                    IgnoredAny::decode(c, r)?;
                                 }
                             }
                         }
 
                         #(#fields)*
+                        #skipped
 
                         return Ok(#construct);
                     }
@@ -280,7 +453,7 @@ fn gen_decode_struct(s: &Struct) -> TokenStream {
             }
         }
         StructRepr::Tuple => {
-            let fields = s.fields.iter().map(|field| {
+            let fields = decoded.iter().map(|field| {
                 let binding = &field.binding;
                 quote! {
                     let #binding = Decode::decode(c, r)?;
@@ -295,6 +468,7 @@ fn gen_decode_struct(s: &Struct) -> TokenStream {
                             return Err(LengthOutOfRange::new::<Self>().into());
                         }
                         #(#fields)*
+                        #skipped
                         return Ok(#construct);
                     }
                     _ => {
@@ -353,25 +527,36 @@ fn gen_decode_union(u: &Union) -> TokenStream {
             }
         }
         UnionRepr::Kinded => {
-            // TODO: this is wrong. Kinded should be based on the kind, not "if it decodes".
+            // Variants are only tried if their representation matches the kind of the data.
+            // Value variants have the kind of their field, so they are always tried.
             let variants = u.variants.iter().map(|s| {
+                let kind = match s.repr {
+                    StructRepr::Map => quote!(major.kind() == MajorKind::Map),
+                    StructRepr::Tuple => quote!(major.kind() == MajorKind::Array),
+                    StructRepr::Null => quote!(major == NULL),
+                    StructRepr::Value => quote!(true),
+                };
                 let parse = gen_decode_struct(s);
                 quote! {
-                    let pos = r.seek(SeekFrom::Current(0))?;
-                    let result: Result<Self> = (|| {
-                        #parse
-                    })();
-                    match result {
-                        Ok(res) => return Ok(res),
-                        Err(err) => {
-                            r.seek(SeekFrom::Start(pos))?;
-                        }
-                    };
+                    if #kind {
+                        let result: Result<Self> = (|| {
+                            #parse
+                        })();
+                        match result {
+                            Ok(res) => return Ok(res),
+                            Err(_) => {
+                                r.seek(SeekFrom::Start(pos))?;
+                            }
+                        };
+                    }
                 }
             });
             quote! {
-                #(#variants;)*
-                Err(UnexpectedCode::new::<Self>(read_major(r)?.into()).into())
+                let pos = r.seek(SeekFrom::Current(0))?;
+                let major = read_major(r)?;
+                r.seek(SeekFrom::Start(pos))?;
+                #(#variants)*
+                Err(UnexpectedCode::new::<Self>(major.into()).into())
             }
         }
         UnionRepr::String => {
@@ -392,10 +577,11 @@ fn gen_decode_union(u: &Union) -> TokenStream {
         UnionRepr::Int => {
             let arms = u.variants.iter().map(|v| {
                 let pat = &*v.pat;
-                quote!(x if x == #pat as u64 => #pat)
+                let value = int_value(v);
+                quote!(x if x == #value => #pat)
             });
             quote! {
-                let key: u64 = Decode::decode(c, r)?;
+                let key: i128 = Decode::decode(c, r)?;
                 let res = match key {
                     #(#arms,)*
                     _ => return Err(UnexpectedKey::new::<Self>(key.to_string()).into()),
@@ -426,3 +612,284 @@ fn gen_decode_union(u: &Union) -> TokenStream {
         }
     }
 }
+
+fn gen_to_ipld_struct(s: &Struct) -> TokenStream {
+    let pat = &*s.pat;
+    let body = gen_to_ipld_struct_body(s);
+    quote! {
+        match *self {
+            #pat => Ok(#body),
+        }
+    }
+}
+
+/// Converts a struct to ipld, in the same representation as `gen_encode_struct_body`.
+fn gen_to_ipld_struct_body(s: &Struct) -> TokenStream {
+    let fields: Vec<_> = s.fields.iter().filter(|field| !field.skip).collect();
+    match s.repr {
+        StructRepr::Map => {
+            let fields = fields.iter().map(|field| {
+                let key = rename(&field.name, field.rename.as_ref());
+                let binding = &field.binding;
+                let insert = quote! {
+                    map.insert(#key.into(), ToIpld::to_ipld(#binding)?);
+                };
+                match omit(field) {
+                    Some(omit) => quote! {
+                        if !(#omit) {
+                            #insert
+                        }
+                    },
+                    None => insert,
+                }
+            });
+            quote! {{
+                let mut map = BTreeMap::new();
+                #(#fields)*
+                Ipld::Map(map)
+            }}
+        }
+        StructRepr::Tuple => {
+            let binding = fields.iter().map(|field| &field.binding);
+            quote!(Ipld::List(vec![#(ToIpld::to_ipld(#binding)?),*]))
+        }
+        StructRepr::Value => {
+            assert_eq!(s.fields.len(), 1);
+            let binding = &s.fields[0].binding;
+            quote!(ToIpld::to_ipld(#binding)?)
+        }
+        StructRepr::Null => {
+            assert_eq!(s.fields.len(), 0);
+            quote!(Ipld::Null)
+        }
+    }
+}
+
+fn gen_to_ipld_union(u: &Union) -> TokenStream {
+    let arms = u.variants.iter().enumerate().map(|(i, s)| {
+        let pat = &*s.pat;
+        let key = rename(&syn::Member::Named(s.name.clone()), s.rename.as_ref());
+        let value = gen_to_ipld_struct_body(s);
+        let ipld = match u.repr {
+            UnionRepr::Keyed => quote!(Ipld::Map(BTreeMap::from([(#key.into(), #value)]))),
+            UnionRepr::Kinded => value,
+            UnionRepr::String => quote!(Ipld::String(#key.into())),
+            UnionRepr::Int => {
+                let value = int_value(s);
+                quote!(Ipld::Integer(#value))
+            }
+            UnionRepr::IntTuple => {
+                let i = i as i128;
+                quote!(Ipld::List(vec![Ipld::Integer(#i), #value]))
+            }
+        };
+        quote!(#pat => Ok(#ipld))
+    });
+    quote! {
+        match *self {
+            #(#arms,)*
+        }
+    }
+}
+
+/// Converts `ipld` to a struct, in the same representation as `gen_decode_struct`.
+fn gen_from_ipld_struct(s: &Struct) -> TokenStream {
+    let decoded: Vec<_> = s.fields.iter().filter(|field| !field.skip).collect();
+    let len = decoded.len();
+    let skipped = gen_decode_skipped(s);
+    let construct = &*s.construct;
+    match s.repr {
+        StructRepr::Map => {
+            let fields = decoded.iter().map(|field| {
+                let binding = &field.binding;
+                let key = rename(&field.name, field.rename.as_ref());
+                let value = quote! {
+                    let #binding = match map.remove(#key) {
+                        Some(value) => Some(FromIpld::from_ipld(value)?),
+                        None => None,
+                    };
+                };
+                let missing = if let Some(default) = field.default.as_ref() {
+                    quote!(let #binding = #binding.unwrap_or(#default);)
+                } else if field.optional {
+                    quote!(let #binding = #binding.unwrap_or(None);)
+                } else {
+                    quote!(let #binding = #binding.ok_or(MissingKey::new::<Self>(#key))?;)
+                };
+                quote!(#value #missing)
+            });
+            quote! {
+                match ipld {
+                    Ipld::Map(mut map) => {
+                        if map.len() > #len {
+                            return Err(LengthOutOfRange::new::<Self>().into());
+                        }
+                        #(#fields)*
+                        #skipped
+                        return Ok(#construct);
+                    }
+                    ipld => {
+                        return Err(TypeError::new(TypeErrorType::Map, ipld).into());
+                    }
+                }
+            }
+        }
+        StructRepr::Tuple => {
+            let binding: Vec<_> = decoded.iter().map(|field| &field.binding).collect();
+            quote! {
+                match ipld {
+                    Ipld::List(list) => {
+                        let [#(#binding),*]: [Ipld; #len] = match list.try_into() {
+                            Ok(list) => list,
+                            Err(_) => return Err(LengthOutOfRange::new::<Self>().into()),
+                        };
+                        #(let #binding = FromIpld::from_ipld(#binding)?;)*
+                        #skipped
+                        return Ok(#construct);
+                    }
+                    ipld => {
+                        return Err(TypeError::new(TypeErrorType::List, ipld).into());
+                    }
+                }
+            }
+        }
+        StructRepr::Value => {
+            assert_eq!(s.fields.len(), 1);
+            let binding = &s.fields[0].binding;
+            quote! {
+                let #binding = FromIpld::from_ipld(ipld)?;
+                return Ok(#construct);
+            }
+        }
+        StructRepr::Null => {
+            assert_eq!(s.fields.len(), 0);
+            quote! {
+                match ipld {
+                    Ipld::Null => {
+                        return Ok(#construct);
+                    }
+                    ipld => {
+                        return Err(TypeError::new(TypeErrorType::Null, ipld).into());
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn gen_from_ipld_union(u: &Union) -> TokenStream {
+    match u.repr {
+        UnionRepr::Keyed => {
+            let variants = u.variants.iter().map(|s| {
+                let key = rename(&syn::Member::Named(s.name.clone()), s.rename.as_ref());
+                let parse = gen_from_ipld_struct(s);
+                quote! {
+                    if key.as_str() == #key {
+                        #parse
+                    }
+                }
+            });
+            quote! {
+                let (key, ipld) = match ipld {
+                    Ipld::Map(map) if map.len() == 1 => map.into_iter().next().unwrap(),
+                    Ipld::Map(_) => return Err(LengthOutOfRange::new::<Self>().into()),
+                    ipld => return Err(TypeError::new(TypeErrorType::Map, ipld).into()),
+                };
+                #(#variants;)*
+                Err(UnexpectedKey::new::<Self>(key).into())
+            }
+        }
+        UnionRepr::Kinded => {
+            // Like when decoding, variants are only tried if their representation matches the kind
+            // of the data.
+            let variants = u.variants.iter().map(|s| {
+                let kind = match s.repr {
+                    StructRepr::Map => quote!(matches!(ipld, Ipld::Map(_))),
+                    StructRepr::Tuple => quote!(matches!(ipld, Ipld::List(_))),
+                    StructRepr::Null => quote!(matches!(ipld, Ipld::Null)),
+                    StructRepr::Value => quote!(true),
+                };
+                let parse = gen_from_ipld_struct(s);
+                quote! {
+                    if #kind {
+                        let result: Result<Self> = (|ipld: Ipld| {
+                            #parse
+                        })(ipld.clone());
+                        if let Ok(res) = result {
+                            return Ok(res);
+                        }
+                    }
+                }
+            });
+            let expected = match u.variants.first().map(|s| &s.repr) {
+                Some(StructRepr::Map) => quote!(TypeErrorType::Map),
+                Some(StructRepr::Tuple) => quote!(TypeErrorType::List),
+                _ => quote!(TypeErrorType::Null),
+            };
+            quote! {
+                #(#variants)*
+                Err(TypeError::new(#expected, ipld).into())
+            }
+        }
+        UnionRepr::String => {
+            let arms = u.variants.iter().map(|v| {
+                let pat = &*v.pat;
+                let value = rename(&syn::Member::Named(v.name.clone()), v.rename.as_ref());
+                quote!(#value => #pat)
+            });
+            quote! {
+                let key = match ipld {
+                    Ipld::String(key) => key,
+                    ipld => return Err(TypeError::new(TypeErrorType::String, ipld).into()),
+                };
+                let res = match key.as_str() {
+                    #(#arms,)*
+                    _ => return Err(UnexpectedKey::new::<Self>(key).into()),
+                };
+                Ok(res)
+            }
+        }
+        UnionRepr::Int => {
+            let arms = u.variants.iter().map(|v| {
+                let pat = &*v.pat;
+                let value = int_value(v);
+                quote!(x if x == #value => #pat)
+            });
+            quote! {
+                let key = match ipld {
+                    Ipld::Integer(key) => key,
+                    ipld => return Err(TypeError::new(TypeErrorType::Integer, ipld).into()),
+                };
+                let res = match key {
+                    #(#arms,)*
+                    _ => return Err(UnexpectedKey::new::<Self>(key.to_string()).into()),
+                };
+                Ok(res)
+            }
+        }
+        UnionRepr::IntTuple => {
+            let variants = u.variants.iter().enumerate().map(|(i, s)| {
+                let i = i as i128;
+                let parse = gen_from_ipld_struct(s);
+                quote!(#i => { #parse })
+            });
+            quote! {
+                let [ty, ipld]: [Ipld; 2] = match ipld {
+                    Ipld::List(list) => match list.try_into() {
+                        Ok(list) => list,
+                        Err(_) => return Err(LengthOutOfRange::new::<Self>().into()),
+                    },
+                    ipld => return Err(TypeError::new(TypeErrorType::List, ipld).into()),
+                };
+                let ty = match ty {
+                    Ipld::Integer(ty) => ty,
+                    ty => return Err(TypeError::new(TypeErrorType::Integer, ty).into()),
+                };
+                match ty {
+                    #(#variants,)*
+                    _ => return Err(UnexpectedKey::new::<Self>(ty.to_string()).into()),
+                }
+            }
+        }
+    }
+}
@@ -4,6 +4,7 @@ use quote::quote;
 use synstructure::{decl_derive, Structure};
 
 decl_derive!([DagCbor, attributes(ipld)] => dag_cbor_derive);
+decl_derive!([DagJson, attributes(ipld)] => dag_json_derive);
 
 mod ast;
 mod attr;
@@ -16,14 +17,28 @@ fn dag_cbor_derive(s: Structure) -> TokenStream {
         Err(error) => return error,
     };
     let ast = parse::parse(&s);
+    let errors = gen::gen_errors(&ast);
     let encode = gen::gen_encode(&ast, &libipld);
     let decode = gen::gen_decode(&ast, &libipld);
     quote! {
+        #errors
         #encode
         #decode
     }
 }
 
+/// Implements the dag-json codec by converting the value to and from an `Ipld`. The conversion
+/// uses the representation chosen with the `ipld` attributes, the same one the dag-cbor codec
+/// uses.
+fn dag_json_derive(s: Structure) -> TokenStream {
+    let libipld = match use_crate("libipld") {
+        Ok(ident) => ident,
+        Err(error) => return error,
+    };
+    let ast = parse::parse(&s);
+    gen::gen_json(&ast, &libipld)
+}
+
 /// Get the name of a crate based on its original name.
 ///
 /// This works even if the crate was renamed in the `Cargo.toml` file. If the crate is not a
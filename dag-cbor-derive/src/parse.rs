@@ -2,15 +2,17 @@ use crate::ast::*;
 use crate::attr::{Attrs, DeriveAttr, FieldAttr};
 use quote::quote;
 use syn::parse::Parse;
+use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
 use synstructure::{BindingInfo, Structure, VariantInfo};
 
 pub fn parse(s: &Structure) -> SchemaType {
     match &s.ast().data {
-        syn::Data::Struct(_) => SchemaType::Struct(parse_struct(
-            &s.variants()[0],
-            Some(s.ast().generics.clone()),
-        )),
+        syn::Data::Struct(_) => {
+            let mut ast = parse_struct(&s.variants()[0], Some(s.ast().generics.clone()));
+            ast.bound = parse_bound(&s.ast().attrs);
+            SchemaType::Struct(ast)
+        }
         syn::Data::Enum(_) => SchemaType::Union(parse_union(s)),
         syn::Data::Union(_) => unimplemented!(),
     }
@@ -29,14 +31,87 @@ fn parse_attrs<T: Parse>(ast: &[syn::Attribute]) -> Vec<T> {
     derive_attrs
 }
 
+fn parse_bound(ast: &[syn::Attribute]) -> Option<Vec<syn::WherePredicate>> {
+    let attrs = parse_attrs::<DeriveAttr>(ast);
+    let mut bound = None;
+    for attr in attrs {
+        if let DeriveAttr::Bound(attr) = attr {
+            let predicates = attr
+                .value
+                .parse_with(Punctuated::<syn::WherePredicate, syn::Token![,]>::parse_terminated)
+                .unwrap_or_else(|err| panic!("invalid bound: {}", err));
+            bound = Some(predicates.into_iter().collect());
+        }
+    }
+    bound
+}
+
+fn parse_rename_all(ast: &[syn::Attribute]) -> Option<String> {
+    let attrs = parse_attrs::<DeriveAttr>(ast);
+    let mut rename_all = None;
+    for attr in attrs {
+        if let DeriveAttr::RenameAll(attr) = attr {
+            rename_all = Some(attr.value.value());
+        }
+    }
+    rename_all
+}
+
+/// Converts a snake case field or pascal case variant name to the case of a `rename_all` rule.
+fn rename_case(rule: &str, name: &str) -> String {
+    let mut words: Vec<String> = Vec::new();
+    for part in name.split('_').filter(|part| !part.is_empty()) {
+        let mut word = String::new();
+        for c in part.chars() {
+            if c.is_uppercase() && !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            word.extend(c.to_lowercase());
+        }
+        words.push(word);
+    }
+    let capitalize = |word: &String| {
+        let mut chars = word.chars();
+        chars
+            .next()
+            .map(|c| c.to_uppercase().chain(chars).collect())
+            .unwrap_or_default()
+    };
+    match rule {
+        "lowercase" => words.concat(),
+        "UPPERCASE" => words.concat().to_uppercase(),
+        "PascalCase" => words.iter().map(capitalize).collect(),
+        "camelCase" => words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| {
+                if i == 0 {
+                    word.clone()
+                } else {
+                    capitalize(word)
+                }
+            })
+            .collect(),
+        "snake_case" => words.join("_"),
+        "SCREAMING_SNAKE_CASE" => words.join("_").to_uppercase(),
+        "kebab-case" => words.join("-"),
+        "SCREAMING-KEBAB-CASE" => words.join("-").to_uppercase(),
+        rule => panic!("unknown rename_all rule {}", rule),
+    }
+}
+
 fn parse_struct_repr(ast: &[syn::Attribute]) -> Option<StructRepr> {
     let attrs = parse_attrs::<DeriveAttr>(ast);
     let mut repr = None;
-    for DeriveAttr::Repr(attr) in attrs {
+    for attr in attrs {
+        let attr = match attr {
+            DeriveAttr::Repr(attr) => attr,
+            _ => continue,
+        };
         repr = Some(match attr.value.value().as_str() {
             "map" => StructRepr::Map,
             "tuple" => StructRepr::Tuple,
-            "value" => StructRepr::Value,
+            "value" | "transparent" => StructRepr::Value,
             "null" => StructRepr::Null,
             repr => panic!("unknown struct representation {}", repr),
         })
@@ -47,7 +122,11 @@ fn parse_struct_repr(ast: &[syn::Attribute]) -> Option<StructRepr> {
 fn parse_union_repr(ast: &[syn::Attribute]) -> UnionRepr {
     let attrs = parse_attrs::<DeriveAttr>(ast);
     let mut repr = None;
-    for DeriveAttr::Repr(attr) in attrs {
+    for attr in attrs {
+        let attr = match attr {
+            DeriveAttr::Repr(attr) => attr,
+            _ => continue,
+        };
         repr = Some(match attr.value.value().as_str() {
             "keyed" => UnionRepr::Keyed,
             "kinded" => UnionRepr::Kinded,
@@ -73,6 +152,13 @@ fn parse_struct(v: &VariantInfo, generics: Option<syn::Generics>) -> Struct {
         syn::Fields::Unnamed(_) => StructRepr::Tuple,
         syn::Fields::Unit => StructRepr::Null,
     });
+    if let Some(rule) = parse_rename_all(v.ast().attrs) {
+        for field in &mut fields {
+            if let (None, syn::Member::Named(ident)) = (&field.rename, &field.name) {
+                field.rename = Some(rename_case(&rule, &ident.to_string()));
+            }
+        }
+    }
     if repr == StructRepr::Map {
         fields.sort_by(|f1, f2| match (&f1.name, &f2.name) {
             (syn::Member::Named(ident1), syn::Member::Named(ident2)) => {
@@ -87,7 +173,9 @@ fn parse_struct(v: &VariantInfo, generics: Option<syn::Generics>) -> Struct {
     Struct {
         name: v.ast().ident.clone(),
         generics,
+        bound: None,
         rename: None,
+        value: None,
         fields,
         repr,
         pat: TokenStreamEq(v.pat()),
@@ -100,26 +188,71 @@ fn parse_struct(v: &VariantInfo, generics: Option<syn::Generics>) -> Struct {
 
 fn parse_union(s: &Structure) -> Union {
     let repr = parse_union_repr(&s.ast().attrs);
+    let rename_all = parse_rename_all(&s.ast().attrs);
+    let mut variants: Vec<_> = s
+        .variants()
+        .iter()
+        .map(|v| {
+            let mut s = parse_struct(v, None);
+            for attr in parse_attrs::<FieldAttr>(v.ast().attrs) {
+                match attr {
+                    FieldAttr::Rename(attr) => s.rename = Some(attr.value.value()),
+                    FieldAttr::Value(attr) => s.value = Some(attr.value.0),
+                    _ => {}
+                }
+            }
+            if let (None, Some(rule)) = (&s.rename, &rename_all) {
+                s.rename = Some(rename_case(rule, &s.name.to_string()));
+            }
+            s
+        })
+        .collect();
+    if repr == UnionRepr::Int {
+        int_values(s, &mut variants);
+    }
     Union {
         name: s.ast().ident.clone(),
         generics: s.ast().generics.clone(),
-        variants: s
-            .variants()
-            .iter()
-            .map(|v| {
-                let mut s = parse_struct(v, None);
-                for attr in parse_attrs::<FieldAttr>(v.ast().attrs) {
-                    if let FieldAttr::Rename(attr) = attr {
-                        s.rename = Some(attr.value.value());
-                    }
-                }
-                s
-            })
-            .collect(),
+        bound: parse_bound(&s.ast().attrs),
+        variants,
         repr,
     }
 }
 
+/// Sets the value of the variants of an int enum without a `value` attribute to their
+/// discriminant. Like rust, a variant without an explicit discriminant is one more than the
+/// previous one, starting at zero. Discriminants that aren't integer literals, and the ones
+/// following them, can't be computed here and are left to the `as` cast.
+fn int_values(s: &Structure, variants: &mut [Struct]) {
+    let mut next = Some(0);
+    for (v, variant) in s.variants().iter().zip(variants) {
+        let discriminant = match v.ast().discriminant {
+            Some((_, expr)) => int_literal(expr),
+            None => next,
+        };
+        next = discriminant.and_then(|d: i128| d.checked_add(1));
+        if variant.value.is_none() {
+            variant.value = discriminant;
+        }
+    }
+}
+
+fn int_literal(expr: &syn::Expr) -> Option<i128> {
+    match expr {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(lit),
+            ..
+        }) => lit.base10_parse().ok(),
+        syn::Expr::Unary(syn::ExprUnary {
+            op: syn::UnOp::Neg(_),
+            expr,
+            ..
+        }) => int_literal(expr).map(|i| -i),
+        syn::Expr::Paren(syn::ExprParen { expr, .. }) => int_literal(expr),
+        _ => None,
+    }
+}
+
 fn parse_field(i: usize, b: &BindingInfo) -> StructField {
     let mut field = StructField {
         name: match b.ast().ident.as_ref() {
@@ -131,17 +264,40 @@ fn parse_field(i: usize, b: &BindingInfo) -> StructField {
         },
         rename: None,
         default: None,
+        skip: false,
+        optional: is_option(&b.ast().ty),
         binding: b.binding.clone(),
     };
     for attr in parse_attrs::<FieldAttr>(&b.ast().attrs) {
         match attr {
             FieldAttr::Rename(attr) => field.rename = Some(attr.value.value()),
             FieldAttr::Default(attr) => field.default = Some(attr.value),
+            FieldAttr::DefaultTrait => {
+                let ty = &b.ast().ty;
+                field.default = Some(Box::new(
+                    syn::parse_quote!(<#ty as ::core::default::Default>::default()),
+                ));
+            }
+            FieldAttr::Skip => field.skip = true,
+            FieldAttr::Value(_) => panic!("value is only supported on enum variants"),
         }
     }
     field
 }
 
+/// Returns true if the type is an `Option`. Missing optional fields decode as `None`.
+fn is_option(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident == "Option")
+            .unwrap_or_default(),
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -179,11 +335,15 @@ pub mod tests {
             SchemaType::Struct(Struct {
                 name: format_ident!("Map"),
                 generics: Some(Default::default()),
+                bound: None,
                 rename: None,
+                value: None,
                 fields: vec![StructField {
                     name: syn::Member::Named(format_ident!("field")),
                     rename: Some("other".to_string()),
                     default: Some(syn::parse2(quote!(false)).unwrap()),
+                    skip: false,
+                    optional: false,
                     binding: format_ident!("__binding_0"),
                 }],
                 repr: StructRepr::Map,
@@ -206,11 +366,15 @@ pub mod tests {
             SchemaType::Struct(Struct {
                 name: format_ident!("Tuple"),
                 generics: Some(Default::default()),
+                bound: None,
                 rename: None,
+                value: None,
                 fields: vec![StructField {
                     name: syn::Member::Unnamed(format_index!(0)),
                     rename: None,
                     default: None,
+                    skip: false,
+                    optional: false,
                     binding: format_ident!("__binding_0"),
                 }],
                 repr: StructRepr::Tuple,
@@ -232,7 +396,9 @@ pub mod tests {
             SchemaType::Struct(Struct {
                 name: format_ident!("Map"),
                 generics: Some(Default::default()),
+                bound: None,
                 rename: None,
+                value: None,
                 fields: Default::default(),
                 repr: StructRepr::Null,
                 pat: TokenStreamEq(quote!(Map)),
@@ -258,11 +424,14 @@ pub mod tests {
             SchemaType::Union(Union {
                 name: format_ident!("Union"),
                 generics: Default::default(),
+                bound: None,
                 variants: vec![
                     Struct {
                         name: format_ident!("Unit"),
                         generics: None,
+                        bound: None,
                         rename: Some("unit".into()),
+                        value: None,
                         fields: vec![],
                         repr: StructRepr::Null,
                         pat: TokenStreamEq(quote!(Union::Unit)),
@@ -271,11 +440,15 @@ pub mod tests {
                     Struct {
                         name: format_ident!("Tuple"),
                         generics: None,
+                        bound: None,
                         rename: None,
+                        value: None,
                         fields: vec![StructField {
                             name: syn::Member::Unnamed(format_index!(0)),
                             rename: None,
                             default: None,
+                            skip: false,
+                            optional: false,
                             binding: format_ident!("__binding_0"),
                         }],
                         repr: StructRepr::Tuple,
@@ -285,11 +458,15 @@ pub mod tests {
                     Struct {
                         name: format_ident!("Struct"),
                         generics: None,
+                        bound: None,
                         rename: None,
+                        value: None,
                         fields: vec![StructField {
                             name: syn::Member::Named(format_ident!("value")),
                             rename: None,
                             default: None,
+                            skip: false,
+                            optional: false,
                             binding: format_ident!("__binding_0"),
                         }],
                         repr: StructRepr::Map,
@@ -318,10 +495,13 @@ pub mod tests {
             SchemaType::Union(Union {
                 name: format_ident!("Enum"),
                 generics: Default::default(),
+                bound: None,
                 variants: vec![Struct {
                     name: format_ident!("Variant"),
                     generics: None,
+                    bound: None,
                     rename: Some("test".into()),
+                    value: None,
                     fields: vec![],
                     repr: StructRepr::Null,
                     pat: TokenStreamEq(quote!(Enum::Variant)),
@@ -331,4 +511,60 @@ pub mod tests {
             })
         );
     }
+
+    #[test]
+    fn test_rename_case() {
+        assert_eq!(rename_case("camelCase", "block_size"), "blockSize");
+        assert_eq!(rename_case("camelCase", "BlockSize"), "blockSize");
+        assert_eq!(rename_case("PascalCase", "block_size"), "BlockSize");
+        assert_eq!(rename_case("snake_case", "BlockSize"), "block_size");
+        assert_eq!(rename_case("kebab-case", "block_size"), "block-size");
+        assert_eq!(
+            rename_case("SCREAMING_SNAKE_CASE", "BlockSize"),
+            "BLOCK_SIZE"
+        );
+        assert_eq!(rename_case("lowercase", "BlockSize"), "blocksize");
+    }
+
+    fn int_values(schema: &SchemaType) -> Vec<Option<i128>> {
+        match schema {
+            SchemaType::Union(u) => u.variants.iter().map(|v| v.value).collect(),
+            SchemaType::Struct(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_int_values() {
+        let schema = ast(quote! {
+            #[derive(DagCbor)]
+            #[ipld(repr = "int")]
+            enum Int {
+                #[ipld(value = -1)]
+                A,
+                B = 5,
+                C,
+                D = X,
+                E,
+            }
+        });
+        assert_eq!(
+            int_values(&schema),
+            vec![Some(-1), Some(5), Some(6), None, None]
+        );
+        assert!(crate::gen::gen_errors(&schema).is_empty());
+
+        // `B` collides with the implicit discriminant of `A`.
+        let schema = ast(quote! {
+            #[derive(DagCbor)]
+            #[ipld(repr = "int")]
+            enum Int {
+                A,
+                #[ipld(value = 0)]
+                B,
+            }
+        });
+        let errors = crate::gen::gen_errors(&schema).to_string();
+        assert!(errors.contains("compile_error"));
+        assert!(errors.contains("variants `A` and `B` have the same value 0"));
+    }
 }
@@ -15,6 +15,36 @@ fn enum_int() {
     assert_roundtrip(DagCborCodec, &EnumInt::Other, &ipld!(0));
 }
 
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+#[ipld(repr = "int")]
+pub enum EnumIntValue {
+    #[ipld(value = 200)]
+    Variant,
+    Other,
+}
+
+#[test]
+fn enum_int_value() {
+    assert_roundtrip(DagCborCodec, &EnumIntValue::Variant, &ipld!(200));
+    assert_roundtrip(DagCborCodec, &EnumIntValue::Other, &ipld!(1));
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+#[ipld(repr = "int")]
+pub enum EnumIntSigned {
+    #[ipld(value = -1)]
+    Negative,
+    Low = -5,
+    Next,
+}
+
+#[test]
+fn enum_int_signed() {
+    assert_roundtrip(DagCborCodec, &EnumIntSigned::Negative, &ipld!(-1));
+    assert_roundtrip(DagCborCodec, &EnumIntSigned::Low, &ipld!(-5));
+    assert_roundtrip(DagCborCodec, &EnumIntSigned::Next, &ipld!(-4));
+}
+
 #[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
 #[ipld(repr = "string")]
 pub enum EnumString {
@@ -28,3 +58,21 @@ fn enum_string() {
     assert_roundtrip(DagCborCodec, &EnumString::Variant, &ipld!("test"));
     assert_roundtrip(DagCborCodec, &EnumString::Other, &ipld!("Other"));
 }
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+#[ipld(repr = "string", rename_all = "kebab-case")]
+pub enum EnumRenameAll {
+    FirstVariant,
+    #[ipld(rename = "other")]
+    SecondVariant,
+}
+
+#[test]
+fn enum_rename_all() {
+    assert_roundtrip(
+        DagCborCodec,
+        &EnumRenameAll::FirstVariant,
+        &ipld!("first-variant"),
+    );
+    assert_roundtrip(DagCborCodec, &EnumRenameAll::SecondVariant, &ipld!("other"));
+}
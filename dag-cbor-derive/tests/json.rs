@@ -0,0 +1,104 @@
+use libipld::codec::{assert_roundtrip, Codec};
+use libipld::json::DagJsonCodec;
+use libipld::{ipld, DagCbor, DagJson, Ipld};
+
+#[derive(Clone, DagCbor, DagJson, Debug, Eq, PartialEq)]
+#[ipld(rename_all = "camelCase")]
+pub struct Map {
+    block_size: u64,
+    name: String,
+}
+
+#[derive(Clone, DagCbor, DagJson, Debug, Eq, PartialEq)]
+#[ipld(repr = "tuple")]
+pub struct Tuple(Option<String>, Vec<u8>);
+
+#[derive(Clone, DagCbor, DagJson, Debug, Eq, PartialEq)]
+#[ipld(repr = "keyed")]
+pub enum Keyed<T> {
+    A,
+    B(T),
+}
+
+#[derive(Clone, DagCbor, DagJson, Debug, PartialEq)]
+#[ipld(repr = "kinded")]
+pub enum Kinded {
+    Map {
+        #[ipld(default = 0)]
+        n: u32,
+        #[ipld(default = None)]
+        s: Option<String>,
+    },
+    Tuple(bool),
+    #[ipld(repr = "value")]
+    Ipld(Ipld),
+}
+
+#[derive(Clone, Copy, DagCbor, DagJson, Debug, Eq, PartialEq)]
+#[ipld(repr = "int")]
+pub enum Int {
+    A = -1,
+    B = 2,
+}
+
+#[derive(Clone, DagCbor, DagJson, Debug, Eq, PartialEq)]
+#[ipld(repr = "int-tuple")]
+pub enum IntTuple {
+    #[ipld(repr = "value")]
+    A(String),
+    B {
+        list: Vec<Int>,
+    },
+}
+
+#[test]
+fn json_struct() {
+    assert_roundtrip(
+        DagJsonCodec,
+        &Map {
+            block_size: 1,
+            name: "a".into(),
+        },
+        &ipld!({"blockSize": 1, "name": "a"}),
+    );
+}
+
+#[test]
+fn json_union() {
+    assert_roundtrip(DagJsonCodec, &Keyed::<bool>::A, &ipld!({ "A": null }));
+    assert_roundtrip(DagJsonCodec, &Keyed::B(true), &ipld!({"B": [true]}));
+}
+
+#[test]
+fn json_tuple() {
+    assert_roundtrip(DagJsonCodec, &Tuple(None, vec![1]), &ipld!([null, [1]]));
+    assert!(DagJsonCodec.decode::<Tuple>(b"[null]").is_err());
+}
+
+#[test]
+fn json_kinded() {
+    assert_roundtrip(
+        DagJsonCodec,
+        &Kinded::Map { n: 1, s: None },
+        &ipld!({ "n": 1 }),
+    );
+    assert_roundtrip(DagJsonCodec, &Kinded::Tuple(true), &ipld!([true]));
+    assert_roundtrip(
+        DagJsonCodec,
+        &Kinded::Ipld(ipld!({ "n": "a" })),
+        &ipld!({ "n": "a" }),
+    );
+}
+
+#[test]
+fn json_int() {
+    assert_roundtrip(DagJsonCodec, &Int::A, &ipld!(-1));
+    assert_roundtrip(
+        DagJsonCodec,
+        &IntTuple::B { list: vec![Int::B] },
+        &ipld!([1, { "list": [2] }]),
+    );
+    assert_roundtrip(DagJsonCodec, &IntTuple::A("a".into()), &ipld!([0, "a"]));
+    assert!(DagJsonCodec.decode::<Int>(b"0").is_err());
+    assert!(DagJsonCodec.decode::<IntTuple>(b"[2,null]").is_err());
+}
@@ -1,6 +1,12 @@
 use libipld::cbor::{DagCbor, DagCborCodec};
-use libipld::codec::assert_roundtrip;
-use libipld::{ipld, DagCbor};
+use libipld::codec::{assert_roundtrip, Codec};
+use libipld::multihash::Code;
+use libipld::typed_map::TypedMap;
+use libipld::{ipld, Cid, DagCbor, Link};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
 
 #[derive(Clone, Copy, DagCbor, Debug, Eq, PartialEq)]
 #[ipld(repr = "map")]
@@ -42,6 +48,26 @@ fn struct_rename() {
     );
 }
 
+#[derive(Clone, Copy, DagCbor, Debug, Eq, PartialEq)]
+#[ipld(rename_all = "camelCase")]
+pub struct RenameAll {
+    block_size: u32,
+    #[ipld(rename = "kind")]
+    block_kind: u32,
+}
+
+#[test]
+fn struct_rename_all() {
+    assert_roundtrip(
+        DagCborCodec,
+        &RenameAll {
+            block_size: 1,
+            block_kind: 2,
+        },
+        &ipld!({"blockSize": 1, "kind": 2}),
+    );
+}
+
 #[derive(Clone, Copy, DagCbor, Debug, Eq, PartialEq)]
 pub struct Nullable {
     nullable: Option<bool>,
@@ -115,6 +141,52 @@ fn struct_optional_nullable() {
     );
 }
 
+#[derive(Clone, DagCbor, Debug, Default, Eq, PartialEq)]
+pub struct Evolved {
+    name: String,
+    #[ipld(default)]
+    count: u64,
+    added: Option<bool>,
+    #[ipld(skip)]
+    cache: Vec<u8>,
+}
+
+#[test]
+fn struct_evolved() {
+    assert_roundtrip(
+        DagCborCodec,
+        &Evolved {
+            name: "a".into(),
+            count: 1,
+            added: Some(true),
+            cache: vec![],
+        },
+        &ipld!({"name": "a", "count": 1, "added": true}),
+    );
+    assert_roundtrip(
+        DagCborCodec,
+        &Evolved {
+            name: "a".into(),
+            ..Default::default()
+        },
+        &ipld!({"name": "a", "added": null}),
+    );
+
+    let data = DagCborCodec.encode(&ipld!({"name": "a"})).unwrap();
+    let evolved: Evolved = DagCborCodec.decode(&data).unwrap();
+    assert_eq!(evolved.count, 0);
+    assert_eq!(evolved.added, None);
+
+    let skipped = Evolved {
+        name: "a".into(),
+        cache: vec![1],
+        ..Default::default()
+    };
+    let data = DagCborCodec.encode(&skipped).unwrap();
+    let decoded: Evolved = DagCborCodec.decode(&data).unwrap();
+    assert!(decoded.cache.is_empty());
+}
+
 #[derive(Clone, Copy, DagCbor, Debug, Eq, PartialEq)]
 #[ipld(repr = "tuple")]
 pub struct Tuple(bool);
@@ -125,6 +197,25 @@ fn struct_tuple() {
     assert_roundtrip(DagCborCodec, &Tuple(false), &ipld!([false]));
 }
 
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+#[ipld(repr = "tuple")]
+pub struct NamedTuple {
+    name: String,
+    size: u64,
+}
+
+#[test]
+fn struct_named_tuple() {
+    assert_roundtrip(
+        DagCborCodec,
+        &NamedTuple {
+            name: "a".into(),
+            size: 1,
+        },
+        &ipld!(["a", 1]),
+    );
+}
+
 #[derive(Clone, Copy, DagCbor, Debug, Eq, PartialEq)]
 pub struct TupleNullable(Option<bool>);
 
@@ -145,6 +236,56 @@ fn struct_value() {
     assert_roundtrip(DagCborCodec, &Value(false), &ipld!(false));
 }
 
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+#[ipld(repr = "transparent")]
+pub struct Wrapper(Map);
+
+#[test]
+fn struct_transparent() {
+    assert_roundtrip(
+        DagCborCodec,
+        &Wrapper(Map { boolean: true }),
+        &ipld!({"boolean": true}),
+    );
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+pub struct Pointers {
+    boxed: Box<Map>,
+    rc: Rc<String>,
+    arc: Arc<u64>,
+    cow: Cow<'static, str>,
+}
+
+#[test]
+fn struct_pointers() {
+    assert_roundtrip(
+        DagCborCodec,
+        &Pointers {
+            boxed: Box::new(Map { boolean: true }),
+            rc: Rc::new("rc".into()),
+            arc: Arc::new(1),
+            cow: Cow::Borrowed("cow"),
+        },
+        &ipld!({"boxed": {"boolean": true}, "rc": "rc", "arc": 1, "cow": "cow"}),
+    );
+}
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+pub struct Balances {
+    balances: TypedMap<u64, i64>,
+}
+
+#[test]
+fn struct_typed_map() {
+    let balances = [(2, -1), (10, 5)].into_iter().collect();
+    assert_roundtrip(
+        DagCborCodec,
+        &Balances { balances },
+        &ipld!({"balances": {"2": -1, "10": 5}}),
+    );
+}
+
 #[derive(Clone, Copy, DagCbor, Debug, Eq, PartialEq)]
 pub struct IlMap {
     #[ipld(rename = "Fun")]
@@ -153,5 +294,85 @@ pub struct IlMap {
     amt: i32,
 }
 
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+pub struct Linked {
+    map: Link<Map>,
+}
+
+#[test]
+fn struct_link() {
+    let mut blocks = HashMap::new();
+    let map = Map { boolean: true };
+    let link = Link::store(DagCborCodec, Code::Blake3_256, &map, |cid, data| {
+        blocks.insert(cid, data);
+        Ok(())
+    })
+    .unwrap();
+    assert_roundtrip(
+        DagCborCodec,
+        &Linked { map: link },
+        &ipld!({ "map": link.cid() }),
+    );
+    let loaded = link
+        .load::<DagCborCodec, Code, _>(|cid| Ok(blocks[cid].clone()))
+        .unwrap();
+    assert_eq!(loaded, map);
+
+    let other = DagCborCodec.encode(&Map { boolean: false }).unwrap();
+    assert!(link
+        .load::<DagCborCodec, Code, _>(|_| Ok(other.clone()))
+        .is_err());
+}
+
 #[derive(DagCbor)]
 pub struct Generic<T: DagCbor>(T);
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+pub struct Node<T> {
+    value: T,
+    next: Option<Cid>,
+}
+
+#[test]
+fn struct_generic() {
+    let cid = Link::<Map>::store(
+        DagCborCodec,
+        Code::Blake3_256,
+        &Map { boolean: true },
+        |_, _| Ok(()),
+    )
+    .unwrap();
+    let node = Node {
+        value: "value".to_string(),
+        next: Some(*cid.cid()),
+    };
+    assert_roundtrip(
+        DagCborCodec,
+        &node,
+        &ipld!({ "next": cid.cid(), "value": "value" }),
+    );
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NotEncodable;
+
+#[derive(Clone, DagCbor, Debug, Eq, PartialEq)]
+#[ipld(bound = "")]
+pub struct Typed<T> {
+    link: Link<T>,
+}
+
+#[test]
+fn struct_bound() {
+    let cid = Link::<Map>::store(
+        DagCborCodec,
+        Code::Blake3_256,
+        &Map { boolean: true },
+        |_, _| Ok(()),
+    )
+    .unwrap();
+    let typed = Typed::<NotEncodable> {
+        link: Link::new(*cid.cid()),
+    };
+    assert_roundtrip(DagCborCodec, &typed, &ipld!({ "link": cid.cid() }));
+}
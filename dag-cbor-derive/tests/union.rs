@@ -1,6 +1,6 @@
 use libipld::cbor::DagCborCodec;
-use libipld::codec::assert_roundtrip;
-use libipld::{ipld, DagCbor};
+use libipld::codec::{assert_roundtrip, Codec};
+use libipld::{ipld, DagCbor, Ipld};
 
 #[derive(Clone, Copy, DagCbor, Debug, Eq, PartialEq)]
 #[ipld(repr = "keyed")]
@@ -64,6 +64,38 @@ fn union_kinded() {
     );
 }
 
+#[derive(Clone, DagCbor, Debug, PartialEq)]
+#[ipld(repr = "kinded")]
+pub enum KindedFallback {
+    Map {
+        #[ipld(default = 0)]
+        n: u32,
+    },
+    #[ipld(repr = "value")]
+    Ipld(Ipld),
+}
+
+#[test]
+fn union_kinded_fallback() {
+    assert_roundtrip(
+        DagCborCodec,
+        &KindedFallback::Map { n: 1 },
+        &ipld!({ "n": 1 }),
+    );
+    assert_roundtrip(DagCborCodec, &KindedFallback::Ipld(ipld!("n")), &ipld!("n"));
+    assert_roundtrip(
+        DagCborCodec,
+        &KindedFallback::Ipld(ipld!({ "n": "a" })),
+        &ipld!({ "n": "a" }),
+    );
+}
+
+#[test]
+fn union_kinded_unexpected() {
+    let data = DagCborCodec.encode(&ipld!("a")).unwrap();
+    assert!(DagCborCodec.decode::<Kinded>(&data).is_err());
+}
+
 #[derive(Clone, Copy, DagCbor, Debug, Eq, PartialEq)]
 #[ipld(repr = "int-tuple")]
 pub enum IntTuple {
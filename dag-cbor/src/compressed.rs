@@ -0,0 +1,169 @@
+//! Compressed bytes.
+//!
+//! [`CompressedBytes`] is a standard wrapper for compressed data. It records the compression
+//! algorithm and the size of the original data next to the compressed bytes and encodes as the
+//! dag-cbor map `{"data": h'...', "size": 1024, "algorithm": "zstd"}`. The library doesn't
+//! implement any compression itself, the algorithm is applied by closures passed to
+//! [`CompressedBytes::compress`] and [`CompressedBytes::decompress`].
+use crate::cbor::MajorKind;
+use crate::decode::{read_major, read_uint};
+use crate::encode::write_u64;
+use crate::error::{DecompressedSize, DuplicateKey, MissingKey, UnexpectedCode, UnexpectedKey};
+use crate::DagCborCodec as DagCbor;
+use libipld_core::codec::{Decode, Encode};
+use libipld_core::error::Result;
+use std::io::{Read, Seek, Write};
+
+/// Compressed data with the algorithm and the size of the original data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompressedBytes {
+    /// Name of the compression algorithm, e.g. `zstd`.
+    pub algorithm: String,
+    /// Size of the original data in bytes.
+    pub size: u64,
+    /// The compressed data.
+    pub data: Vec<u8>,
+}
+
+impl CompressedBytes {
+    /// Compresses `bytes` with `compress`, recording `algorithm` as the algorithm it implements.
+    pub fn compress<F>(algorithm: impl Into<String>, bytes: &[u8], compress: F) -> Result<Self>
+    where
+        F: FnOnce(&[u8]) -> Result<Vec<u8>>,
+    {
+        Ok(Self {
+            algorithm: algorithm.into(),
+            size: bytes.len() as u64,
+            data: compress(bytes)?,
+        })
+    }
+
+    /// Decompresses the data with `decompress`, which is passed the algorithm, the compressed data
+    /// and the recorded size.
+    ///
+    /// The size comes from the encoded block and can't be trusted, so it shouldn't be used to
+    /// preallocate more than the decompressor would produce anyway. Fails if the decompressed
+    /// data doesn't have the recorded size.
+    pub fn decompress<F>(&self, decompress: F) -> Result<Vec<u8>>
+    where
+        F: FnOnce(&str, &[u8], u64) -> Result<Vec<u8>>,
+    {
+        let bytes = decompress(&self.algorithm, &self.data, self.size)?;
+        if bytes.len() as u64 != self.size {
+            return Err(DecompressedSize {
+                expected: self.size,
+                actual: bytes.len() as u64,
+            }
+            .into());
+        }
+        Ok(bytes)
+    }
+}
+
+impl Encode<DagCbor> for CompressedBytes {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        // Keys in dag-cbor order, sorted by length first.
+        write_u64(w, MajorKind::Map, 3)?;
+        "data".encode(c, w)?;
+        self.data[..].encode(c, w)?;
+        "size".encode(c, w)?;
+        self.size.encode(c, w)?;
+        "algorithm".encode(c, w)?;
+        self.algorithm.encode(c, w)
+    }
+}
+
+impl Decode<DagCbor> for CompressedBytes {
+    fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
+        let major = read_major(r)?;
+        if major.kind() != MajorKind::Map {
+            return Err(UnexpectedCode::new::<Self>(major.into()).into());
+        }
+        let len = read_uint(r, major)?;
+        let (mut algorithm, mut size, mut data) = (None, None, None);
+        for _ in 0..len {
+            let key = String::decode(c, r)?;
+            let duplicate = match key.as_str() {
+                "algorithm" => algorithm.replace(String::decode(c, r)?).is_some(),
+                "size" => size.replace(u64::decode(c, r)?).is_some(),
+                "data" => data.replace(Box::<[u8]>::decode(c, r)?).is_some(),
+                _ => return Err(UnexpectedKey::new::<Self>(key).into()),
+            };
+            if duplicate {
+                return Err(DuplicateKey.into());
+            }
+        }
+        Ok(Self {
+            algorithm: algorithm.ok_or_else(|| MissingKey::new::<Self>("algorithm"))?,
+            size: size.ok_or_else(|| MissingKey::new::<Self>("size"))?,
+            data: data.ok_or_else(|| MissingKey::new::<Self>("data"))?.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libipld_core::codec::{assert_roundtrip, Codec};
+    use libipld_core::ipld::Ipld;
+    use libipld_macro::ipld;
+
+    /// Run-length encoding, as a stand-in for a real compression algorithm.
+    fn rle(bytes: &[u8]) -> Result<Vec<u8>> {
+        let mut out: Vec<u8> = Vec::new();
+        for &byte in bytes {
+            match out.len() {
+                len if len >= 2 && out[len - 1] == byte && out[len - 2] < u8::MAX => {
+                    out[len - 2] += 1
+                }
+                _ => out.extend([1, byte]),
+            }
+        }
+        Ok(out)
+    }
+
+    fn unrle(algorithm: &str, bytes: &[u8], _size: u64) -> Result<Vec<u8>> {
+        assert_eq!(algorithm, "rle");
+        Ok(bytes
+            .chunks(2)
+            .flat_map(|chunk| std::iter::repeat_n(chunk[1], chunk[0] as usize))
+            .collect())
+    }
+
+    #[test]
+    fn test_compressed_bytes() {
+        let original = b"aaaaaaaabbbbbbbb";
+        let compressed = CompressedBytes::compress("rle", original, rle).unwrap();
+        assert_eq!(compressed.size, 16);
+        assert_eq!(compressed.data, vec![8, b'a', 8, b'b']);
+        assert_roundtrip(
+            DagCbor,
+            &compressed,
+            &ipld!({
+                "algorithm": "rle",
+                "size": 16,
+                "data": Ipld::Bytes(vec![8, b'a', 8, b'b']),
+            }),
+        );
+        assert_eq!(compressed.decompress(unrle).unwrap(), original);
+
+        let lying = CompressedBytes {
+            size: 1 << 40,
+            ..compressed
+        };
+        let err = lying.decompress(unrle).unwrap_err();
+        assert!(err.downcast_ref::<DecompressedSize>().is_some());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let bytes = DagCbor
+            .encode(&ipld!({ "algorithm": "rle", "data": Ipld::Bytes(vec![]) }))
+            .unwrap();
+        assert!(DagCbor.decode::<CompressedBytes>(&bytes).is_err());
+        let bytes = DagCbor
+            .encode(&ipld!({ "algorithm": "rle", "size": 0, "data": [] }))
+            .unwrap();
+        assert!(DagCbor.decode::<CompressedBytes>(&bytes).is_err());
+    }
+}
@@ -1,19 +1,28 @@
 //! CBOR decoder
 use crate::cbor::{Major, MajorKind, F32, F64, FALSE, NULL, TRUE};
 use crate::error::{
-    DuplicateKey, InvalidCidPrefix, LengthOutOfRange, NumberNotMinimal, NumberOutOfRange,
-    UnexpectedCode, UnexpectedEof, UnknownTag,
+    DuplicateKey, InvalidCidPrefix, InvalidMapKey, LengthOutOfRange, NumberNotMinimal,
+    NumberOutOfRange, UnexpectedCode, UnexpectedEof, UnknownTag,
 };
 use crate::DagCborCodec as DagCbor;
 use byteorder::{BigEndian, ByteOrder};
 use core::convert::TryFrom;
-use libipld_core::codec::{Decode, References};
-use libipld_core::error::Result;
+use libipld_core::codec::{Decode, DecodeRef, References, Tokens};
+pub use libipld_core::error::PartialIpld;
+use libipld_core::error::{Error, Result};
 use libipld_core::ipld::Ipld;
+use libipld_core::ipld_ref::IpldRef;
+use libipld_core::number::{FromNumber, Number, NumberPolicy};
+use libipld_core::token::Token;
+use libipld_core::typed_map::TypedMap;
 use libipld_core::{cid::Cid, raw_value::SkipOne};
 use std::collections::BTreeMap;
-use std::io::{Read, Seek, SeekFrom};
-use std::sync::Arc;
+use std::fmt::Display;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Reads a u8 from a byte stream.
 pub fn read_u8<R: Read>(r: &mut R) -> Result<u8> {
@@ -187,47 +196,42 @@ impl Decode<DagCbor> for bool {
     }
 }
 
-macro_rules! impl_num {
-    (unsigned $($t:ty),*) => {
-        $(
-            impl Decode<DagCbor> for $t {
-                fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
-                    let major = read_major(r)?;
-                    if major.kind() != MajorKind::UnsignedInt {
-                        return Err(UnexpectedCode::new::<Self>(major.into()).into());
-                    }
-                    let value = read_uint(r, major)?;
-                    Self::try_from(value).map_err(|_| NumberOutOfRange::new::<Self>().into())
-                }
-            }
-        )*
+/// Reads an integer or a float as a [`Number`].
+///
+/// The typed number decoders convert it with [`NumberPolicy::Strict`], decode a [`Number`] to
+/// convert it under another policy.
+pub fn read_number<R: Read>(r: &mut R) -> Result<Number> {
+    let major = read_major(r)?;
+    let number = match major.kind() {
+        MajorKind::UnsignedInt => Number::from(read_uint(r, major)? as i128),
+        // This is guaranteed to not overflow.
+        MajorKind::NegativeInt => Number::from(-1 - read_uint(r, major)? as i128),
+        _ if major == F32 => Number::F64(read_f32(r)?.into()),
+        _ if major == F64 => Number::F64(read_f64(r)?),
+        _ => return Err(UnexpectedCode::new::<Number>(major.into()).into()),
     };
-    (signed $($t:ty),*) => {
+    Ok(number)
+}
+
+impl Decode<DagCbor> for Number {
+    fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
+        read_number(r)
+    }
+}
+
+macro_rules! impl_num {
+    ($($t:ty),*) => {
         $(
             impl Decode<DagCbor> for $t {
                 fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
-                    let major = read_major(r)?;
-                    let value = read_uint(r, major)?;
-                    match major.kind() {
-                        MajorKind::UnsignedInt | MajorKind::NegativeInt => (),
-                        _ => return Err(UnexpectedCode::new::<Self>(major.into()).into()),
-                    };
-
-                    let mut value = Self::try_from(value)
-                        .map_err(|_| NumberOutOfRange::new::<Self>())?;
-                    if major.kind() == MajorKind::NegativeInt {
-                        // This is guaranteed to not overflow.
-                        value = -1 - value;
-                    }
-                    Ok(value)
+                    Ok(Self::from_number(read_number(r)?, NumberPolicy::Strict)?)
                 }
             }
         )*
     };
 }
 
-impl_num!(unsigned u8, u16, u32, u64, u128);
-impl_num!(signed i8, i16, i32, i64, i128);
+impl_num!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
 
 macro_rules! impl_nonzero {
     ($(($nzero:ty => $base:ty))*) => {
@@ -283,11 +287,7 @@ impl Decode<DagCbor> for f64 {
     fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
         // TODO: We don't accept f16
         // TODO: By IPLD spec, we shouldn't accept f32 either...
-        let num = match read_major(r)? {
-            F32 => read_f32(r)?.into(),
-            F64 => read_f64(r)?,
-            m => return Err(UnexpectedCode::new::<Self>(m.into()).into()),
-        };
+        let num = Self::from_number(read_number(r)?, NumberPolicy::Strict)?;
         // This is by IPLD spec, but is it widely used?
         if !num.is_finite() {
             return Err(NumberOutOfRange::new::<Self>().into());
@@ -368,6 +368,33 @@ impl<K: Decode<DagCbor> + Ord, T: Decode<DagCbor>> Decode<DagCbor> for BTreeMap<
     }
 }
 
+impl<K, V> Decode<DagCbor> for TypedMap<K, V>
+where
+    K: FromStr + Display + Ord,
+    V: Decode<DagCbor>,
+{
+    fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
+        let major = read_major(r)?;
+        if major.kind() != MajorKind::Map {
+            return Err(UnexpectedCode::new::<Self>(major.into()).into());
+        }
+        let len = read_uint(r, major)?;
+        let mut map = TypedMap::new();
+        for _ in 0..len {
+            let key = String::decode(DagCbor, r)?;
+            let typed_key = match K::from_str(&key) {
+                Ok(typed_key) if typed_key.to_string() == key => typed_key,
+                _ => return Err(InvalidMapKey(key).into()),
+            };
+            let value = V::decode(DagCbor, r)?;
+            if map.insert(typed_key, value).is_some() {
+                return Err(DuplicateKey.into());
+            }
+        }
+        Ok(map)
+    }
+}
+
 impl Decode<DagCbor> for Ipld {
     fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
         let major = read_major(r)?;
@@ -411,6 +438,145 @@ impl Decode<DagCbor> for Ipld {
     }
 }
 
+/// Borrows `len` bytes from the slice underlying the cursor.
+fn read_slice<'a>(r: &mut Cursor<&'a [u8]>, len: u64) -> Result<&'a [u8]> {
+    let bytes = *r.get_ref();
+    let start = usize::try_from(r.position()).map_err(|_| LengthOutOfRange::new::<usize>())?;
+    let end = usize::try_from(len)
+        .ok()
+        .and_then(|len| start.checked_add(len))
+        .filter(|end| *end <= bytes.len())
+        .ok_or(UnexpectedEof)?;
+    r.set_position(end as u64);
+    Ok(&bytes[start..end])
+}
+
+fn read_ref<'a>(r: &mut Cursor<&'a [u8]>) -> Result<IpldRef<'a>> {
+    let major = read_major(r)?;
+    let ipld = match major.kind() {
+        MajorKind::UnsignedInt => IpldRef::Integer(read_uint(r, major)? as i128),
+        MajorKind::NegativeInt => IpldRef::Integer(-1 - read_uint(r, major)? as i128),
+        MajorKind::ByteString => {
+            let len = read_uint(r, major)?;
+            IpldRef::Bytes(read_slice(r, len)?)
+        }
+        MajorKind::TextString => {
+            let len = read_uint(r, major)?;
+            IpldRef::String(std::str::from_utf8(read_slice(r, len)?)?)
+        }
+        MajorKind::Array => {
+            let len = read_uint(r, major)?;
+            let len = usize::try_from(len).map_err(|_| LengthOutOfRange::new::<usize>())?;
+            // Limit up-front allocations, the length is user controlled.
+            let mut list = Vec::with_capacity(len.min(1024));
+            for _ in 0..len {
+                list.push(read_ref(r)?);
+            }
+            IpldRef::List(list)
+        }
+        MajorKind::Map => {
+            let len = read_uint(r, major)?;
+            let mut map = BTreeMap::new();
+            for _ in 0..len {
+                let major = read_major(r)?;
+                if major.kind() != MajorKind::TextString {
+                    return Err(UnexpectedCode::new::<String>(major.into()).into());
+                }
+                let len = read_uint(r, major)?;
+                let key = std::str::from_utf8(read_slice(r, len)?)?;
+                if map.insert(key, read_ref(r)?).is_some() {
+                    return Err(DuplicateKey.into());
+                }
+            }
+            IpldRef::Map(map)
+        }
+        MajorKind::Tag => {
+            let value = read_uint(r, major)?;
+            if value == 42 {
+                IpldRef::Link(read_link(r)?)
+            } else {
+                return Err(UnknownTag(value).into());
+            }
+        }
+        MajorKind::Other => match major {
+            FALSE => IpldRef::Bool(false),
+            TRUE => IpldRef::Bool(true),
+            NULL => IpldRef::Null,
+            F32 => IpldRef::Float(read_f32(r)? as f64),
+            F64 => IpldRef::Float(read_f64(r)?),
+            m => return Err(UnexpectedCode::new::<IpldRef>(m.into()).into()),
+        },
+    };
+    Ok(ipld)
+}
+
+impl<'a> DecodeRef<'a, DagCbor> for IpldRef<'a> {
+    fn decode_ref(_: DagCbor, bytes: &'a [u8]) -> Result<Self> {
+        read_ref(&mut Cursor::new(bytes))
+    }
+}
+
+pub(crate) fn read_lenient<R: Read + Seek>(
+    r: &mut R,
+) -> std::result::Result<Ipld, (Option<Ipld>, Error)> {
+    let major = read_major(r).map_err(|err| (None, err))?;
+    match major.kind() {
+        MajorKind::Array => {
+            let len = read_uint(r, major).map_err(|err| (None, err))?;
+            let mut list = Vec::new();
+            for _ in 0..len {
+                match read_lenient(r) {
+                    Ok(ipld) => list.push(ipld),
+                    Err((partial, err)) => {
+                        list.extend(partial);
+                        return Err((Some(Ipld::List(list)), err));
+                    }
+                }
+            }
+            Ok(Ipld::List(list))
+        }
+        MajorKind::Map => {
+            let len = read_uint(r, major).map_err(|err| (None, err))?;
+            let mut map = BTreeMap::new();
+            for _ in 0..len {
+                let key = match String::decode(DagCbor, r) {
+                    Ok(key) => key,
+                    Err(err) => return Err((Some(Ipld::Map(map)), err)),
+                };
+                match read_lenient(r) {
+                    Ok(ipld) => {
+                        if map.insert(key, ipld).is_some() {
+                            return Err((Some(Ipld::Map(map)), DuplicateKey.into()));
+                        }
+                    }
+                    Err((partial, err)) => {
+                        if let Some(partial) = partial {
+                            map.insert(key, partial);
+                        }
+                        return Err((Some(Ipld::Map(map)), err));
+                    }
+                }
+            }
+            Ok(Ipld::Map(map))
+        }
+        _ => {
+            r.seek(SeekFrom::Current(-1))
+                .map_err(|err| (None, err.into()))?;
+            Ipld::decode(DagCbor, r).map_err(|err| (None, err))
+        }
+    }
+}
+
+impl Tokens<DagCbor> for Ipld {
+    fn tokens<R: Read + Seek, F: FnMut(Token) -> Result<()>>(
+        _: DagCbor,
+        r: &mut R,
+        f: &mut F,
+    ) -> Result<()> {
+        read_tokens(r, f)
+    }
+}
+
 impl References<DagCbor> for Ipld {
     fn references<R: Read + Seek, E: Extend<Cid>>(
         _: DagCbor,
@@ -465,9 +631,134 @@ impl References<DagCbor> for Ipld {
     }
 }
 
-impl<T: Decode<DagCbor>> Decode<DagCbor> for Arc<T> {
+/// Reads a value as a stream of [`Token`]s passed to `f`, without building an [`Ipld`].
+///
+/// Keys are emitted in the order they are encoded, duplicates aren't detected. Decoding stops at
+/// the first error returned by `f`.
+pub fn read_tokens<R: Read + Seek, F: FnMut(Token) -> Result<()>>(
+    r: &mut R,
+    f: &mut F,
+) -> Result<()> {
+    let major = read_major(r)?;
+    match major.kind() {
+        MajorKind::Array => {
+            let len = read_uint(r, major)?;
+            f(Token::ListStart)?;
+            for _ in 0..len {
+                read_tokens(r, f)?;
+            }
+            f(Token::ListEnd)
+        }
+        MajorKind::Map => {
+            let len = read_uint(r, major)?;
+            f(Token::MapStart)?;
+            for _ in 0..len {
+                f(Token::Key(String::decode(DagCbor, r)?))?;
+                read_tokens(r, f)?;
+            }
+            f(Token::MapEnd)
+        }
+        _ => {
+            r.seek(SeekFrom::Current(-1))?;
+            Ipld::decode(DagCbor, r)?.into_tokens(f)
+        }
+    }
+}
+
+impl<T: Decode<DagCbor>, const N: usize> Decode<DagCbor> for [T; N] {
+    fn decode<R: Read + Seek>(_: DagCbor, r: &mut R) -> Result<Self> {
+        let major = read_major(r)?;
+        if major.kind() != MajorKind::Array {
+            return Err(UnexpectedCode::new::<Self>(major.into()).into());
+        }
+        let len = read_uint(r, major)?;
+        if len != N as u64 {
+            return Err(LengthOutOfRange::new::<Self>().into());
+        }
+        let list: Vec<T> = read_list(r, len)?;
+        list.try_into()
+            .map_err(|_| LengthOutOfRange::new::<Self>().into())
+    }
+}
+
+impl Decode<DagCbor> for Duration {
+    fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
+        let (secs, nanos): (u64, u32) = Decode::decode(c, r)?;
+        if nanos >= 1_000_000_000 {
+            return Err(NumberOutOfRange::new::<Self>().into());
+        }
+        Ok(Duration::new(secs, nanos))
+    }
+}
+
+impl Decode<DagCbor> for SystemTime {
+    fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
+        let (secs, nanos): (i64, u32) = Decode::decode(c, r)?;
+        if nanos >= 1_000_000_000 {
+            return Err(NumberOutOfRange::new::<Self>().into());
+        }
+        let time = if secs >= 0 {
+            UNIX_EPOCH.checked_add(Duration::new(secs as u64, nanos))
+        } else {
+            UNIX_EPOCH
+                .checked_sub(Duration::from_secs(secs.unsigned_abs()))
+                .and_then(|time| time.checked_add(Duration::from_nanos(nanos as u64)))
+        };
+        time.ok_or_else(|| NumberOutOfRange::new::<Self>().into())
+    }
+}
+
+impl Decode<DagCbor> for PathBuf {
     fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
-        Ok(Arc::new(T::decode(c, r)?))
+        Ok(String::decode(c, r)?.into())
+    }
+}
+
+impl Decode<DagCbor> for Ipv4Addr {
+    fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
+        let bytes = Box::<[u8]>::decode(c, r)?;
+        let octets: [u8; 4] = (*bytes)
+            .try_into()
+            .map_err(|_| LengthOutOfRange::new::<Self>())?;
+        Ok(octets.into())
+    }
+}
+
+impl Decode<DagCbor> for Ipv6Addr {
+    fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
+        let bytes = Box::<[u8]>::decode(c, r)?;
+        let octets: [u8; 16] = (*bytes)
+            .try_into()
+            .map_err(|_| LengthOutOfRange::new::<Self>())?;
+        Ok(octets.into())
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl Decode<DagCbor> for uuid::Uuid {
+    fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
+        let bytes = Box::<[u8]>::decode(c, r)?;
+        Self::from_slice(&bytes).map_err(|_| LengthOutOfRange::new::<Self>().into())
+    }
+}
+
+impl Decode<DagCbor> for IpAddr {
+    fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
+        let bytes = Box::<[u8]>::decode(c, r)?;
+        if let Ok(octets) = <[u8; 4]>::try_from(&*bytes) {
+            Ok(Ipv4Addr::from(octets).into())
+        } else if let Ok(octets) = <[u8; 16]>::try_from(&*bytes) {
+            Ok(Ipv6Addr::from(octets).into())
+        } else {
+            Err(LengthOutOfRange::new::<Self>().into())
+        }
+    }
+}
+
+impl Decode<DagCbor> for SocketAddr {
+    fn decode<R: Read + Seek>(c: DagCbor, r: &mut R) -> Result<Self> {
+        let (ip, port): (IpAddr, u16) = Decode::decode(c, r)?;
+        Ok(Self::new(ip, port))
     }
 }
 
@@ -598,11 +889,43 @@ mod tests {
     use super::*;
     use crate::{error::UnexpectedEof, DagCborCodec};
     use libipld_core::codec::Codec;
+    use libipld_core::error::NumberConversionError;
     use std::num::{
         NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroU128, NonZeroU16,
         NonZeroU32, NonZeroU64, NonZeroU8,
     };
 
+    #[test]
+    fn ipld_ref() {
+        let cid =
+            Cid::try_from("bafyreibvjvcv745gig4mvqs4hctx4zfkono4rjejm2ta6gtyzkqxfjeily").unwrap();
+        let ipld = libipld_macro::ipld!({
+            "bytes": Ipld::Bytes(vec![1, 2, 3]),
+            "list": [cid, "text", -5, 1.5, null, true],
+            "link": cid,
+        });
+        let bytes = DagCborCodec.encode(&ipld).unwrap();
+        let ipld_ref: IpldRef = DagCborCodec.decode_ref(&bytes).unwrap();
+        assert_eq!(ipld_ref.to_ipld(), ipld);
+        // Strings and bytes point into the encoded block.
+        match &ipld_ref {
+            IpldRef::Map(map) => match map["bytes"] {
+                IpldRef::Bytes(b) => assert!(bytes.as_ptr_range().contains(&b.as_ptr())),
+                _ => panic!("expected bytes"),
+            },
+            _ => panic!("expected a map"),
+        }
+
+        assert!(DagCborCodec.decode_ref::<IpldRef>(&bytes[..10]).is_err());
+        assert!(DagCborCodec
+            .decode_ref::<IpldRef>(&[0x5a, 0xff, 0xff, 0xff, 0xff])
+            .is_err());
+        // Maps with integer keys aren't valid dag-cbor.
+        assert!(DagCborCodec
+            .decode_ref::<IpldRef>(&[0xa1, 0x01, 0x01])
+            .is_err());
+    }
+
     #[test]
     fn il_map() {
         let bytes = [
@@ -634,6 +957,45 @@ mod tests {
             .expect("expected an unexpected eof");
     }
 
+    #[test]
+    fn lenient_truncated() {
+        let ipld = Ipld::List(vec![
+            Ipld::Integer(1),
+            Ipld::Map(BTreeMap::from([
+                ("a".to_string(), Ipld::Bool(true)),
+                ("b".to_string(), Ipld::String("hello".into())),
+            ])),
+        ]);
+        let bytes = DagCborCodec.encode(&ipld).unwrap();
+        assert_eq!(DagCborCodec.decode_lenient(&bytes).unwrap(), ipld);
+
+        // Cut off in the middle of the "hello" string.
+        let partial = DagCborCodec
+            .decode_lenient(&bytes[..bytes.len() - 2])
+            .unwrap_err();
+        assert_eq!(
+            partial.ipld,
+            Some(Ipld::List(vec![
+                Ipld::Integer(1),
+                Ipld::Map(BTreeMap::from([("a".to_string(), Ipld::Bool(true))])),
+            ]))
+        );
+        assert!(partial.error.downcast_ref::<UnexpectedEof>().is_some());
+        assert_eq!(partial.offset, bytes.len() as u64 - 2);
+    }
+
+    #[test]
+    fn lenient_invalid() {
+        // A list with an unknown tag as its second element.
+        let bytes = [0x82, 0x01, 0xd8, 0x2b, 0x01];
+        let partial = DagCborCodec.decode_lenient(&bytes).unwrap_err();
+        assert_eq!(partial.ipld, Some(Ipld::List(vec![Ipld::Integer(1)])));
+        assert!(partial.error.downcast_ref::<UnknownTag>().is_some());
+
+        let partial = DagCborCodec.decode_lenient(&[0xff]).unwrap_err();
+        assert_eq!(partial.ipld, None);
+    }
+
     #[test]
     #[allow(clippy::let_unit_value)]
     fn tuples() -> Result<()> {
@@ -664,6 +1026,27 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn numbers() -> Result<()> {
+        let bytes = DagCborCodec.encode(&300u64)?;
+        assert_eq!(DagCborCodec.decode::<u16>(&bytes)?, 300);
+        let err = DagCborCodec.decode::<u8>(&bytes).unwrap_err();
+        assert!(err.downcast_ref::<NumberConversionError>().is_some());
+
+        let bytes = DagCborCodec.encode(&-1i8)?;
+        assert!(DagCborCodec.decode::<u64>(&bytes).is_err());
+        assert_eq!(DagCborCodec.decode::<Number>(&bytes)?, Number::I64(-1));
+
+        // Floats don't decode as integers and vice versa, unless converted with another policy.
+        let bytes = DagCborCodec.encode(&2.5f64)?;
+        assert!(DagCborCodec.decode::<i32>(&bytes).is_err());
+        let number: Number = DagCborCodec.decode(&bytes)?;
+        assert_eq!(number.to::<i32>(NumberPolicy::Round)?, 3);
+        let bytes = DagCborCodec.encode(&1u8)?;
+        assert!(DagCborCodec.decode::<f64>(&bytes).is_err());
+        Ok(())
+    }
+
     #[test]
     fn nonzero_int() -> Result<()> {
         let bytes = DagCborCodec.encode(&42i128)?;
@@ -0,0 +1,311 @@
+//! CBOR diagnostic notation.
+//!
+//! [`Diagnostic`] renders [`Ipld`] in the textual notation described in RFC 8949 section 8,
+//! [`parse`] parses a safe subset of it back. Only the constructs that map onto the IPLD data
+//! model are supported: no indefinite lengths, no `undefined` and no tags other than 42 (links).
+use std::collections::BTreeMap;
+
+use libipld_core::cid::Cid;
+pub use libipld_core::diag::{Diagnostic, LinkFormat};
+use libipld_core::error::Result;
+use libipld_core::ipld::Ipld;
+
+use crate::error::InvalidDiagnostic;
+
+/// Parses diagnostic notation into an [`Ipld`].
+///
+/// Accepts everything [`Diagnostic`] produces, in either [`LinkFormat`].
+pub fn parse(s: &str) -> Result<Ipld> {
+    let mut parser = Parser { s, pos: 0 };
+    let ipld = parser.parse_value()?;
+    parser.skip_ws();
+    if parser.pos != s.len() {
+        return Err(parser.error("trailing characters").into());
+    }
+    Ok(ipld)
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: &'static str) -> InvalidDiagnostic {
+        InvalidDiagnostic {
+            offset: self.pos,
+            msg,
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_ws(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        self.skip_ws();
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            Ok(())
+        } else {
+            Err(self.error("unexpected character").into())
+        }
+    }
+
+    /// Consumes `c` if it is the next non-whitespace character.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_ws();
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.rest().starts_with(keyword) {
+            self.pos += keyword.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Ipld> {
+        self.skip_ws();
+        match self.peek() {
+            Some('[') => self.parse_list(),
+            Some('{') => self.parse_map(),
+            Some('"') => Ok(Ipld::String(self.parse_str()?)),
+            Some('h') if self.rest().starts_with("h'") => Ok(Ipld::Bytes(self.parse_bytes()?)),
+            Some('-' | '0'..='9' | 'N' | 'I') => self.parse_number(),
+            _ if self.eat_keyword("null") => Ok(Ipld::Null),
+            _ if self.eat_keyword("true") => Ok(Ipld::Bool(true)),
+            _ if self.eat_keyword("false") => Ok(Ipld::Bool(false)),
+            _ => Err(self.error("expected a value").into()),
+        }
+    }
+
+    fn parse_list(&mut self) -> Result<Ipld> {
+        self.expect('[')?;
+        let mut list = Vec::new();
+        if self.eat(']') {
+            return Ok(Ipld::List(list));
+        }
+        loop {
+            list.push(self.parse_value()?);
+            if self.eat(']') {
+                return Ok(Ipld::List(list));
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn parse_map(&mut self) -> Result<Ipld> {
+        self.expect('{')?;
+        let mut map = BTreeMap::new();
+        if self.eat('}') {
+            return Ok(Ipld::Map(map));
+        }
+        loop {
+            self.skip_ws();
+            if self.peek() != Some('"') {
+                return Err(self.error("map keys must be strings").into());
+            }
+            let key = self.parse_str()?;
+            self.expect(':')?;
+            let value = self.parse_value()?;
+            if map.insert(key, value).is_some() {
+                return Err(self.error("duplicate map key").into());
+            }
+            if self.eat('}') {
+                return Ok(Ipld::Map(map));
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn parse_str(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(s);
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/') => s.push('/'),
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('u') => {
+                        let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                        let c = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| self.error("invalid unicode escape"))?;
+                        s.push(c);
+                    }
+                    _ => return Err(self.error("invalid escape").into()),
+                },
+                c => s.push(c),
+            }
+        }
+        Err(self.error("unterminated string").into())
+    }
+
+    fn parse_bytes(&mut self) -> Result<Vec<u8>> {
+        self.pos += "h'".len();
+        let end = self
+            .rest()
+            .find('\'')
+            .ok_or_else(|| self.error("unterminated byte string"))?;
+        let digits: Vec<u8> = self.rest()[..end]
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<_>>()
+            .ok_or_else(|| self.error("invalid hex digit"))?;
+        if !digits.len().is_multiple_of(2) {
+            return Err(self.error("odd number of hex digits").into());
+        }
+        self.pos += end + 1;
+        Ok(digits
+            .chunks(2)
+            .map(|pair| pair[0] << 4 | pair[1])
+            .collect())
+    }
+
+    fn parse_number(&mut self) -> Result<Ipld> {
+        for (keyword, value) in [
+            ("NaN", f64::NAN),
+            ("Infinity", f64::INFINITY),
+            ("-Infinity", f64::NEG_INFINITY),
+        ] {
+            if self.eat_keyword(keyword) {
+                return Ok(Ipld::Float(value));
+            }
+        }
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !matches!(c, '-' | '+' | '.' | 'e' | 'E' | '0'..='9'))
+            .unwrap_or(rest.len());
+        let number = &rest[..len];
+        if number.contains(['.', 'e', 'E']) {
+            let float = number.parse().map_err(|_| self.error("invalid float"))?;
+            self.pos += len;
+            return Ok(Ipld::Float(float));
+        }
+        let int = number.parse().map_err(|_| self.error("invalid integer"))?;
+        self.pos += len;
+        if self.eat('(') {
+            return self.parse_tag(int);
+        }
+        Ok(Ipld::Integer(int))
+    }
+
+    fn parse_tag(&mut self, tag: i128) -> Result<Ipld> {
+        if tag != 42 {
+            return Err(self.error("only tag 42 is supported").into());
+        }
+        self.skip_ws();
+        let cid = match self.peek() {
+            Some('"') => {
+                let s = self.parse_str()?;
+                Cid::try_from(s.as_str()).map_err(|_| self.error("invalid cid"))?
+            }
+            Some('h') if self.rest().starts_with("h'") => {
+                let bytes = self.parse_bytes()?;
+                match bytes.split_first() {
+                    Some((0, cid)) => Cid::try_from(cid).map_err(|_| self.error("invalid cid"))?,
+                    _ => return Err(self.error("invalid cid prefix").into()),
+                }
+            }
+            _ => return Err(self.error("expected a cid").into()),
+        };
+        self.expect(')')?;
+        Ok(Ipld::Link(cid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DagCborCodec;
+    use libipld_core::codec::Codec;
+    use libipld_core::multihash::{Code, MultihashDigest};
+    use libipld_macro::ipld;
+
+    #[test]
+    fn test_display() {
+        let ipld = ipld!({
+            "list": [1, -2, 1.5, null, true],
+            "a": "quote\"",
+            "bytes": vec![0u8, 1, 255],
+        });
+        assert_eq!(
+            Diagnostic::new(&ipld).to_string(),
+            r#"{"a": "quote\"", "list": [1, -2, 1.5, null, true], "bytes": h'0001ff'}"#
+        );
+    }
+
+    #[test]
+    fn test_display_links() {
+        let cid = Cid::new_v1(0x71, Code::Blake3_256.digest(&b"cid"[..]));
+        let ipld = Ipld::Link(cid);
+        let bytes = Diagnostic::new(&ipld).to_string();
+        assert!(bytes.starts_with("42(h'0001711e20"));
+        let string = Diagnostic::new(&ipld).links(LinkFormat::Cid).to_string();
+        assert_eq!(string, format!("42(\"{}\")", cid));
+        assert_eq!(parse(&bytes).unwrap(), ipld);
+        assert_eq!(parse(&string).unwrap(), ipld);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let cid = Cid::new_v1(0x71, Code::Blake3_256.digest(&b"cid"[..]));
+        let ipld = ipld!({
+            "number": 1,
+            "list": [true, null, false, -1.0e100],
+            "bytes": vec![0, 1, 2, 3],
+            "map": { "float": 0.0, "string": "hello\n\u{1}" },
+            "link": cid,
+        });
+        let diag = Diagnostic::new(&ipld).to_string();
+        assert_eq!(parse(&diag).unwrap(), ipld);
+        // Re-encoding the parsed value gives the same block.
+        let bytes = DagCborCodec.encode(&ipld).unwrap();
+        let parsed = parse(&diag).unwrap();
+        assert_eq!(DagCborCodec.encode(&parsed).unwrap(), bytes);
+        // The `Display` output of `Ipld` is diagnostic notation as well.
+        assert_eq!(parse(&ipld.to_string()).unwrap(), ipld);
+        assert_eq!(parse(&format!("{:#}", ipld)).unwrap(), ipld);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("[1, 2").is_err());
+        assert!(parse("{1: 2}").is_err());
+        assert!(parse("h'abc'").is_err());
+        assert!(parse("24(1)").is_err());
+        assert!(parse("1 2").is_err());
+        let err = parse("[1, x]").unwrap_err();
+        assert_eq!(err.downcast::<InvalidDiagnostic>().unwrap().offset, 4);
+    }
+}
@@ -2,19 +2,22 @@
 
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
+use std::fmt::Display;
 use std::io::Write;
 use std::iter::FromIterator;
-use std::ops::Deref;
-use std::sync::Arc;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use byteorder::{BigEndian, ByteOrder};
 use libipld_core::cid::Cid;
 use libipld_core::codec::Encode;
 use libipld_core::error::Result;
 use libipld_core::ipld::Ipld;
+use libipld_core::typed_map::TypedMap;
 
 use crate::cbor::{MajorKind, FALSE, TRUE};
-use crate::error::NumberOutOfRange;
+use crate::error::{DuplicateKey, InvalidPath, NumberOutOfRange};
 use crate::DagCborCodec as DagCbor;
 
 /// Writes a null byte to a cbor encoded byte stream.
@@ -201,12 +204,6 @@ impl Encode<DagCbor> for [u8] {
     }
 }
 
-impl Encode<DagCbor> for Box<[u8]> {
-    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
-        self[..].encode(c, w)
-    }
-}
-
 impl Encode<DagCbor> for str {
     fn encode<W: Write>(&self, _: DagCbor, w: &mut W) -> Result<()> {
         write_u64(w, MajorKind::TextString, self.len() as u64)?;
@@ -273,6 +270,25 @@ impl<T: Encode<DagCbor>> Encode<DagCbor> for Vec<T> {
     }
 }
 
+impl<K: Display, V: Encode<DagCbor>> Encode<DagCbor> for TypedMap<K, V> {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        let mut cbor_order: Vec<_> = self.iter().map(|(k, v)| (k.to_string(), v)).collect();
+        // Same order as for `BTreeMap<String, T>`.
+        cbor_order.sort_unstable_by(|(key_a, _), (key_b, _)| {
+            (key_a.len(), key_a).cmp(&(key_b.len(), key_b))
+        });
+        if cbor_order.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return Err(DuplicateKey.into());
+        }
+        write_u64(w, MajorKind::Map, self.len() as u64)?;
+        for (k, v) in cbor_order {
+            k.encode(c, w)?;
+            v.encode(c, w)?;
+        }
+        Ok(())
+    }
+}
+
 impl<T: Encode<DagCbor> + 'static> Encode<DagCbor> for BTreeMap<String, T> {
     fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
         write_u64(w, MajorKind::Map, self.len() as u64)?;
@@ -311,9 +327,91 @@ impl Encode<DagCbor> for Ipld {
     }
 }
 
-impl<T: Encode<DagCbor>> Encode<DagCbor> for Arc<T> {
+impl<T: Encode<DagCbor>, const N: usize> Encode<DagCbor> for [T; N] {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        write_u64(w, MajorKind::Array, N as u64)?;
+        for value in self {
+            value.encode(c, w)?;
+        }
+        Ok(())
+    }
+}
+
+/// Encoded as a `[seconds, nanoseconds]` list.
+impl Encode<DagCbor> for Duration {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        (self.as_secs(), self.subsec_nanos()).encode(c, w)
+    }
+}
+
+/// Encoded as a `[seconds, nanoseconds]` list relative to the unix epoch. The seconds are negative
+/// for times before the epoch, the nanoseconds are always positive.
+impl Encode<DagCbor> for SystemTime {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        let (secs, nanos) = match self.duration_since(UNIX_EPOCH) {
+            Ok(after) => (after.as_secs() as i128, after.subsec_nanos()),
+            Err(err) => {
+                let before = err.duration();
+                match before.subsec_nanos() {
+                    0 => (-(before.as_secs() as i128), 0),
+                    nanos => (-(before.as_secs() as i128) - 1, 1_000_000_000 - nanos),
+                }
+            }
+        };
+        let secs = i64::try_from(secs).map_err(|_| NumberOutOfRange::new::<SystemTime>())?;
+        (secs, nanos).encode(c, w)
+    }
+}
+
+/// Encoded as a string. Fails if the path isn't valid UTF-8.
+impl Encode<DagCbor> for Path {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        self.to_str().ok_or(InvalidPath)?.encode(c, w)
+    }
+}
+
+impl Encode<DagCbor> for PathBuf {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        self.as_path().encode(c, w)
+    }
+}
+
+/// Encoded as the 4 bytes of the address.
+impl Encode<DagCbor> for Ipv4Addr {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        self.octets()[..].encode(c, w)
+    }
+}
+
+/// Encoded as the 16 bytes of the address.
+impl Encode<DagCbor> for Ipv6Addr {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        self.octets()[..].encode(c, w)
+    }
+}
+
+/// Encoded as 4 or 16 bytes, depending on the version.
+impl Encode<DagCbor> for IpAddr {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        match self {
+            Self::V4(ip) => ip.encode(c, w),
+            Self::V6(ip) => ip.encode(c, w),
+        }
+    }
+}
+
+/// Encoded as the 16 bytes of the uuid.
+#[cfg(feature = "uuid")]
+impl Encode<DagCbor> for uuid::Uuid {
+    fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
+        self.as_bytes()[..].encode(c, w)
+    }
+}
+
+/// Encoded as an `[ip, port]` list. The flow info and scope id of IPv6 addresses are dropped.
+impl Encode<DagCbor> for SocketAddr {
     fn encode<W: Write>(&self, c: DagCbor, w: &mut W) -> Result<()> {
-        self.deref().encode(c, w)
+        (self.ip(), self.port()).encode(c, w)
     }
 }
 
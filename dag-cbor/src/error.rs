@@ -120,3 +120,43 @@ pub struct InvalidCidPrefix(pub u8);
 #[derive(Debug, Error)]
 #[error("Duplicate map key.")]
 pub struct DuplicateKey;
+
+/// A map key that can't be parsed into the key type, or isn't in its canonical form.
+#[derive(Debug, Error)]
+#[error("Invalid map key `{0}`.")]
+pub struct InvalidMapKey(pub String);
+
+/// Invalid diagnostic notation.
+#[derive(Debug, Error)]
+#[error("Invalid diagnostic notation at offset {offset}: {msg}.")]
+pub struct InvalidDiagnostic {
+    /// Byte offset of the error.
+    pub offset: usize,
+    /// Description of the error.
+    pub msg: &'static str,
+}
+
+/// A shared reference to a value that wasn't marked as shareable before.
+#[derive(Debug, Error)]
+#[error("Invalid shared reference `{0}`.")]
+pub struct InvalidSharedRef(pub u64);
+
+/// Shared references expand to a value larger than the limit.
+#[derive(Debug, Error)]
+#[error("Shared value larger than the limit of {0}.")]
+pub struct SharedLimitExceeded(pub usize);
+
+/// Decompressed data doesn't have the recorded size.
+#[derive(Debug, Error)]
+#[error("Decompressed {actual} bytes, expected {expected}.")]
+pub struct DecompressedSize {
+    /// The recorded size.
+    pub expected: u64,
+    /// The size of the decompressed data.
+    pub actual: u64,
+}
+
+/// A path that isn't valid UTF-8.
+#[derive(Debug, Error)]
+#[error("Path is not valid UTF-8.")]
+pub struct InvalidPath;
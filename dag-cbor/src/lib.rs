@@ -4,12 +4,23 @@
 
 use core::convert::TryFrom;
 use libipld_core::codec::{Codec, Decode, Encode};
+use libipld_core::error::PartialIpld;
 pub use libipld_core::error::{Result, UnsupportedCodec};
+use libipld_core::ipld::Ipld;
+use libipld_core::token::Token;
+use std::io::Cursor;
 
 pub mod cbor;
+pub mod compressed;
 pub mod decode;
+pub mod diag;
 pub mod encode;
 pub mod error;
+pub mod paged;
+pub mod plain;
+#[cfg(feature = "serde-codec")]
+pub mod serde_codec;
+pub mod shared;
 
 /// CBOR codec.
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
@@ -17,6 +28,27 @@ pub struct DagCborCodec;
 
 impl Codec for DagCborCodec {}
 
+impl DagCborCodec {
+    /// Decodes a block as a stream of tokens, see [`decode::read_tokens`].
+    pub fn tokens<F: FnMut(Token) -> Result<()>>(&self, bytes: &[u8], mut f: F) -> Result<()> {
+        decode::read_tokens(&mut Cursor::new(bytes), &mut f)
+    }
+
+    /// Decodes an [`Ipld`], keeping what was decoded so far if the block turns out to be corrupt.
+    ///
+    /// Unlike the regular decoder, which discards everything on the first error, this returns the
+    /// partially decoded value together with the error and its byte offset. Useful for inspecting
+    /// truncated or otherwise damaged blocks.
+    pub fn decode_lenient(&self, bytes: &[u8]) -> core::result::Result<Ipld, PartialIpld> {
+        let mut r = Cursor::new(bytes);
+        decode::read_lenient(&mut r).map_err(|(ipld, error)| PartialIpld {
+            ipld,
+            error,
+            offset: r.position(),
+        })
+    }
+}
+
 impl From<DagCborCodec> for u64 {
     fn from(_: DagCborCodec) -> Self {
         0x71
@@ -31,6 +63,50 @@ impl TryFrom<u64> for DagCborCodec {
     }
 }
 
+/// Plain CBOR codec.
+///
+/// Decodes CBOR that isn't valid dag-cbor, see the [`plain`] module. Tags other than 42 are
+/// rejected when decoding, unless they are stripped. Encoding produces dag-cbor, unless indefinite
+/// lengths are enabled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CborCodec {
+    indefinite_lengths: bool,
+    strip_tags: bool,
+}
+
+impl CborCodec {
+    /// Decode items tagged with tags other than 42 as if they weren't tagged, dropping the tag.
+    pub fn with_stripped_tags(mut self, strip_tags: bool) -> Self {
+        self.strip_tags = strip_tags;
+        self
+    }
+
+    /// Encode lists and maps with indefinite lengths.
+    pub fn with_indefinite_lengths(mut self, indefinite_lengths: bool) -> Self {
+        self.indefinite_lengths = indefinite_lengths;
+        self
+    }
+}
+
+impl Codec for CborCodec {}
+
+impl From<CborCodec> for u64 {
+    fn from(_: CborCodec) -> Self {
+        0x51
+    }
+}
+
+impl TryFrom<u64> for CborCodec {
+    type Error = UnsupportedCodec;
+
+    fn try_from(code: u64) -> core::result::Result<Self, Self::Error> {
+        match code {
+            0x51 => Ok(Self::default()),
+            _ => Err(UnsupportedCodec(code)),
+        }
+    }
+}
+
 /// Marker trait for types supporting the `DagCborCodec`.
 pub trait DagCbor: Encode<DagCborCodec> + Decode<DagCborCodec> {}
 
@@ -41,6 +117,7 @@ mod tests {
     use super::*;
     use libipld_core::cid::Cid;
     use libipld_core::codec::assert_roundtrip;
+    use libipld_core::error::Error;
     use libipld_core::ipld::Ipld;
     use libipld_core::multihash::{Code, MultihashDigest};
     use libipld_macro::ipld;
@@ -98,4 +175,136 @@ mod tests {
         assert_roundtrip(DagCborCodec, &u32::MIN, &Ipld::Integer(u32::MIN as i128));
         assert_roundtrip(DagCborCodec, &u64::MIN, &Ipld::Integer(u64::MIN as i128));
     }
+
+    #[test]
+    fn test_std_types() {
+        use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+        use std::path::PathBuf;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        assert_roundtrip(DagCborCodec, &[1u8, 2, 3], &ipld!([1, 2, 3]));
+        assert_roundtrip(DagCborCodec, &Duration::new(5, 1), &ipld!([5, 1]));
+        assert_roundtrip(
+            DagCborCodec,
+            &(UNIX_EPOCH + Duration::new(5, 1)),
+            &ipld!([5, 1]),
+        );
+        assert_roundtrip(
+            DagCborCodec,
+            &(UNIX_EPOCH - Duration::new(5, 1)),
+            &ipld!([-6, 999_999_999]),
+        );
+        assert_roundtrip(DagCborCodec, &PathBuf::from("a/b"), &ipld!("a/b"));
+        let ip = IpAddr::from(Ipv4Addr::new(127, 0, 0, 1));
+        assert_roundtrip(DagCborCodec, &ip, &Ipld::Bytes(vec![127, 0, 0, 1]));
+        assert_roundtrip(
+            DagCborCodec,
+            &IpAddr::from(Ipv6Addr::LOCALHOST),
+            &Ipld::Bytes(Ipv6Addr::LOCALHOST.octets().to_vec()),
+        );
+        assert_roundtrip(
+            DagCborCodec,
+            &SocketAddr::new(ip, 80),
+            &ipld!([Ipld::Bytes(vec![127, 0, 0, 1]), 80]),
+        );
+
+        let bytes = DagCborCodec.encode(&ipld!([1, 2])).unwrap();
+        assert!(DagCborCodec.decode::<[u8; 3]>(&bytes).is_err());
+        let bytes = DagCborCodec.encode(&ipld!([1, 1_000_000_000])).unwrap();
+        assert!(DagCborCodec.decode::<Duration>(&bytes).is_err());
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn test_uuid() {
+        let uuid = uuid::Uuid::from_bytes([7; 16]);
+        assert_roundtrip(DagCborCodec, &uuid, &Ipld::Bytes(vec![7; 16]));
+        let bytes = DagCborCodec.encode(&Ipld::Bytes(vec![7; 15])).unwrap();
+        assert!(DagCborCodec.decode::<uuid::Uuid>(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_pointers() {
+        use std::borrow::Cow;
+        use std::rc::Rc;
+        use std::sync::Arc;
+
+        assert_roundtrip(DagCborCodec, &Box::new(1u8), &ipld!(1));
+        assert_roundtrip(DagCborCodec, &Rc::new(true), &ipld!(true));
+        assert_roundtrip(DagCborCodec, &Cow::<str>::Owned("a".into()), &ipld!("a"));
+        assert_eq!(
+            DagCborCodec.encode(&Cow::Borrowed("a")).unwrap(),
+            DagCborCodec.encode("a").unwrap()
+        );
+        let shared: Arc<str> = "a".into();
+        assert_eq!(
+            DagCborCodec.encode(&shared).unwrap(),
+            DagCborCodec.encode(&&*shared).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_typed_map() {
+        use libipld_core::typed_map::TypedMap;
+
+        let map: TypedMap<u64, bool> = [(10, true), (9, false)].into_iter().collect();
+        assert_roundtrip(DagCborCodec, &map, &ipld!({"9": false, "10": true}));
+
+        let decode = |ipld: Ipld| {
+            let bytes = DagCborCodec.encode(&ipld).unwrap();
+            DagCborCodec.decode::<TypedMap<u64, bool>>(&bytes)
+        };
+        assert!(decode(ipld!({"x": true})).is_err());
+        assert!(decode(ipld!({"09": true})).is_err());
+        assert!(decode(ipld!([])).is_err());
+    }
+
+    #[test]
+    fn test_codec_code() {
+        assert_eq!(u64::from(CborCodec::default()), 0x51);
+        assert_eq!(CborCodec::try_from(0x51).unwrap(), CborCodec::default());
+        assert_eq!(CborCodec::try_from(0x71).unwrap_err().0, 0x71);
+    }
+
+    #[test]
+    fn test_tokens() {
+        let cid = Cid::new_v1(0, Code::Blake3_256.digest(&b"0"[..]));
+        let ipld = ipld!({
+            "a": [cid, "b", cid],
+            "b": vec![1u8, 2],
+            "c": {},
+        });
+        let bytes = DagCborCodec.encode(&ipld).unwrap();
+
+        let mut tokens = Vec::new();
+        DagCborCodec
+            .tokens(&bytes, |token| {
+                tokens.push(token);
+                Ok(())
+            })
+            .unwrap();
+        let mut expected = Vec::new();
+        ipld.into_tokens(&mut |token| {
+            expected.push(token);
+            Ok::<_, ()>(())
+        })
+        .unwrap();
+        assert_eq!(tokens, expected);
+
+        // Stop at the first link.
+        let mut links = 0;
+        let err = DagCborCodec.tokens(&bytes, |token| match token {
+            Token::Link(_) => {
+                links += 1;
+                Err(Error::msg("found"))
+            }
+            _ => Ok(()),
+        });
+        assert_eq!(err.unwrap_err().to_string(), "found");
+        assert_eq!(links, 1);
+
+        assert!(DagCborCodec
+            .tokens(&bytes[..bytes.len() - 1], |_| Ok(()))
+            .is_err());
+    }
 }
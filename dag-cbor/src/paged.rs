@@ -0,0 +1,125 @@
+//! Paged access to large lists.
+//!
+//! [`PagedList`] scans a dag-cbor encoded list once, skipping over its elements to record their
+//! offsets, and then decodes only the elements that are asked for.
+use crate::cbor::MajorKind;
+use crate::decode::{read_major, read_uint};
+use crate::error::{LengthOutOfRange, UnexpectedCode, UnexpectedEof};
+use crate::DagCborCodec as DagCbor;
+use libipld_core::codec::Decode;
+use libipld_core::error::Result;
+use libipld_core::ipld::Ipld;
+use libipld_core::raw_value::SkipOne;
+use std::io::Cursor;
+use std::ops::Range;
+
+/// A dag-cbor encoded list, indexed by the offsets of its elements.
+#[derive(Clone, Debug)]
+pub struct PagedList<'a> {
+    bytes: &'a [u8],
+    /// Start offsets of the elements, followed by the end offset of the last one.
+    offsets: Vec<usize>,
+}
+
+impl<'a> PagedList<'a> {
+    /// Scans the list encoded in `bytes`.
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        let mut r = Cursor::new(bytes);
+        let major = read_major(&mut r)?;
+        if major.kind() != MajorKind::Array {
+            return Err(UnexpectedCode::new::<Vec<Ipld>>(major.into()).into());
+        }
+        let len = read_uint(&mut r, major)?;
+        let len = usize::try_from(len).map_err(|_| LengthOutOfRange::new::<usize>())?;
+        // Every element is at least one byte.
+        if len > bytes.len() {
+            return Err(UnexpectedEof.into());
+        }
+        let mut offsets = Vec::with_capacity(len + 1);
+        offsets.push(r.position() as usize);
+        for _ in 0..len {
+            DagCbor.skip(&mut r)?;
+            // Skipping seeks, which happily moves past the end.
+            if r.position() > bytes.len() as u64 {
+                return Err(UnexpectedEof.into());
+            }
+            offsets.push(r.position() as usize);
+        }
+        Ok(Self { bytes, offsets })
+    }
+
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Returns true if the list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the byte offsets of the elements.
+    pub fn offsets(&self) -> &[usize] {
+        &self.offsets[..self.len()]
+    }
+
+    /// Returns the encoded element at `index`.
+    pub fn raw(&self, index: usize) -> Option<&'a [u8]> {
+        if index >= self.len() {
+            return None;
+        }
+        Some(&self.bytes[self.offsets[index]..self.offsets[index + 1]])
+    }
+
+    /// Decodes the element at `index`.
+    pub fn get<T: Decode<DagCbor>>(&self, index: usize) -> Result<Option<T>> {
+        self.raw(index)
+            .map(|bytes| T::decode(DagCbor, &mut Cursor::new(bytes)))
+            .transpose()
+    }
+
+    /// Decodes the elements in `range`. The range is clamped to the length of the list, so
+    /// requesting a page past the end returns fewer or no elements.
+    pub fn page<T: Decode<DagCbor>>(&self, range: Range<usize>) -> Result<Vec<T>> {
+        let end = range.end.min(self.len());
+        let start = range.start.min(end);
+        let mut r = Cursor::new(&self.bytes[..self.offsets[end]]);
+        r.set_position(self.offsets[start] as u64);
+        (start..end).map(|_| T::decode(DagCbor, &mut r)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libipld_core::codec::Codec;
+    use libipld_macro::ipld;
+
+    #[test]
+    fn test_paged_list() {
+        let list: Vec<Ipld> = (0..100)
+            .map(|i| ipld!({ "i": i, "s": "x".repeat(i as usize) }))
+            .collect();
+        let bytes = DagCbor.encode(&list).unwrap();
+        let paged = PagedList::new(&bytes).unwrap();
+        assert_eq!(paged.len(), 100);
+        assert_eq!(paged.offsets()[0], 2);
+        assert_eq!(paged.get::<Ipld>(42).unwrap(), Some(list[42].clone()));
+        assert_eq!(paged.get::<Ipld>(100).unwrap(), None);
+        assert_eq!(paged.page::<Ipld>(10..20).unwrap(), &list[10..20]);
+        assert_eq!(paged.page::<Ipld>(95..120).unwrap(), &list[95..]);
+        assert!(paged.page::<Ipld>(120..130).unwrap().is_empty());
+        assert_eq!(
+            DagCbor.decode::<Ipld>(paged.raw(7).unwrap()).unwrap(),
+            list[7]
+        );
+    }
+
+    #[test]
+    fn test_paged_list_invalid() {
+        assert!(PagedList::new(&DagCbor.encode(&ipld!({})).unwrap()).is_err());
+        let mut bytes = DagCbor.encode(&ipld!([1, "abc"])).unwrap();
+        bytes.pop();
+        assert!(PagedList::new(&bytes).is_err());
+    }
+}
@@ -0,0 +1,301 @@
+//! Plain CBOR support.
+//!
+//! [`CborCodec`] reads CBOR produced by systems that don't follow the dag-cbor rules: indefinite
+//! lengths, non-minimal integers, half precision floats, `undefined`, integer map keys and tags
+//! other than 42. The [`Ipld`] data model has no tags, so tags other than 42 can't be preserved.
+//! They are rejected with an [`UnknownTag`] error, unless stripping them is enabled with
+//! [`CborCodec::with_stripped_tags`], in which case the tagged item is decoded as if it wasn't
+//! tagged and the tag is lost. Integer map keys are converted to strings, a map with both an integer key and the string it converts to, e.g. `1` and `"1"`, is
+//! rejected like any other duplicate key. Decoded data can be stored as dag-cbor by encoding it with
+//! the `DagCborCodec`.
+use crate::cbor::MajorKind;
+use crate::decode::{
+    read_bytes, read_f32, read_f64, read_link, read_u16, read_u32, read_u64, read_u8,
+};
+use crate::error::{DuplicateKey, LengthOutOfRange, UnexpectedCode, UnknownTag};
+use crate::{CborCodec, DagCborCodec};
+use libipld_core::cid::Cid;
+use libipld_core::codec::{Decode, Encode, References};
+use libipld_core::error::Result;
+use libipld_core::ipld::Ipld;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, Write};
+
+/// The "break" stop code terminating indefinite length items.
+const BREAK: u8 = 0xff;
+
+/// Reads the argument of an item. Returns `None` for indefinite lengths.
+fn read_arg<R: Read>(r: &mut R, byte: u8) -> Result<Option<u64>> {
+    let arg = match byte & 0x1f {
+        info @ 0..=23 => info as u64,
+        24 => read_u8(r)? as u64,
+        25 => read_u16(r)? as u64,
+        26 => read_u32(r)? as u64,
+        27 => read_u64(r)?,
+        31 => return Ok(None),
+        _ => return Err(UnexpectedCode::new::<Ipld>(byte).into()),
+    };
+    Ok(Some(arg))
+}
+
+/// Reads a definite length argument, rejecting indefinite lengths.
+fn read_len<R: Read>(r: &mut R, byte: u8) -> Result<u64> {
+    read_arg(r, byte)?.ok_or_else(|| UnexpectedCode::new::<Ipld>(byte).into())
+}
+
+/// Reads a byte or text string, concatenating the chunks of indefinite length strings.
+fn read_chunks<R: Read>(r: &mut R, byte: u8) -> Result<Vec<u8>> {
+    match read_arg(r, byte)? {
+        Some(len) => read_bytes(r, len),
+        None => {
+            let mut buf = Vec::new();
+            loop {
+                let chunk = read_u8(r)?;
+                if chunk == BREAK {
+                    return Ok(buf);
+                }
+                // Chunks must be definite length strings of the same major type.
+                if chunk >> 5 != byte >> 5 {
+                    return Err(UnexpectedCode::new::<Ipld>(chunk).into());
+                }
+                let len = read_len(r, chunk)?;
+                buf.extend(read_bytes(r, len)?);
+            }
+        }
+    }
+}
+
+/// Converts a half precision float, see RFC 8949 Appendix D.
+fn f16_to_f64(half: u16) -> f64 {
+    let exp = (half >> 10) & 0x1f;
+    let mant = (half & 0x3ff) as f64;
+    let value = match exp {
+        0 => mant * 2f64.powi(-24),
+        31 if mant == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        exp => (mant + 1024.0) * 2f64.powi(exp as i32 - 25),
+    };
+    if half & 0x8000 != 0 {
+        -value
+    } else {
+        value
+    }
+}
+
+/// Reads a map key. Text keys are used as is, integer keys are converted to strings.
+fn read_key<R: Read + Seek>(c: CborCodec, r: &mut R, byte: u8) -> Result<String> {
+    match read_item(c, r, byte)? {
+        Ipld::String(key) => Ok(key),
+        Ipld::Integer(key) => Ok(key.to_string()),
+        _ => Err(UnexpectedCode::new::<String>(byte).into()),
+    }
+}
+
+/// Reads the item starting with `byte`.
+fn read_item<R: Read + Seek>(c: CborCodec, r: &mut R, byte: u8) -> Result<Ipld> {
+    let ipld = match byte >> 5 {
+        0 => Ipld::Integer(read_len(r, byte)? as i128),
+        1 => Ipld::Integer(-1 - read_len(r, byte)? as i128),
+        2 => Ipld::Bytes(read_chunks(r, byte)?),
+        3 => Ipld::String(String::from_utf8(read_chunks(r, byte)?)?),
+        4 => {
+            let mut list = Vec::new();
+            match read_arg(r, byte)? {
+                Some(len) => {
+                    let len = usize::try_from(len).map_err(|_| LengthOutOfRange::new::<usize>())?;
+                    list.reserve(len.min(1024));
+                    for _ in 0..len {
+                        let byte = read_u8(r)?;
+                        list.push(read_item(c, r, byte)?);
+                    }
+                }
+                None => loop {
+                    match read_u8(r)? {
+                        BREAK => break,
+                        byte => list.push(read_item(c, r, byte)?),
+                    }
+                },
+            }
+            Ipld::List(list)
+        }
+        5 => {
+            let mut map = BTreeMap::new();
+            let len = read_arg(r, byte)?;
+            let mut read = 0;
+            loop {
+                if Some(read) == len {
+                    break;
+                }
+                let byte = read_u8(r)?;
+                if byte == BREAK && len.is_none() {
+                    break;
+                }
+                let key = read_key(c, r, byte)?;
+                let byte = read_u8(r)?;
+                if map.insert(key, read_item(c, r, byte)?).is_some() {
+                    return Err(DuplicateKey.into());
+                }
+                read += 1;
+            }
+            Ipld::Map(map)
+        }
+        6 => match read_len(r, byte)? {
+            42 => Ipld::Link(read_link(r)?),
+            _ if c.strip_tags => {
+                let byte = read_u8(r)?;
+                read_item(c, r, byte)?
+            }
+            tag => return Err(UnknownTag(tag).into()),
+        },
+        _ => match byte & 0x1f {
+            20 => Ipld::Bool(false),
+            21 => Ipld::Bool(true),
+            // Null and undefined.
+            22 | 23 => Ipld::Null,
+            25 => Ipld::Float(f16_to_f64(read_u16(r)?)),
+            26 => Ipld::Float(read_f32(r)? as f64),
+            27 => Ipld::Float(read_f64(r)?),
+            _ => return Err(UnexpectedCode::new::<Ipld>(byte).into()),
+        },
+    };
+    Ok(ipld)
+}
+
+impl Decode<CborCodec> for Ipld {
+    fn decode<R: Read + Seek>(c: CborCodec, r: &mut R) -> Result<Self> {
+        let byte = read_u8(r)?;
+        read_item(c, r, byte)
+    }
+}
+
+impl References<CborCodec> for Ipld {
+    fn references<R: Read + Seek, E: Extend<Cid>>(
+        c: CborCodec,
+        r: &mut R,
+        set: &mut E,
+    ) -> Result<()> {
+        Ipld::decode(c, r)?.references(set);
+        Ok(())
+    }
+}
+
+impl Encode<CborCodec> for Ipld {
+    fn encode<W: Write>(&self, c: CborCodec, w: &mut W) -> Result<()> {
+        if !c.indefinite_lengths {
+            return self.encode(DagCborCodec, w);
+        }
+        match self {
+            Self::List(list) => {
+                w.write_all(&[(MajorKind::Array as u8) << 5 | 31])?;
+                for item in list {
+                    item.encode(c, w)?;
+                }
+                w.write_all(&[BREAK])?;
+            }
+            Self::Map(map) => {
+                w.write_all(&[(MajorKind::Map as u8) << 5 | 31])?;
+                // Keep the dag-cbor key order, so the output only differs in the lengths.
+                let mut cbor_order = Vec::from_iter(map);
+                cbor_order.sort_unstable_by(|&(key_a, _), &(key_b, _)| {
+                    match key_a.len().cmp(&key_b.len()) {
+                        Ordering::Equal => key_a.cmp(key_b),
+                        ordering => ordering,
+                    }
+                });
+                for (key, value) in cbor_order {
+                    key.encode(DagCborCodec, w)?;
+                    value.encode(c, w)?;
+                }
+                w.write_all(&[BREAK])?;
+            }
+            ipld => ipld.encode(DagCborCodec, w)?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libipld_core::codec::Codec;
+    use libipld_core::multihash::{Code, MultihashDigest};
+    use libipld_macro::ipld;
+
+    #[test]
+    fn test_indefinite_lengths() {
+        // {_ "a": [_ 1, 2], "b": (_ h'01', h'02'), "c": (_ "x", "y")}
+        let bytes = hex::decode("bf61619f0102ff61625f41014102ff61637f61786179ffff").unwrap();
+        let ipld: Ipld = CborCodec::default().decode(&bytes).unwrap();
+        assert_eq!(ipld, ipld!({ "a": [1, 2], "b": vec![1u8, 2], "c": "xy" }));
+        // Not valid dag-cbor.
+        assert!(DagCborCodec.decode::<Ipld>(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_tolerant_values() {
+        // [non-minimal 1, f16 1.5, undefined, 1(1600000000), {1: "one"}]
+        let bytes = hex::decode("851801f93e00f7c11a5f5e1000a101636f6e65").unwrap();
+        let ipld: Ipld = CborCodec::default()
+            .with_stripped_tags(true)
+            .decode(&bytes)
+            .unwrap();
+        assert_eq!(ipld, ipld!([1, 1.5, null, 1600000000, { "1": "one" }]));
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        // {1: "a", "1": "b"}
+        let bytes = hex::decode("a201616161316162").unwrap();
+        assert!(CborCodec::default()
+            .decode::<Ipld>(&bytes)
+            .unwrap_err()
+            .downcast_ref::<DuplicateKey>()
+            .is_some());
+    }
+
+    #[test]
+    fn test_stripped_tags() {
+        // [1("a"), 24(h'01')]
+        let bytes = hex::decode("82c16161d8184101").unwrap();
+        let err = CborCodec::default().decode::<Ipld>(&bytes).unwrap_err();
+        assert_eq!(err.downcast_ref::<UnknownTag>().unwrap().0, 1);
+
+        let ipld: Ipld = CborCodec::default()
+            .with_stripped_tags(true)
+            .decode(&bytes)
+            .unwrap();
+        assert_eq!(ipld, ipld!(["a", vec![1u8]]));
+    }
+
+    #[test]
+    fn test_links() {
+        let cid = Cid::new_v1(0x71, Code::Blake3_256.digest(&b"cid"[..]));
+        let ipld = ipld!({ "link": cid, "list": [cid] });
+        let bytes = DagCborCodec.encode(&ipld).unwrap();
+        let decoded: Ipld = CborCodec::default().decode(&bytes).unwrap();
+        assert_eq!(decoded, ipld);
+        let mut set = Vec::new();
+        CborCodec::default()
+            .references::<Ipld, _>(&bytes, &mut set)
+            .unwrap();
+        assert_eq!(set, vec![cid, cid]);
+    }
+
+    #[test]
+    fn test_encode() {
+        let ipld = ipld!({ "bb": [1, "a"], "a": {} });
+        let definite = CborCodec::default().encode(&ipld).unwrap();
+        assert_eq!(definite, DagCborCodec.encode(&ipld).unwrap());
+
+        let indefinite = CborCodec::default()
+            .with_indefinite_lengths(true)
+            .encode(&ipld)
+            .unwrap();
+        assert_eq!(hex::encode(&indefinite), "bf6161bfff6262629f016161ffff");
+        assert_eq!(
+            CborCodec::default().decode::<Ipld>(&indefinite).unwrap(),
+            ipld
+        );
+    }
+}
@@ -0,0 +1,595 @@
+//! Serde support.
+//!
+//! [`to_writer`] and [`to_vec`] serialize any `T: Serialize` straight to dag-cbor, without
+//! building an intermediate [`Ipld`]. Values map to the data model the same way as with
+//! [`libipld_core::serde::to_ipld`], so the output is identical to encoding the converted `Ipld`:
+//! map keys are sorted canonically and [`Cid`]s are written with tag 42. Decoding goes through
+//! `Ipld` with [`libipld_core::serde::from_ipld`].
+//!
+//! [`Serde`] wraps a value to use it with the [`DagCborCodec`] directly.
+use crate::cbor::MajorKind;
+use crate::encode::{write_null, write_u64};
+use crate::DagCborCodec;
+use core::convert::TryFrom;
+use libipld_core::cid::serde::CID_SERDE_PRIVATE_IDENTIFIER;
+use libipld_core::cid::Cid;
+use libipld_core::codec::{Codec, Decode, Encode};
+use libipld_core::error::{Result, SerdeError};
+use libipld_core::ipld::Ipld;
+use libipld_core::serde::{from_ipld, to_ipld};
+use serde::de::DeserializeOwned;
+use serde::ser::{self, Error as _, Serialize};
+use std::fmt::Display;
+use std::io::{Read, Seek, Write};
+
+fn error<E: Display>(err: E) -> SerdeError {
+    SerdeError::custom(err)
+}
+
+/// Serializes a value as dag-cbor to the writer.
+pub fn to_writer<W: Write, T: Serialize + ?Sized>(
+    writer: W,
+    value: &T,
+) -> core::result::Result<(), SerdeError> {
+    value.serialize(&mut Serializer::new(writer))
+}
+
+/// Serializes a value as dag-cbor.
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> core::result::Result<Vec<u8>, SerdeError> {
+    let mut bytes = Vec::new();
+    to_writer(&mut bytes, value)?;
+    Ok(bytes)
+}
+
+/// Deserializes a value from dag-cbor.
+pub fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    let ipld: Ipld = DagCborCodec.decode(bytes)?;
+    Ok(from_ipld(ipld)?)
+}
+
+/// Encodes and decodes the wrapped value with serde.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Serde<T>(pub T);
+
+impl<T: Serialize> Encode<DagCborCodec> for Serde<T> {
+    fn encode<W: Write>(&self, _: DagCborCodec, w: &mut W) -> Result<()> {
+        Ok(to_writer(w, &self.0)?)
+    }
+}
+
+impl<T: DeserializeOwned> Decode<DagCborCodec> for Serde<T> {
+    fn decode<R: Read + Seek>(c: DagCborCodec, r: &mut R) -> Result<Self> {
+        let ipld = Ipld::decode(c, r)?;
+        Ok(Self(from_ipld(ipld)?))
+    }
+}
+
+/// A serde serializer writing dag-cbor.
+pub struct Serializer<W> {
+    writer: W,
+}
+
+impl<W: Write> Serializer<W> {
+    /// Creates a serializer writing to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Returns the writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn encode<T: Encode<DagCborCodec> + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> core::result::Result<(), SerdeError> {
+        value.encode(DagCborCodec, &mut self.writer).map_err(error)
+    }
+
+    fn header(&mut self, major: MajorKind, len: usize) -> core::result::Result<(), SerdeError> {
+        write_u64(&mut self.writer, major, len as u64).map_err(error)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> core::result::Result<(), SerdeError> {
+        self.writer.write_all(bytes).map_err(error)
+    }
+}
+
+impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    type SerializeSeq = SerializeSeq<'a, W>;
+    type SerializeTuple = SerializeSeq<'a, W>;
+    type SerializeTupleStruct = SerializeSeq<'a, W>;
+    type SerializeTupleVariant = SerializeSeq<'a, W>;
+    type SerializeMap = SerializeMap<'a, W>;
+    type SerializeStruct = SerializeMap<'a, W>;
+    type SerializeStructVariant = SerializeMap<'a, W>;
+
+    fn serialize_bool(self, value: bool) -> core::result::Result<(), SerdeError> {
+        self.encode(&value)
+    }
+
+    fn serialize_i8(self, value: i8) -> core::result::Result<(), SerdeError> {
+        self.encode(&value)
+    }
+
+    fn serialize_i16(self, value: i16) -> core::result::Result<(), SerdeError> {
+        self.encode(&value)
+    }
+
+    fn serialize_i32(self, value: i32) -> core::result::Result<(), SerdeError> {
+        self.encode(&value)
+    }
+
+    fn serialize_i64(self, value: i64) -> core::result::Result<(), SerdeError> {
+        self.encode(&value)
+    }
+
+    fn serialize_i128(self, value: i128) -> core::result::Result<(), SerdeError> {
+        self.encode(&value)
+    }
+
+    fn serialize_u8(self, value: u8) -> core::result::Result<(), SerdeError> {
+        self.encode(&value)
+    }
+
+    fn serialize_u16(self, value: u16) -> core::result::Result<(), SerdeError> {
+        self.encode(&value)
+    }
+
+    fn serialize_u32(self, value: u32) -> core::result::Result<(), SerdeError> {
+        self.encode(&value)
+    }
+
+    fn serialize_u64(self, value: u64) -> core::result::Result<(), SerdeError> {
+        self.encode(&value)
+    }
+
+    fn serialize_u128(self, value: u128) -> core::result::Result<(), SerdeError> {
+        self.encode(&i128::try_from(value).map_err(error)?)
+    }
+
+    fn serialize_f32(self, value: f32) -> core::result::Result<(), SerdeError> {
+        self.serialize_f64(f64::from(value))
+    }
+
+    fn serialize_f64(self, value: f64) -> core::result::Result<(), SerdeError> {
+        self.encode(&value)
+    }
+
+    fn serialize_char(self, value: char) -> core::result::Result<(), SerdeError> {
+        self.encode(value.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, value: &str) -> core::result::Result<(), SerdeError> {
+        self.encode(value)
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> core::result::Result<(), SerdeError> {
+        self.encode(value)
+    }
+
+    fn serialize_none(self) -> core::result::Result<(), SerdeError> {
+        write_null(&mut self.writer).map_err(error)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(
+        self,
+        value: &T,
+    ) -> core::result::Result<(), SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> core::result::Result<(), SerdeError> {
+        Err(SerdeError::custom("Unit is not supported"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> core::result::Result<(), SerdeError> {
+        Err(SerdeError::custom("Unit structs are not supported"))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> core::result::Result<(), SerdeError> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> core::result::Result<(), SerdeError> {
+        if name == CID_SERDE_PRIVATE_IDENTIFIER {
+            if let Ipld::Bytes(bytes) = to_ipld(value)? {
+                let cid = Cid::try_from(bytes)
+                    .map_err(|err| SerdeError::custom(format!("Invalid CID: {}", err)))?;
+                return self.encode(&cid);
+            }
+        }
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> core::result::Result<(), SerdeError> {
+        self.header(MajorKind::Map, 1)?;
+        self.encode(variant)?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(
+        self,
+        len: Option<usize>,
+    ) -> core::result::Result<SerializeSeq<'a, W>, SerdeError> {
+        Ok(match len {
+            Some(len) => {
+                self.header(MajorKind::Array, len)?;
+                SerializeSeq::Known(self)
+            }
+            None => SerializeSeq::Unknown {
+                ser: self,
+                items: Serializer::new(Vec::new()),
+                len: 0,
+            },
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> core::result::Result<SerializeSeq<'a, W>, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> core::result::Result<SerializeSeq<'a, W>, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> core::result::Result<SerializeSeq<'a, W>, SerdeError> {
+        self.header(MajorKind::Map, 1)?;
+        self.encode(variant)?;
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_map(
+        self,
+        _len: Option<usize>,
+    ) -> core::result::Result<SerializeMap<'a, W>, SerdeError> {
+        Ok(SerializeMap {
+            ser: self,
+            entries: Vec::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> core::result::Result<SerializeMap<'a, W>, SerdeError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> core::result::Result<SerializeMap<'a, W>, SerdeError> {
+        self.header(MajorKind::Map, 1)?;
+        self.encode(variant)?;
+        self.serialize_map(Some(len))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Serializes lists. Items of lists with an unknown length are buffered until the length is
+/// known.
+pub enum SerializeSeq<'a, W> {
+    #[doc(hidden)]
+    Known(&'a mut Serializer<W>),
+    #[doc(hidden)]
+    Unknown {
+        ser: &'a mut Serializer<W>,
+        items: Serializer<Vec<u8>>,
+        len: usize,
+    },
+}
+
+impl<'a, W: Write> SerializeSeq<'a, W> {
+    fn element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> core::result::Result<(), SerdeError> {
+        match self {
+            Self::Known(ser) => value.serialize(&mut **ser),
+            Self::Unknown { items, len, .. } => {
+                *len += 1;
+                value.serialize(items)
+            }
+        }
+    }
+
+    fn finish(self) -> core::result::Result<(), SerdeError> {
+        if let Self::Unknown { ser, items, len } = self {
+            ser.header(MajorKind::Array, len)?;
+            ser.write(&items.into_inner())?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, W: Write> ser::SerializeSeq for SerializeSeq<'a, W> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> core::result::Result<(), SerdeError> {
+        self.element(value)
+    }
+
+    fn end(self) -> core::result::Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write> ser::SerializeTuple for SerializeSeq<'a, W> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> core::result::Result<(), SerdeError> {
+        self.element(value)
+    }
+
+    fn end(self) -> core::result::Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write> ser::SerializeTupleStruct for SerializeSeq<'a, W> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> core::result::Result<(), SerdeError> {
+        self.element(value)
+    }
+
+    fn end(self) -> core::result::Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write> ser::SerializeTupleVariant for SerializeSeq<'a, W> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> core::result::Result<(), SerdeError> {
+        self.element(value)
+    }
+
+    fn end(self) -> core::result::Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+/// Serializes maps. The encoded entries are buffered to write them in canonical key order.
+pub struct SerializeMap<'a, W> {
+    ser: &'a mut Serializer<W>,
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    key: Option<Vec<u8>>,
+}
+
+impl<'a, W: Write> SerializeMap<'a, W> {
+    fn entry<T: Serialize + ?Sized>(
+        &mut self,
+        key: Vec<u8>,
+        value: &T,
+    ) -> core::result::Result<(), SerdeError> {
+        let mut ser = Serializer::new(Vec::new());
+        value.serialize(&mut ser)?;
+        self.entries.push((key, ser.into_inner()));
+        Ok(())
+    }
+
+    fn finish(mut self) -> core::result::Result<(), SerdeError> {
+        // Keys are encoded strings, so ordering them by length first and then bytewise is the
+        // dag-cbor key order.
+        self.entries.sort_unstable_by(|(key_a, _), (key_b, _)| {
+            key_a.len().cmp(&key_b.len()).then_with(|| key_a.cmp(key_b))
+        });
+        if self.entries.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return Err(SerdeError::custom("Duplicate map key"));
+        }
+        self.ser.header(MajorKind::Map, self.entries.len())?;
+        for (key, value) in &self.entries {
+            self.ser.write(key)?;
+            self.ser.write(value)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, W: Write> ser::SerializeMap for SerializeMap<'a, W> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(
+        &mut self,
+        key: &T,
+    ) -> core::result::Result<(), SerdeError> {
+        let mut ser = Serializer::new(Vec::new());
+        key.serialize(&mut ser)?;
+        let key = ser.into_inner();
+        match key.first() {
+            Some(byte) if byte >> 5 == MajorKind::TextString as u8 => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(SerdeError::custom("Map keys must be strings")),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> core::result::Result<(), SerdeError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| SerdeError::custom("Map value without a key"))?;
+        self.entry(key, value)
+    }
+
+    fn end(self) -> core::result::Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write> ser::SerializeStruct for SerializeMap<'a, W> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> core::result::Result<(), SerdeError> {
+        self.entry(DagCborCodec.encode(key).map_err(error)?, value)
+    }
+
+    fn end(self) -> core::result::Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write> ser::SerializeStructVariant for SerializeMap<'a, W> {
+    type Ok = ();
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> core::result::Result<(), SerdeError> {
+        self.entry(DagCborCodec.encode(key).map_err(error)?, value)
+    }
+
+    fn end(self) -> core::result::Result<(), SerdeError> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libipld_core::multihash::{Code, MultihashDigest};
+    use libipld_macro::ipld;
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    enum Kind {
+        File,
+        Directory { entries: u32 },
+        Link(Cid),
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Node {
+        name: String,
+        size: u64,
+        kind: Kind,
+        tags: HashMap<String, Option<f64>>,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+        parts: Vec<(u8, char)>,
+    }
+
+    #[test]
+    fn test_matches_ipld() {
+        let cid = Cid::new_v1(0x71, Code::Blake3_256.digest(b"node"));
+        let node = Node {
+            name: "node".into(),
+            size: u64::MAX,
+            kind: Kind::Link(cid),
+            tags: [("b".into(), Some(1.5)), ("aa".into(), None)].into(),
+            data: vec![1, 2, 3],
+            parts: vec![(1, 'a')],
+        };
+        let bytes = to_vec(&node).unwrap();
+        assert_eq!(
+            bytes,
+            DagCborCodec.encode(&to_ipld(&node).unwrap()).unwrap()
+        );
+        assert_eq!(from_slice::<Node>(&bytes).unwrap(), node);
+
+        let kind = Kind::Directory { entries: 2 };
+        assert_eq!(
+            DagCborCodec.encode(&Serde(&kind)).unwrap(),
+            DagCborCodec
+                .encode(&ipld!({"Directory": {"entries": 2}}))
+                .unwrap()
+        );
+        let Serde(decoded): Serde<Kind> = DagCborCodec
+            .decode(&DagCborCodec.encode(&Serde(&kind)).unwrap())
+            .unwrap();
+        assert_eq!(decoded, kind);
+    }
+
+    #[test]
+    fn test_unknown_length() {
+        struct Items;
+
+        impl Serialize for Items {
+            fn serialize<S: ser::Serializer>(
+                &self,
+                serializer: S,
+            ) -> core::result::Result<S::Ok, S::Error> {
+                serializer.collect_seq((0..3).filter(|i| i % 2 == 0))
+            }
+        }
+
+        assert_eq!(
+            to_vec(&Items).unwrap(),
+            DagCborCodec.encode(&ipld!([0, 2])).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        let map: HashMap<u8, u8> = [(1, 1)].into();
+        assert!(to_vec(&map).is_err());
+    }
+}
@@ -0,0 +1,370 @@
+//! Value sharing.
+//!
+//! Large values often repeat the same lists and maps. [`encode_shared`] writes every repeated
+//! subtree once, tagged as shareable (tag 28), and replaces later occurrences with a shared
+//! reference (tag 29) holding the index of the shareable value, following the CBOR value sharing
+//! tags. [`decode_shared`] reinflates the references, up to a size limit.
+//!
+//! The output is not dag-cbor, as blocks may only contain tag 42, and it isn't canonical. It's
+//! meant for transferring large values. Use the `DagCborCodec` to store the decoded value.
+use crate::cbor::{MajorKind, F32, F64, FALSE, NULL, TRUE};
+use crate::decode::{read_bytes, read_f32, read_f64, read_link, read_major, read_str, read_uint};
+use crate::encode::{write_tag, write_u64};
+use crate::error::{
+    DuplicateKey, InvalidSharedRef, SharedLimitExceeded, UnexpectedCode, UnknownTag,
+};
+use crate::DagCborCodec;
+use libipld_core::codec::{Codec, Encode};
+use libipld_core::error::Result;
+use libipld_core::ipld::Ipld;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Seek, Write};
+
+/// Tag marking a value that is referenced later.
+const SHAREABLE: u64 = 28;
+/// Tag of a reference to a shareable value.
+const SHARED_REF: u64 = 29;
+/// Lists and maps with a shorter dag-cbor encoding aren't worth sharing.
+const MIN_SHARED_LEN: usize = 8;
+
+/// Structure of a value, with the children replaced by the ids of their structure. Equal values
+/// have the same structure, so every distinct value gets one id.
+#[derive(Eq, Hash, PartialEq)]
+enum Shape {
+    Scalar(Vec<u8>),
+    List(Vec<usize>),
+    Map(Vec<(String, usize)>),
+}
+
+/// Id of a value and its children, in the same order as the value.
+struct Node {
+    id: usize,
+    /// Lists and maps with a long enough encoding.
+    shareable: bool,
+    children: Vec<Node>,
+}
+
+/// Length of the header of an item of length `len`.
+fn header_len(len: usize) -> usize {
+    match len {
+        0..=23 => 1,
+        24..=0xff => 2,
+        0x100..=0xffff => 3,
+        0x1_0000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+/// Assigns ids to the value and its children bottom-up, so every value is visited and encoded
+/// once. Returns the node and the length of the dag-cbor encoding.
+fn intern(ipld: &Ipld, shapes: &mut HashMap<Shape, usize>) -> Result<(Node, usize)> {
+    let mut children = Vec::new();
+    let (shape, len) = match ipld {
+        Ipld::List(list) => {
+            let mut ids = Vec::with_capacity(list.len());
+            let mut len = header_len(list.len());
+            for ipld in list {
+                let (node, node_len) = intern(ipld, shapes)?;
+                len += node_len;
+                ids.push(node.id);
+                children.push(node);
+            }
+            (Shape::List(ids), len)
+        }
+        Ipld::Map(map) => {
+            let mut ids = Vec::with_capacity(map.len());
+            let mut len = header_len(map.len());
+            for (key, ipld) in map {
+                let (node, node_len) = intern(ipld, shapes)?;
+                len += header_len(key.len()) + key.len() + node_len;
+                ids.push((key.clone(), node.id));
+                children.push(node);
+            }
+            (Shape::Map(ids), len)
+        }
+        ipld => {
+            let bytes = DagCborCodec.encode(ipld)?;
+            let len = bytes.len();
+            (Shape::Scalar(bytes), len)
+        }
+    };
+    let shareable = matches!(ipld, Ipld::List(_) | Ipld::Map(_)) && len >= MIN_SHARED_LEN;
+    let next = shapes.len();
+    let id = *shapes.entry(shape).or_insert(next);
+    let node = Node {
+        id,
+        shareable,
+        children,
+    };
+    Ok((node, len))
+}
+
+/// Counts the occurrences of every shareable subtree. Repeated subtrees aren't descended into, as
+/// they are written as a reference.
+fn count(node: &Node, counts: &mut [usize]) {
+    if node.shareable {
+        counts[node.id] += 1;
+        if counts[node.id] > 1 {
+            return;
+        }
+    }
+    for child in &node.children {
+        count(child, counts);
+    }
+}
+
+struct SharedWriter {
+    counts: Vec<usize>,
+    indices: HashMap<usize, u64>,
+}
+
+impl SharedWriter {
+    fn write<W: Write>(&mut self, ipld: &Ipld, node: &Node, w: &mut W) -> Result<()> {
+        if node.shareable && self.counts[node.id] > 1 {
+            if let Some(index) = self.indices.get(&node.id) {
+                write_tag(w, SHARED_REF)?;
+                return index.encode(DagCborCodec, w);
+            }
+            let index = self.indices.len() as u64;
+            self.indices.insert(node.id, index);
+            write_tag(w, SHAREABLE)?;
+        }
+        match ipld {
+            Ipld::List(list) => {
+                write_u64(w, MajorKind::Array, list.len() as u64)?;
+                for (ipld, node) in list.iter().zip(&node.children) {
+                    self.write(ipld, node, w)?;
+                }
+            }
+            Ipld::Map(map) => {
+                write_u64(w, MajorKind::Map, map.len() as u64)?;
+                // Same key order as the dag-cbor encoding.
+                let mut cbor_order = Vec::from_iter(map.iter().zip(&node.children));
+                cbor_order.sort_unstable_by(|&((key_a, _), _), &((key_b, _), _)| {
+                    match key_a.len().cmp(&key_b.len()) {
+                        Ordering::Equal => key_a.cmp(key_b),
+                        ordering => ordering,
+                    }
+                });
+                for ((key, ipld), node) in cbor_order {
+                    key.encode(DagCborCodec, w)?;
+                    self.write(ipld, node, w)?;
+                }
+            }
+            ipld => ipld.encode(DagCborCodec, w)?,
+        }
+        Ok(())
+    }
+}
+
+/// Encodes ipld, writing repeated lists and maps only once.
+pub fn encode_shared<W: Write>(ipld: &Ipld, w: &mut W) -> Result<()> {
+    let mut shapes = HashMap::new();
+    let (node, _) = intern(ipld, &mut shapes)?;
+    let mut counts = vec![0; shapes.len()];
+    count(&node, &mut counts);
+    SharedWriter {
+        counts,
+        indices: HashMap::new(),
+    }
+    .write(ipld, &node, w)
+}
+
+struct SharedReader {
+    /// Shareable values read so far with their size, `None` while still being read.
+    shared: Vec<Option<(Ipld, usize)>>,
+    /// Size left before reaching the limit.
+    budget: usize,
+    limit: usize,
+}
+
+impl SharedReader {
+    /// Charges `size` to the budget.
+    fn charge(&mut self, size: usize) -> Result<()> {
+        self.budget = self
+            .budget
+            .checked_sub(size)
+            .ok_or(SharedLimitExceeded(self.limit))?;
+        Ok(())
+    }
+
+    /// Reads an item, resolving shared references with the shareable values read so far.
+    fn read<R: Read + Seek>(&mut self, r: &mut R) -> Result<Ipld> {
+        let major = read_major(r)?;
+        if major.kind() != MajorKind::Tag {
+            self.charge(1)?;
+        }
+        let ipld = match major.kind() {
+            MajorKind::UnsignedInt => Ipld::Integer(read_uint(r, major)? as i128),
+            MajorKind::NegativeInt => Ipld::Integer(-1 - read_uint(r, major)? as i128),
+            MajorKind::ByteString => {
+                let len = read_uint(r, major)?;
+                let bytes = read_bytes(r, len)?;
+                self.charge(bytes.len())?;
+                Ipld::Bytes(bytes)
+            }
+            MajorKind::TextString => {
+                let len = read_uint(r, major)?;
+                let string = read_str(r, len)?;
+                self.charge(string.len())?;
+                Ipld::String(string)
+            }
+            MajorKind::Array => {
+                let len = read_uint(r, major)?;
+                let mut list = Vec::new();
+                for _ in 0..len {
+                    list.push(self.read(r)?);
+                }
+                Ipld::List(list)
+            }
+            MajorKind::Map => {
+                let len = read_uint(r, major)?;
+                let mut map = BTreeMap::new();
+                for _ in 0..len {
+                    let major = read_major(r)?;
+                    if major.kind() != MajorKind::TextString {
+                        return Err(UnexpectedCode::new::<String>(major.into()).into());
+                    }
+                    let len = read_uint(r, major)?;
+                    let key = read_str(r, len)?;
+                    self.charge(key.len())?;
+                    let value = self.read(r)?;
+                    if map.insert(key, value).is_some() {
+                        return Err(DuplicateKey.into());
+                    }
+                }
+                Ipld::Map(map)
+            }
+            MajorKind::Tag => match read_uint(r, major)? {
+                42 => {
+                    self.charge(1)?;
+                    Ipld::Link(read_link(r)?)
+                }
+                SHAREABLE => {
+                    // The index is assigned before reading the value, so nested shareable values
+                    // get higher indices.
+                    let index = self.shared.len();
+                    self.shared.push(None);
+                    let budget = self.budget;
+                    let ipld = self.read(r)?;
+                    self.shared[index] = Some((ipld.clone(), budget - self.budget));
+                    ipld
+                }
+                SHARED_REF => {
+                    let major = read_major(r)?;
+                    if major.kind() != MajorKind::UnsignedInt {
+                        return Err(UnexpectedCode::new::<u64>(major.into()).into());
+                    }
+                    let index = read_uint(r, major)?;
+                    let (ipld, size) = usize::try_from(index)
+                        .ok()
+                        .and_then(|index| self.shared.get(index))
+                        .and_then(Option::as_ref)
+                        .ok_or(InvalidSharedRef(index))?;
+                    let (ipld, size) = (ipld.clone(), *size);
+                    self.charge(size)?;
+                    ipld
+                }
+                tag => return Err(UnknownTag(tag).into()),
+            },
+            MajorKind::Other => match major {
+                FALSE => Ipld::Bool(false),
+                TRUE => Ipld::Bool(true),
+                NULL => Ipld::Null,
+                F32 => Ipld::Float(read_f32(r)? as f64),
+                F64 => Ipld::Float(read_f64(r)?),
+                m => return Err(UnexpectedCode::new::<Ipld>(m.into()).into()),
+            },
+        };
+        Ok(ipld)
+    }
+}
+
+/// Decodes ipld written by [`encode_shared`]. Data without shared values is read as well.
+///
+/// Shared references make it possible to encode values exponentially larger than the input, so
+/// the size of the decoded value is limited to `limit`. Every value counts one, strings, bytes and
+/// map keys additionally count their length. Values past the limit are a
+/// [`SharedLimitExceeded`] error.
+pub fn decode_shared<R: Read + Seek>(r: &mut R, limit: usize) -> Result<Ipld> {
+    SharedReader {
+        shared: Vec::new(),
+        budget: limit,
+        limit,
+    }
+    .read(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libipld_macro::ipld;
+    use std::io::Cursor;
+
+    fn roundtrip(ipld: &Ipld) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        encode_shared(ipld, &mut bytes).unwrap();
+        assert_eq!(
+            &decode_shared(&mut Cursor::new(&bytes), usize::MAX).unwrap(),
+            ipld
+        );
+        bytes
+    }
+
+    #[test]
+    fn test_shared() {
+        let sample = ipld!({"name": "sample", "values": [1, 2, 3, 4, 5]});
+        let ipld = Ipld::List(vec![sample.clone(); 100]);
+        let bytes = roundtrip(&ipld);
+        assert!(bytes.len() * 5 < DagCborCodec.encode(&ipld).unwrap().len());
+
+        // Nested shared values.
+        let ipld = ipld!({"a": [sample.clone(), sample.clone()], "b": [sample.clone(), sample]});
+        roundtrip(&ipld);
+    }
+
+    #[test]
+    fn test_unshared() {
+        let ipld = ipld!({"a": [1, 2], "b": "c", "d": [1, 2]});
+        let bytes = roundtrip(&ipld);
+        assert_eq!(bytes, DagCborCodec.encode(&ipld).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_ref() {
+        // tag 29, index 0
+        let bytes = [0xd8, 0x1d, 0x00];
+        assert!(decode_shared(&mut Cursor::new(&bytes), usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_limit() {
+        // A list of levels, each a shareable list holding two references to the previous level,
+        // so the decoded value doubles in size with every level.
+        let mut bytes = vec![0x98, 64, 0xd8, 0x1c, 0x80];
+        for index in 0..63u8 {
+            let mut shared_ref = vec![0xd8, 0x1d];
+            index.encode(DagCborCodec, &mut shared_ref).unwrap();
+            bytes.extend([0xd8, 0x1c, 0x82]);
+            bytes.extend(&shared_ref);
+            bytes.extend(&shared_ref);
+        }
+        let err = decode_shared(&mut Cursor::new(&bytes), 1 << 20).unwrap_err();
+        assert!(err.downcast_ref::<SharedLimitExceeded>().is_some());
+
+        let ipld = ipld!({"a": [1, 2, 3]});
+        let mut bytes = Vec::new();
+        encode_shared(&ipld, &mut bytes).unwrap();
+        assert!(decode_shared(&mut Cursor::new(&bytes), 5).is_err());
+        assert_eq!(decode_shared(&mut Cursor::new(&bytes), 6).unwrap(), ipld);
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        // {"a": 1, "a": 2}
+        let bytes = [0xa2, 0x61, 0x61, 0x01, 0x61, 0x61, 0x02];
+        let err = decode_shared(&mut Cursor::new(&bytes), usize::MAX).unwrap_err();
+        assert!(err.downcast_ref::<DuplicateKey>().is_some());
+    }
+}
@@ -1,7 +1,9 @@
 use core::convert::TryFrom;
 use libipld_core::cid::Cid;
+use libipld_core::error::{Error as CoreError, PartialIpld, Result as CoreResult};
 use libipld_core::ipld::Ipld;
 use libipld_core::multibase::Base;
+use libipld_core::token::Token;
 use serde::de::Error as SerdeError;
 use serde::{de, ser, Deserialize, Serialize};
 use serde_json::ser::Serializer;
@@ -19,16 +21,68 @@ pub fn encode<W: Write>(ipld: &Ipld, writer: &mut W) -> Result<(), Error> {
     Ok(())
 }
 
+pub fn encode_pretty<W: Write>(ipld: &Ipld, writer: &mut W) -> Result<(), Error> {
+    let mut ser = Serializer::pretty(writer);
+    serialize(ipld, &mut ser)?;
+    Ok(())
+}
+
 pub fn decode<R: Read>(r: &mut R) -> Result<Ipld, Error> {
     let mut de = serde_json::Deserializer::from_reader(r);
     deserialize(&mut de)
 }
 
+pub fn tokens<R: Read, F: FnMut(Token) -> CoreResult<()>>(r: &mut R, f: F) -> CoreResult<()> {
+    let mut emitter = Emitter { f, error: None };
+    let mut de = serde_json::Deserializer::from_reader(r);
+    let result = de::DeserializeSeed::deserialize(TokenVisitor(&mut emitter), &mut de);
+    match (emitter.error, result) {
+        (Some(err), _) => Err(err),
+        (None, result) => Ok(result?),
+    }
+}
+
+pub fn decode_lenient(bytes: &[u8]) -> Result<Ipld, PartialIpld> {
+    let mut r = bytes;
+    let mut builder = Builder::default();
+    let result = tokens(&mut r, |token| builder.push(token));
+    // serde_json reads byte by byte, so what's left of the slice is what wasn't read.
+    let offset = (bytes.len() - r.len()) as u64;
+    match (result, builder.finish()) {
+        (Ok(()), Some(ipld)) => Ok(ipld),
+        (Ok(()), None) => Err(PartialIpld {
+            ipld: None,
+            error: CoreError::msg("empty input"),
+            offset,
+        }),
+        (Err(error), ipld) => Err(PartialIpld {
+            ipld,
+            error,
+            offset,
+        }),
+    }
+}
+
+pub fn references<R: Read, E: Extend<Cid>>(r: &mut R, set: &mut E) -> Result<(), Error> {
+    let mut de = serde_json::Deserializer::from_reader(r);
+    de::DeserializeSeed::deserialize(
+        RefsVisitor {
+            set,
+            keep_str: false,
+        },
+        &mut de,
+    )?;
+    Ok(())
+}
+
 fn serialize<S: ser::Serializer>(ipld: &Ipld, ser: S) -> Result<S::Ok, S::Error> {
     match &ipld {
         Ipld::Null => ser.serialize_none(),
         Ipld::Bool(bool) => ser.serialize_bool(*bool),
         Ipld::Integer(i128) => ser.serialize_i128(*i128),
+        Ipld::Float(f64) if !f64.is_finite() => {
+            Err(ser::Error::custom("NaN and infinities can't be encoded"))
+        }
         Ipld::Float(f64) => ser.serialize_f64(*f64),
         Ipld::String(string) => ser.serialize_str(string),
         Ipld::Bytes(bytes) => {
@@ -41,6 +95,9 @@ fn serialize<S: ser::Serializer>(ipld: &Ipld, ser: S) -> Result<S::Ok, S::Error>
             let wrapped = list.iter().map(Wrapper);
             ser.collect_seq(wrapped)
         }
+        Ipld::Map(map) if is_ambiguous(map) => Err(ser::Error::custom(
+            "map would be decoded as a link or bytes",
+        )),
         Ipld::Map(map) => {
             let wrapped = map.iter().map(|(key, ipld)| (key, Wrapper(ipld)));
             ser.collect_map(wrapped)
@@ -54,6 +111,22 @@ fn serialize<S: ser::Serializer>(ipld: &Ipld, ser: S) -> Result<S::Ok, S::Error>
     }
 }
 
+/// Returns true if the map has the form of an encoded link or bytes, a single `"/"` key holding a
+/// string or a `{"bytes": "..."}` map. Such maps can't be encoded, as they would decode as a link
+/// or bytes.
+pub fn is_ambiguous(map: &BTreeMap<String, Ipld>) -> bool {
+    if map.len() != 1 {
+        return false;
+    }
+    match map.get(RESERVED_KEY) {
+        Some(Ipld::String(_)) => true,
+        Some(Ipld::Map(inner)) => {
+            inner.len() == 1 && matches!(inner.get(BYTES_KEY), Some(Ipld::String(_)))
+        }
+        _ => false,
+    }
+}
+
 fn deserialize<'de, D: de::Deserializer<'de>>(deserializer: D) -> Result<Ipld, D::Error> {
     // Sadly such a PhantomData hack is needed
     deserializer.deserialize_any(JsonVisitor)
@@ -174,26 +247,10 @@ impl<'de> de::Visitor<'de> for JsonVisitor {
             values.push((key, value));
         }
 
-        // JSON Object represents an IPLD Link if it is a slash, followed by a string
-        // (`{ "/": "...." }`) therefore we validate if that is the case here.
-        if let Some((key, WrapperOwned(Ipld::String(value)))) = values.first() {
-            if key == RESERVED_KEY && values.len() == 1 {
-                let cid = Cid::try_from(value.clone()).map_err(SerdeError::custom)?;
-                return Ok(Ipld::Link(cid));
-            }
-        }
-        // JSON Object represents IPLD bytes if it is a slash, followed by an object which contains
-        // only a single key called "bytes", where the value is a string.
-        if let Some((key, WrapperOwned(Ipld::Map(map)))) = values.first() {
-            // Object with a slash
-            if key == RESERVED_KEY && values.len() == 1 {
-                if let Some((bytes_key, Ipld::String(bytes_value))) = map.iter().next() {
-                    if bytes_key == BYTES_KEY && values.len() == 1 {
-                        let decoded_bytes = Base::Base64.decode(bytes_value).map_err(|_| {
-                            SerdeError::custom("bytes kind must be base-64 encoded")
-                        })?;
-                        return Ok(Ipld::Bytes(decoded_bytes));
-                    }
+        if let [(key, WrapperOwned(value))] = &values[..] {
+            if key == RESERVED_KEY {
+                if let Some(ipld) = decode_reserved(value)? {
+                    return Ok(ipld);
                 }
             }
         }
@@ -216,6 +273,317 @@ impl<'de> de::Visitor<'de> for JsonVisitor {
     }
 }
 
+/// Decodes the value of a `"/"` key that is the only key of its map. Returns `None` if it's
+/// neither a link nor bytes, so the map is a regular map.
+fn decode_reserved<E: de::Error>(value: &Ipld) -> Result<Option<Ipld>, E> {
+    match value {
+        // JSON Object represents an IPLD Link if it is a slash, followed by a string
+        // (`{ "/": "...." }`).
+        Ipld::String(cid) => {
+            let cid = Cid::try_from(cid.as_str()).map_err(SerdeError::custom)?;
+            Ok(Some(Ipld::Link(cid)))
+        }
+        // JSON Object represents IPLD bytes if it is a slash, followed by an object which
+        // contains only a single key called "bytes", where the value is a string.
+        Ipld::Map(map) if map.len() == 1 => match map.get(BYTES_KEY) {
+            Some(Ipld::String(bytes)) => {
+                let bytes = Base::Base64
+                    .decode(bytes)
+                    .map_err(|_| SerdeError::custom("bytes kind must be base-64 encoded"))?;
+                Ok(Some(Ipld::Bytes(bytes)))
+            }
+            _ => Ok(None),
+        },
+        _ => Ok(None),
+    }
+}
+
+// serde deserializer visitor that collects the links without building an `Ipld`. Scalars are
+// skipped, only the string value of a `"/"` key is kept, as it might be a link.
+struct RefsVisitor<'a, E> {
+    set: &'a mut E,
+    keep_str: bool,
+}
+
+impl<'de, 'a, E: Extend<Cid>> de::DeserializeSeed<'de> for RefsVisitor<'a, E> {
+    type Value = Option<String>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'a, E: Extend<Cid>> de::Visitor<'de> for RefsVisitor<'a, E> {
+    type Value = Option<String>;
+
+    fn expecting(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str("any valid JSON value")
+    }
+
+    fn visit_str<Er: de::Error>(self, value: &str) -> Result<Self::Value, Er> {
+        Ok(self.keep_str.then(|| value.to_string()))
+    }
+
+    fn visit_bytes<Er: de::Error>(self, _: &[u8]) -> Result<Self::Value, Er> {
+        Ok(None)
+    }
+
+    fn visit_u64<Er: de::Error>(self, _: u64) -> Result<Self::Value, Er> {
+        Ok(None)
+    }
+
+    fn visit_i64<Er: de::Error>(self, _: i64) -> Result<Self::Value, Er> {
+        Ok(None)
+    }
+
+    fn visit_i128<Er: de::Error>(self, _: i128) -> Result<Self::Value, Er> {
+        Ok(None)
+    }
+
+    fn visit_f64<Er: de::Error>(self, _: f64) -> Result<Self::Value, Er> {
+        Ok(None)
+    }
+
+    fn visit_bool<Er: de::Error>(self, _: bool) -> Result<Self::Value, Er> {
+        Ok(None)
+    }
+
+    fn visit_none<Er: de::Error>(self) -> Result<Self::Value, Er> {
+        Ok(None)
+    }
+
+    fn visit_unit<Er: de::Error>(self) -> Result<Self::Value, Er> {
+        Ok(None)
+    }
+
+    fn visit_seq<V>(self, mut visitor: V) -> Result<Self::Value, V::Error>
+    where
+        V: de::SeqAccess<'de>,
+    {
+        while visitor
+            .next_element_seed(RefsVisitor {
+                set: &mut *self.set,
+                keep_str: false,
+            })?
+            .is_some()
+        {}
+        Ok(None)
+    }
+
+    fn visit_map<V>(self, mut visitor: V) -> Result<Self::Value, V::Error>
+    where
+        V: de::MapAccess<'de>,
+    {
+        let mut len = 0;
+        let mut link = None;
+        while let Some(key) = visitor.next_key::<String>()? {
+            len += 1;
+            let value = visitor.next_value_seed(RefsVisitor {
+                set: &mut *self.set,
+                keep_str: key == RESERVED_KEY,
+            })?;
+            if key == RESERVED_KEY {
+                link = value;
+            }
+        }
+        // Same rule as in `JsonVisitor::visit_map`.
+        if let (1, Some(link)) = (len, link) {
+            let cid = Cid::try_from(link).map_err(SerdeError::custom)?;
+            self.set.extend(std::iter::once(cid));
+        }
+        Ok(None)
+    }
+}
+
+// Passes tokens to the callback. serde can't carry the error of the callback, so it is stashed
+// here and decoding is aborted with a placeholder error.
+struct Emitter<F> {
+    f: F,
+    error: Option<CoreError>,
+}
+
+impl<F: FnMut(Token) -> CoreResult<()>> Emitter<F> {
+    fn emit<E: de::Error>(&mut self, token: Token) -> Result<(), E> {
+        (self.f)(token).map_err(|err| {
+            self.error = Some(err);
+            E::custom("stopped by callback")
+        })
+    }
+}
+
+// serde deserializer visitor that emits tokens without building an `Ipld`. Only the value of a
+// leading `"/"` key is buffered, as it might be a link or bytes.
+struct TokenVisitor<'a, F>(&'a mut Emitter<F>);
+
+impl<'de, 'a, F: FnMut(Token) -> CoreResult<()>> de::DeserializeSeed<'de> for TokenVisitor<'a, F> {
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'a, F: FnMut(Token) -> CoreResult<()>> de::Visitor<'de> for TokenVisitor<'a, F> {
+    type Value = ();
+
+    fn expecting(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str("any valid JSON value")
+    }
+
+    fn visit_str<Er: de::Error>(self, value: &str) -> Result<Self::Value, Er> {
+        self.0.emit(Token::String(value.to_string()))
+    }
+
+    fn visit_string<Er: de::Error>(self, value: String) -> Result<Self::Value, Er> {
+        self.0.emit(Token::String(value))
+    }
+
+    fn visit_u64<Er: de::Error>(self, v: u64) -> Result<Self::Value, Er> {
+        self.0.emit(Token::Integer(v.into()))
+    }
+
+    fn visit_i64<Er: de::Error>(self, v: i64) -> Result<Self::Value, Er> {
+        self.0.emit(Token::Integer(v.into()))
+    }
+
+    fn visit_i128<Er: de::Error>(self, v: i128) -> Result<Self::Value, Er> {
+        self.0.emit(Token::Integer(v))
+    }
+
+    fn visit_f64<Er: de::Error>(self, v: f64) -> Result<Self::Value, Er> {
+        self.0.emit(Token::Float(v))
+    }
+
+    fn visit_bool<Er: de::Error>(self, v: bool) -> Result<Self::Value, Er> {
+        self.0.emit(Token::Bool(v))
+    }
+
+    fn visit_none<Er: de::Error>(self) -> Result<Self::Value, Er> {
+        self.visit_unit()
+    }
+
+    fn visit_unit<Er: de::Error>(self) -> Result<Self::Value, Er> {
+        self.0.emit(Token::Null)
+    }
+
+    fn visit_seq<V>(self, mut visitor: V) -> Result<Self::Value, V::Error>
+    where
+        V: de::SeqAccess<'de>,
+    {
+        self.0.emit(Token::ListStart)?;
+        while visitor
+            .next_element_seed(TokenVisitor(&mut *self.0))?
+            .is_some()
+        {}
+        self.0.emit(Token::ListEnd)
+    }
+
+    fn visit_map<V>(self, mut visitor: V) -> Result<Self::Value, V::Error>
+    where
+        V: de::MapAccess<'de>,
+    {
+        let mut key = visitor.next_key::<String>()?;
+        if key.as_deref() == Some(RESERVED_KEY) {
+            let WrapperOwned(value) = visitor.next_value()?;
+            key = visitor.next_key()?;
+            // Same rule as in `JsonVisitor::visit_map`.
+            if key.is_none() {
+                if let Some(ipld) = decode_reserved(&value)? {
+                    return ipld.into_tokens(&mut |token| self.0.emit(token));
+                }
+            }
+            self.0.emit(Token::MapStart)?;
+            self.0.emit(Token::Key(RESERVED_KEY.to_string()))?;
+            value.into_tokens(&mut |token| self.0.emit(token))?;
+        } else {
+            self.0.emit(Token::MapStart)?;
+        }
+        while let Some(k) = key {
+            self.0.emit(Token::Key(k))?;
+            visitor.next_value_seed(TokenVisitor(&mut *self.0))?;
+            key = visitor.next_key()?;
+        }
+        self.0.emit(Token::MapEnd)
+    }
+}
+
+// Builds an `Ipld` from tokens. The lists and maps that are still open are kept on a stack, so
+// the value decoded so far can be recovered if the tokens stop early.
+#[derive(Default)]
+struct Builder {
+    stack: Vec<(Ipld, Option<String>)>,
+    root: Option<Ipld>,
+}
+
+impl Builder {
+    fn push(&mut self, token: Token) -> CoreResult<()> {
+        let ipld = match token {
+            Token::ListStart => {
+                self.stack.push((Ipld::List(Vec::new()), None));
+                return Ok(());
+            }
+            Token::MapStart => {
+                self.stack.push((Ipld::Map(BTreeMap::new()), None));
+                return Ok(());
+            }
+            Token::Key(key) => {
+                if let Some((_, pending)) = self.stack.last_mut() {
+                    *pending = Some(key);
+                }
+                return Ok(());
+            }
+            Token::ListEnd | Token::MapEnd => match self.stack.pop() {
+                Some((ipld, _)) => ipld,
+                None => return Err(CoreError::msg("unbalanced tokens")),
+            },
+            Token::Null => Ipld::Null,
+            Token::Bool(b) => Ipld::Bool(b),
+            Token::Integer(i) => Ipld::Integer(i),
+            Token::Float(f) => Ipld::Float(f),
+            Token::String(s) => Ipld::String(s),
+            Token::Bytes(b) => Ipld::Bytes(b),
+            Token::Link(cid) => Ipld::Link(cid),
+        };
+        match self.stack.last_mut() {
+            None => self.root = Some(ipld),
+            Some((Ipld::List(list), _)) => list.push(ipld),
+            Some((Ipld::Map(map), key)) => {
+                let key = key.take().unwrap_or_default();
+                if map.contains_key(&key) {
+                    return Err(CoreError::msg("duplicate map key"));
+                }
+                map.insert(key, ipld);
+            }
+            Some(_) => unreachable!(),
+        }
+        Ok(())
+    }
+
+    // Returns the root, or the open containers with each one added to its parent.
+    fn finish(mut self) -> Option<Ipld> {
+        let mut partial = self.root.take();
+        while let Some((mut ipld, key)) = self.stack.pop() {
+            match (&mut ipld, partial) {
+                (Ipld::List(list), Some(child)) => list.push(child),
+                (Ipld::Map(map), Some(child)) => {
+                    if let Some(key) = key {
+                        map.entry(key).or_insert(child);
+                    }
+                }
+                _ => {}
+            }
+            partial = Some(ipld);
+        }
+        partial
+    }
+}
+
 // Needed for `visit_seq` and `visit_map` in Deserializer
 /// We cannot directly implement `serde::Deserializer` for `Ipld` as it is a remote type.
 /// Instead wrap it into a newtype struct and implement `serde::Deserialize` for that one.
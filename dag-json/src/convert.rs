@@ -0,0 +1,504 @@
+//! Conversion of typed values to and from ipld.
+//!
+//! Types that aren't encoded natively are encoded by converting them to an [`Ipld`] first and
+//! decoded by converting the decoded [`Ipld`]. The conversions use the same data model
+//! representation as the dag-cbor implementations, so a value has the same shape in both codecs.
+//! `#[derive(DagJson)]` implements them for structs and enums.
+use libipld_core::cid::Cid;
+use libipld_core::error::{NumberConversionError, Result, TypeError, TypeErrorType};
+use libipld_core::ipld::Ipld;
+use libipld_core::number::{Number, NumberPolicy};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::num::{
+    NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroU16, NonZeroU32, NonZeroU64,
+    NonZeroU8,
+};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// Converts a value to the [`Ipld`] it is represented as.
+pub trait ToIpld {
+    /// Converts the value to ipld.
+    fn to_ipld(&self) -> Result<Ipld>;
+}
+
+/// Converts an [`Ipld`] back to the value it represents.
+pub trait FromIpld: Sized {
+    /// Converts the ipld to a value.
+    fn from_ipld(ipld: Ipld) -> Result<Self>;
+}
+
+/// A list or bytes don't have the length of the type they are converted to.
+#[derive(Debug, Error)]
+#[error("Expected a length of {expected} but found {found}.")]
+pub struct LengthMismatch {
+    /// The expected length.
+    pub expected: usize,
+    /// The actual length.
+    pub found: usize,
+}
+
+/// Converts the list to an array of its items, failing if it doesn't have `N` items.
+pub(crate) fn into_array<const N: usize>(ipld: Ipld) -> Result<[Ipld; N]> {
+    match ipld {
+        Ipld::List(list) => list.try_into().map_err(|list: Vec<Ipld>| {
+            LengthMismatch {
+                expected: N,
+                found: list.len(),
+            }
+            .into()
+        }),
+        ipld => Err(TypeError::new(TypeErrorType::List, ipld).into()),
+    }
+}
+
+/// Path is not valid UTF-8.
+#[derive(Debug, Error)]
+#[error("Path is not valid UTF-8.")]
+pub struct InvalidPath;
+
+/// Converts the bytes to an array, failing if there aren't `N` of them.
+fn into_bytes<const N: usize>(ipld: Ipld) -> Result<[u8; N]> {
+    let bytes = Box::<[u8]>::from_ipld(ipld)?;
+    (*bytes).try_into().map_err(|_| {
+        LengthMismatch {
+            expected: N,
+            found: bytes.len(),
+        }
+        .into()
+    })
+}
+
+impl ToIpld for Ipld {
+    fn to_ipld(&self) -> Result<Ipld> {
+        Ok(self.clone())
+    }
+}
+
+impl FromIpld for Ipld {
+    fn from_ipld(ipld: Ipld) -> Result<Self> {
+        Ok(ipld)
+    }
+}
+
+impl<T: ToIpld + ?Sized> ToIpld for &T {
+    fn to_ipld(&self) -> Result<Ipld> {
+        (**self).to_ipld()
+    }
+}
+
+impl<T: ToIpld + ?Sized> ToIpld for Box<T> {
+    fn to_ipld(&self) -> Result<Ipld> {
+        (**self).to_ipld()
+    }
+}
+
+impl<T: FromIpld> FromIpld for Box<T> {
+    fn from_ipld(ipld: Ipld) -> Result<Self> {
+        Ok(Box::new(T::from_ipld(ipld)?))
+    }
+}
+
+macro_rules! impl_kind {
+    ($($ty:ty => $kind:ident,)*) => {
+        $(
+            impl ToIpld for $ty {
+                fn to_ipld(&self) -> Result<Ipld> {
+                    Ok(Ipld::$kind(self.clone()))
+                }
+            }
+
+            impl FromIpld for $ty {
+                fn from_ipld(ipld: Ipld) -> Result<Self> {
+                    match ipld {
+                        Ipld::$kind(value) => Ok(value),
+                        ipld => Err(TypeError::new(TypeErrorType::$kind, ipld).into()),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_kind!(
+    bool => Bool,
+    String => String,
+    Cid => Link,
+);
+
+// Numbers are converted with the strict number policy, like they are decoded.
+macro_rules! impl_num {
+    ($($ty:ty),*) => {
+        $(
+            impl ToIpld for $ty {
+                fn to_ipld(&self) -> Result<Ipld> {
+                    Ok(Ipld::from(*self))
+                }
+            }
+
+            impl FromIpld for $ty {
+                fn from_ipld(ipld: Ipld) -> Result<Self> {
+                    ipld.to_number(NumberPolicy::Strict)
+                }
+            }
+        )*
+    };
+}
+
+impl_num!(u8, u16, u32, u64, i8, i16, i32, i64, i128, f64);
+
+impl ToIpld for str {
+    fn to_ipld(&self) -> Result<Ipld> {
+        Ok(Ipld::String(self.into()))
+    }
+}
+
+/// Converted to bytes, unlike `Vec<u8>` which is a list.
+impl ToIpld for [u8] {
+    fn to_ipld(&self) -> Result<Ipld> {
+        Ok(Ipld::Bytes(self.into()))
+    }
+}
+
+impl FromIpld for Box<[u8]> {
+    fn from_ipld(ipld: Ipld) -> Result<Self> {
+        match ipld {
+            Ipld::Bytes(bytes) => Ok(bytes.into()),
+            ipld => Err(TypeError::new(TypeErrorType::Bytes, ipld).into()),
+        }
+    }
+}
+
+impl<T: ToIpld> ToIpld for Option<T> {
+    fn to_ipld(&self) -> Result<Ipld> {
+        match self {
+            Some(value) => value.to_ipld(),
+            None => Ok(Ipld::Null),
+        }
+    }
+}
+
+impl<T: FromIpld> FromIpld for Option<T> {
+    fn from_ipld(ipld: Ipld) -> Result<Self> {
+        match ipld {
+            Ipld::Null => Ok(None),
+            ipld => Ok(Some(T::from_ipld(ipld)?)),
+        }
+    }
+}
+
+impl<T: ToIpld> ToIpld for Vec<T> {
+    fn to_ipld(&self) -> Result<Ipld> {
+        Ok(Ipld::List(
+            self.iter().map(ToIpld::to_ipld).collect::<Result<_>>()?,
+        ))
+    }
+}
+
+impl<T: FromIpld> FromIpld for Vec<T> {
+    fn from_ipld(ipld: Ipld) -> Result<Self> {
+        match ipld {
+            Ipld::List(list) => list.into_iter().map(T::from_ipld).collect(),
+            ipld => Err(TypeError::new(TypeErrorType::List, ipld).into()),
+        }
+    }
+}
+
+impl<T: ToIpld> ToIpld for BTreeMap<String, T> {
+    fn to_ipld(&self) -> Result<Ipld> {
+        Ok(Ipld::Map(
+            self.iter()
+                .map(|(key, value)| Ok((key.clone(), value.to_ipld()?)))
+                .collect::<Result<_>>()?,
+        ))
+    }
+}
+
+impl<T: FromIpld> FromIpld for BTreeMap<String, T> {
+    fn from_ipld(ipld: Ipld) -> Result<Self> {
+        match ipld {
+            Ipld::Map(map) => map
+                .into_iter()
+                .map(|(key, value)| Ok((key, T::from_ipld(value)?)))
+                .collect(),
+            ipld => Err(TypeError::new(TypeErrorType::Map, ipld).into()),
+        }
+    }
+}
+
+/// Converted to an empty list.
+impl ToIpld for () {
+    fn to_ipld(&self) -> Result<Ipld> {
+        Ok(Ipld::List(vec![]))
+    }
+}
+
+impl FromIpld for () {
+    fn from_ipld(ipld: Ipld) -> Result<Self> {
+        let [] = into_array(ipld)?;
+        Ok(())
+    }
+}
+
+macro_rules! impl_tuple {
+    ($(($($ty:ident $value:ident),*),)*) => {
+        $(
+            impl<$($ty: ToIpld),*> ToIpld for ($($ty,)*) {
+                fn to_ipld(&self) -> Result<Ipld> {
+                    let ($($value,)*) = self;
+                    Ok(Ipld::List(vec![$($value.to_ipld()?),*]))
+                }
+            }
+
+            impl<$($ty: FromIpld),*> FromIpld for ($($ty,)*) {
+                fn from_ipld(ipld: Ipld) -> Result<Self> {
+                    let [$($value),*] = into_array(ipld)?;
+                    Ok(($($ty::from_ipld($value)?,)*))
+                }
+            }
+        )*
+    };
+}
+
+impl_tuple!(
+    (A a),
+    (A a, B b),
+    (A a, B b, C c),
+    (A a, B b, C c, D d),
+);
+
+impl<T: ToIpld, const N: usize> ToIpld for [T; N] {
+    fn to_ipld(&self) -> Result<Ipld> {
+        Ok(Ipld::List(
+            self.iter().map(ToIpld::to_ipld).collect::<Result<_>>()?,
+        ))
+    }
+}
+
+impl<T: FromIpld, const N: usize> FromIpld for [T; N] {
+    fn from_ipld(ipld: Ipld) -> Result<Self> {
+        let values = Vec::<T>::from_ipld(ipld)?;
+        let found = values.len();
+        values
+            .try_into()
+            .map_err(|_| LengthMismatch { expected: N, found }.into())
+    }
+}
+
+/// Converted to a `[seconds, nanoseconds]` list.
+impl ToIpld for Duration {
+    fn to_ipld(&self) -> Result<Ipld> {
+        (self.as_secs(), self.subsec_nanos()).to_ipld()
+    }
+}
+
+impl FromIpld for Duration {
+    fn from_ipld(ipld: Ipld) -> Result<Self> {
+        let (secs, nanos): (u64, u32) = FromIpld::from_ipld(ipld)?;
+        if nanos >= 1_000_000_000 {
+            return Err(NumberConversionError(Number::from(nanos as i128), "Duration").into());
+        }
+        Ok(Duration::new(secs, nanos))
+    }
+}
+
+/// Converted to a `[seconds, nanoseconds]` list relative to the unix epoch. The seconds are
+/// negative for times before the epoch, the nanoseconds are always positive.
+impl ToIpld for SystemTime {
+    fn to_ipld(&self) -> Result<Ipld> {
+        let (secs, nanos) = match self.duration_since(UNIX_EPOCH) {
+            Ok(after) => (after.as_secs() as i128, after.subsec_nanos()),
+            Err(err) => {
+                let before = err.duration();
+                match before.subsec_nanos() {
+                    0 => (-(before.as_secs() as i128), 0),
+                    nanos => (-(before.as_secs() as i128) - 1, 1_000_000_000 - nanos),
+                }
+            }
+        };
+        let secs: i64 = Number::from(secs).to(NumberPolicy::Strict)?;
+        (secs, nanos).to_ipld()
+    }
+}
+
+impl FromIpld for SystemTime {
+    fn from_ipld(ipld: Ipld) -> Result<Self> {
+        let (secs, nanos): (i64, u32) = FromIpld::from_ipld(ipld)?;
+        if nanos >= 1_000_000_000 {
+            return Err(NumberConversionError(Number::from(nanos as i128), "SystemTime").into());
+        }
+        let time = if secs >= 0 {
+            UNIX_EPOCH.checked_add(Duration::new(secs as u64, nanos))
+        } else {
+            UNIX_EPOCH
+                .checked_sub(Duration::from_secs(secs.unsigned_abs()))
+                .and_then(|time| time.checked_add(Duration::from_nanos(nanos as u64)))
+        };
+        time.ok_or_else(|| NumberConversionError(Number::from(secs as i128), "SystemTime").into())
+    }
+}
+
+/// Converted to a string. Fails if the path isn't valid UTF-8.
+impl ToIpld for Path {
+    fn to_ipld(&self) -> Result<Ipld> {
+        self.to_str().ok_or(InvalidPath)?.to_ipld()
+    }
+}
+
+impl ToIpld for PathBuf {
+    fn to_ipld(&self) -> Result<Ipld> {
+        self.as_path().to_ipld()
+    }
+}
+
+impl FromIpld for PathBuf {
+    fn from_ipld(ipld: Ipld) -> Result<Self> {
+        Ok(String::from_ipld(ipld)?.into())
+    }
+}
+
+/// Converted to the 4 bytes of the address.
+impl ToIpld for Ipv4Addr {
+    fn to_ipld(&self) -> Result<Ipld> {
+        self.octets()[..].to_ipld()
+    }
+}
+
+impl FromIpld for Ipv4Addr {
+    fn from_ipld(ipld: Ipld) -> Result<Self> {
+        Ok(into_bytes::<4>(ipld)?.into())
+    }
+}
+
+/// Converted to the 16 bytes of the address.
+impl ToIpld for Ipv6Addr {
+    fn to_ipld(&self) -> Result<Ipld> {
+        self.octets()[..].to_ipld()
+    }
+}
+
+impl FromIpld for Ipv6Addr {
+    fn from_ipld(ipld: Ipld) -> Result<Self> {
+        Ok(into_bytes::<16>(ipld)?.into())
+    }
+}
+
+/// Converted to 4 or 16 bytes, depending on the version.
+impl ToIpld for IpAddr {
+    fn to_ipld(&self) -> Result<Ipld> {
+        match self {
+            Self::V4(ip) => ip.to_ipld(),
+            Self::V6(ip) => ip.to_ipld(),
+        }
+    }
+}
+
+impl FromIpld for IpAddr {
+    fn from_ipld(ipld: Ipld) -> Result<Self> {
+        match &ipld {
+            Ipld::Bytes(bytes) if bytes.len() == 4 => Ok(Ipv4Addr::from_ipld(ipld)?.into()),
+            _ => Ok(Ipv6Addr::from_ipld(ipld)?.into()),
+        }
+    }
+}
+
+/// Converted to an `[ip, port]` list. The flow info and scope id of IPv6 addresses are dropped.
+impl ToIpld for SocketAddr {
+    fn to_ipld(&self) -> Result<Ipld> {
+        (self.ip(), self.port()).to_ipld()
+    }
+}
+
+impl FromIpld for SocketAddr {
+    fn from_ipld(ipld: Ipld) -> Result<Self> {
+        let (ip, port): (IpAddr, u16) = FromIpld::from_ipld(ipld)?;
+        Ok(Self::new(ip, port))
+    }
+}
+
+/// Converted to the 16 bytes of the uuid.
+#[cfg(feature = "uuid")]
+impl ToIpld for uuid::Uuid {
+    fn to_ipld(&self) -> Result<Ipld> {
+        self.as_bytes()[..].to_ipld()
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl FromIpld for uuid::Uuid {
+    fn from_ipld(ipld: Ipld) -> Result<Self> {
+        Ok(Self::from_bytes(into_bytes(ipld)?))
+    }
+}
+
+macro_rules! impl_nonzero {
+    ($($nzero:ty => $base:ty,)*) => {
+        $(
+            impl ToIpld for $nzero {
+                fn to_ipld(&self) -> Result<Ipld> {
+                    self.get().to_ipld()
+                }
+            }
+
+            impl FromIpld for $nzero {
+                fn from_ipld(ipld: Ipld) -> Result<Self> {
+                    Ok(<$nzero>::try_from(<$base>::from_ipld(ipld)?)?)
+                }
+            }
+        )*
+    };
+}
+
+impl_nonzero!(
+    NonZeroU8 => u8,
+    NonZeroU16 => u16,
+    NonZeroU32 => u32,
+    NonZeroU64 => u64,
+    NonZeroI8 => i8,
+    NonZeroI16 => i16,
+    NonZeroI32 => i32,
+    NonZeroI64 => i64,
+    NonZeroI128 => i128,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libipld_core::error::NumberConversionError;
+
+    #[test]
+    fn conversions() {
+        let value = (
+            Some(1u8),
+            vec![String::from("a")],
+            BTreeMap::from([(String::from("b"), true)]),
+            Box::<[u8]>::from(&[1, 2][..]),
+        );
+        let ipld = Ipld::List(vec![
+            Ipld::Integer(1),
+            Ipld::List(vec![Ipld::String("a".into())]),
+            Ipld::Map(BTreeMap::from([("b".into(), Ipld::Bool(true))])),
+            Ipld::Bytes(vec![1, 2]),
+        ]);
+        assert_eq!(value.to_ipld().unwrap(), ipld);
+        assert_eq!(<(_, _, _, _)>::from_ipld(ipld).unwrap(), value);
+        assert_eq!(
+            vec![1u8, 2].to_ipld().unwrap(),
+            Ipld::List(vec![Ipld::Integer(1), Ipld::Integer(2)])
+        );
+        assert_eq!(<Option<u8>>::from_ipld(Ipld::Null).unwrap(), None,);
+    }
+
+    #[test]
+    fn invalid() {
+        let err = u8::from_ipld(Ipld::Integer(300)).unwrap_err();
+        assert!(err.downcast_ref::<NumberConversionError>().is_some());
+        let err = String::from_ipld(Ipld::Integer(1)).unwrap_err();
+        assert!(err.downcast_ref::<TypeError>().is_some());
+        let err = <(u8, u8)>::from_ipld(Ipld::List(vec![Ipld::Integer(1)])).unwrap_err();
+        let err = err.downcast::<LengthMismatch>().unwrap();
+        assert_eq!((err.expected, err.found), (2, 1));
+    }
+}
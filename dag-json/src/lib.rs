@@ -4,21 +4,64 @@
 
 use core::convert::TryFrom;
 use libipld_core::cid::Cid;
-use libipld_core::codec::{Codec, Decode, Encode, References};
-use libipld_core::error::{Result, UnsupportedCodec};
+use libipld_core::codec::{Codec, Decode, Encode, References, Tokens};
+use libipld_core::error::{PartialIpld, Result, TypeError, TypeErrorType, UnsupportedCodec};
 use libipld_core::ipld::Ipld;
+use libipld_core::number::{Number, NumberPolicy};
+use libipld_core::token::Token;
 // TODO vmx 2020-05-28: Don't expose the `serde_json` error directly, but wrap it in a custom one
 pub use serde_json::Error;
 use std::io::{Read, Seek, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::num::{
+    NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroU16, NonZeroU32, NonZeroU64,
+    NonZeroU8,
+};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
 
 mod codec;
+mod convert;
+
+pub use codec::is_ambiguous;
+pub use convert::{FromIpld, InvalidPath, LengthMismatch, ToIpld};
 
 /// Json codec.
+///
+/// Encoding is deterministic, so the same [`Ipld`] always encodes to the same bytes and hashes to
+/// the same CID. Map keys are sorted bytewise, there is no whitespace and floats use the shortest
+/// representation that roundtrips. NaN and infinities are rejected.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct DagJsonCodec;
 
 impl Codec for DagJsonCodec {}
 
+impl DagJsonCodec {
+    /// Encodes ipld as dag-json indented with two spaces, for debugging and display. The output
+    /// decodes to the same value, but isn't the canonical encoding, so don't hash it.
+    pub fn encode_pretty(&self, ipld: &Ipld) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        codec::encode_pretty(ipld, &mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Decodes a block as a stream of [`Token`]s passed to `f`, without building an [`Ipld`].
+    ///
+    /// Keys are emitted in the order they appear in the block, duplicates aren't detected.
+    /// Decoding stops at the first error returned by `f`.
+    pub fn tokens<F: FnMut(Token) -> Result<()>>(&self, bytes: &[u8], f: F) -> Result<()> {
+        codec::tokens(&mut &bytes[..], f)
+    }
+
+    /// Decodes an [`Ipld`], keeping what was decoded so far if the block turns out to be corrupt.
+    ///
+    /// Like `DagCborCodec::decode_lenient`, this returns the partially decoded value together with
+    /// the error and the byte offset at which decoding stopped, instead of discarding everything.
+    pub fn decode_lenient(&self, bytes: &[u8]) -> core::result::Result<Ipld, PartialIpld> {
+        codec::decode_lenient(bytes)
+    }
+}
+
 impl From<DagJsonCodec> for u64 {
     fn from(_: DagJsonCodec) -> Self {
         0x0129
@@ -47,12 +90,106 @@ impl Decode<DagJsonCodec> for Ipld {
 
 impl References<DagJsonCodec> for Ipld {
     fn references<R: Read + Seek, E: Extend<Cid>>(
-        c: DagJsonCodec,
+        _: DagJsonCodec,
         r: &mut R,
         set: &mut E,
     ) -> Result<()> {
-        Ipld::decode(c, r)?.references(set);
-        Ok(())
+        Ok(codec::references(r, set)?)
+    }
+}
+
+// Numbers are decoded with the strict number policy, decode a `Number` to convert it under another
+// policy.
+macro_rules! impl_num {
+    ($($ty:ty),*) => {
+        $(
+            impl Encode<DagJsonCodec> for $ty {
+                fn encode<W: Write>(&self, c: DagJsonCodec, w: &mut W) -> Result<()> {
+                    Ipld::from(*self).encode(c, w)
+                }
+            }
+
+            impl Decode<DagJsonCodec> for $ty {
+                fn decode<R: Read + Seek>(c: DagJsonCodec, r: &mut R) -> Result<Self> {
+                    Ipld::decode(c, r)?.to_number(NumberPolicy::Strict)
+                }
+            }
+        )*
+    };
+}
+
+impl_num!(u8, u16, u32, u64, i8, i16, i32, i64, i128, f64);
+
+impl Decode<DagJsonCodec> for Number {
+    fn decode<R: Read + Seek>(c: DagJsonCodec, r: &mut R) -> Result<Self> {
+        let ipld = Ipld::decode(c, r)?;
+        ipld.as_number()
+            .ok_or_else(|| TypeError::new(TypeErrorType::Integer, ipld).into())
+    }
+}
+
+impl Tokens<DagJsonCodec> for Ipld {
+    fn tokens<R: Read + Seek, F: FnMut(Token) -> Result<()>>(
+        _: DagJsonCodec,
+        r: &mut R,
+        f: &mut F,
+    ) -> Result<()> {
+        codec::tokens(r, f)
+    }
+}
+
+// The std types are converted through `Ipld`, using the representations documented on their
+// `ToIpld` implementations, which are the same as in dag-cbor.
+macro_rules! impl_via_ipld {
+    ($($(#[$attr:meta])* $ty:ty,)*) => {
+        $(
+            $(#[$attr])*
+            impl Encode<DagJsonCodec> for $ty {
+                fn encode<W: Write>(&self, c: DagJsonCodec, w: &mut W) -> Result<()> {
+                    self.to_ipld()?.encode(c, w)
+                }
+            }
+
+            $(#[$attr])*
+            impl Decode<DagJsonCodec> for $ty {
+                fn decode<R: Read + Seek>(c: DagJsonCodec, r: &mut R) -> Result<Self> {
+                    Self::from_ipld(Ipld::decode(c, r)?)
+                }
+            }
+        )*
+    };
+}
+
+impl_via_ipld!(
+    Duration,
+    SystemTime,
+    PathBuf,
+    Ipv4Addr,
+    Ipv6Addr,
+    IpAddr,
+    SocketAddr,
+    NonZeroU8,
+    NonZeroU16,
+    NonZeroU32,
+    NonZeroU64,
+    NonZeroI8,
+    NonZeroI16,
+    NonZeroI32,
+    NonZeroI64,
+    NonZeroI128,
+    #[cfg(feature = "uuid")]
+    uuid::Uuid,
+);
+
+impl<T: ToIpld, const N: usize> Encode<DagJsonCodec> for [T; N] {
+    fn encode<W: Write>(&self, c: DagJsonCodec, w: &mut W) -> Result<()> {
+        self.to_ipld()?.encode(c, w)
+    }
+}
+
+impl<T: FromIpld, const N: usize> Decode<DagJsonCodec> for [T; N] {
+    fn decode<R: Read + Seek>(c: DagJsonCodec, r: &mut R) -> Result<Self> {
+        Self::from_ipld(Ipld::decode(c, r)?)
     }
 }
 
@@ -60,6 +197,7 @@ impl References<DagJsonCodec> for Ipld {
 mod tests {
     use super::*;
     use libipld_core::cid::Cid;
+    use libipld_core::error::NumberConversionError;
     use libipld_core::multihash::{Code, MultihashDigest};
     use std::collections::BTreeMap;
 
@@ -90,4 +228,252 @@ mod tests {
         let contact_decoded: Ipld = DagJsonCodec.decode(&contact_encoded).unwrap();
         assert_eq!(contact_decoded, contact);
     }
+
+    #[test]
+    fn references() {
+        let cid = Cid::new_v1(0x55, Code::Blake3_256.digest(&b"block"[..]));
+        let json = format!(
+            r#"{{"a":[1,-2.5,null,true,{{"/":"{cid}"}}],"b":{{"/":{{"bytes":"AQI"}}}},"c":{{"/":"{cid}","d":1}},"e":{{"/":"{cid}"}}}}"#
+        );
+        let mut refs = Vec::new();
+        DagJsonCodec
+            .references::<Ipld, _>(json.as_bytes(), &mut refs)
+            .unwrap();
+        let mut expected = Vec::new();
+        DagJsonCodec
+            .decode::<Ipld>(json.as_bytes())
+            .unwrap()
+            .references(&mut expected);
+        assert_eq!(refs, vec![cid, cid]);
+        assert_eq!(refs, expected);
+
+        assert!(DagJsonCodec
+            .references::<Ipld, _>(br#"{"/":"not a cid"}"#, &mut refs)
+            .is_err());
+        assert!(DagJsonCodec
+            .references::<Ipld, _>(b"[1,", &mut refs)
+            .is_err());
+    }
+
+    #[test]
+    fn bytes() {
+        let ipld = Ipld::List(vec![Ipld::Bytes(vec![1, 2]), Ipld::Bytes(vec![])]);
+        let json = DagJsonCodec.encode(&ipld).unwrap();
+        assert_eq!(json, br#"[{"/":{"bytes":"AQI"}},{"/":{"bytes":""}}]"#);
+        assert_eq!(DagJsonCodec.decode::<Ipld>(&json).unwrap(), ipld);
+
+        // Extra keys make it a map.
+        let json = br#"{"/":{"bytes":"AQI","a":1}}"#;
+        assert!(matches!(
+            DagJsonCodec.decode::<Ipld>(json).unwrap(),
+            Ipld::Map(_)
+        ));
+        assert!(DagJsonCodec
+            .decode::<Ipld>(br#"{"/":{"bytes":"!"}}"#)
+            .is_err());
+    }
+
+    #[test]
+    fn ambiguous_maps() {
+        let link = Ipld::Map(BTreeMap::from([("/".into(), Ipld::String("a".into()))]));
+        assert!(DagJsonCodec.encode(&link).is_err());
+        let bytes = Ipld::Map(BTreeMap::from([(
+            "/".into(),
+            Ipld::Map(BTreeMap::from([(
+                "bytes".into(),
+                Ipld::String("AQI".into()),
+            )])),
+        )]));
+        assert!(DagJsonCodec.encode(&bytes).is_err());
+
+        let map = Ipld::Map(BTreeMap::from([("/".into(), Ipld::Integer(1))]));
+        let json = DagJsonCodec.encode(&map).unwrap();
+        assert_eq!(DagJsonCodec.decode::<Ipld>(&json).unwrap(), map);
+    }
+
+    #[test]
+    fn deterministic() {
+        let ipld = Ipld::Map(BTreeMap::from([
+            ("b".into(), Ipld::Float(0.1)),
+            (
+                "a".into(),
+                Ipld::List(vec![Ipld::Float(1.0), Ipld::Float(-1e300)]),
+            ),
+            ("aa".into(), Ipld::Integer(1)),
+            ("B".into(), Ipld::Null),
+        ]));
+        let json = DagJsonCodec.encode(&ipld).unwrap();
+        assert_eq!(json, br#"{"B":null,"a":[1.0,-1e+300],"aa":1,"b":0.1}"#);
+
+        assert!(DagJsonCodec.encode(&Ipld::Float(f64::NAN)).is_err());
+        assert!(DagJsonCodec.encode(&Ipld::Float(f64::INFINITY)).is_err());
+    }
+
+    #[test]
+    fn encode_pretty() {
+        let cid = Cid::new_v1(0x55, Code::Blake3_256.digest(b"block"));
+        let ipld = Ipld::Map(BTreeMap::from([
+            ("link".into(), Ipld::Link(cid)),
+            ("list".into(), Ipld::List(vec![Ipld::Integer(1)])),
+        ]));
+        let json = DagJsonCodec.encode_pretty(&ipld).unwrap();
+        assert_eq!(
+            String::from_utf8(json.clone()).unwrap(),
+            format!(
+                "{{\n  \"link\": {{\n    \"/\": \"{}\"\n  }},\n  \"list\": [\n    1\n  ]\n}}",
+                cid
+            )
+        );
+        assert_eq!(DagJsonCodec.decode::<Ipld>(&json).unwrap(), ipld);
+    }
+
+    #[test]
+    fn tokens() {
+        let cid = Cid::new_v1(0x55, Code::Blake3_256.digest(b"block"));
+        let json = format!(
+            r#"{{"b":[{{"/":"{}"}},{{"/":{{"bytes":"AQI"}}}}],"a":{{"/":1,"x":null}}}}"#,
+            cid
+        );
+        let mut tokens = Vec::new();
+        DagJsonCodec
+            .tokens(json.as_bytes(), |token| {
+                tokens.push(token);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::MapStart,
+                Token::Key("b".into()),
+                Token::ListStart,
+                Token::Link(cid),
+                Token::Bytes(vec![1, 2]),
+                Token::ListEnd,
+                Token::Key("a".into()),
+                Token::MapStart,
+                Token::Key("/".into()),
+                Token::Integer(1),
+                Token::Key("x".into()),
+                Token::Null,
+                Token::MapEnd,
+                Token::MapEnd,
+            ]
+        );
+
+        let err = DagJsonCodec.tokens(json.as_bytes(), |token| match token {
+            Token::Link(_) => Err(libipld_core::error::Error::msg("found")),
+            _ => Ok(()),
+        });
+        assert_eq!(err.unwrap_err().to_string(), "found");
+        assert!(DagJsonCodec.tokens(b"[1,", |_| Ok(())).is_err());
+    }
+
+    #[test]
+    fn lenient() {
+        let json = br#"[1,{"a":true,"b":"hello"}]"#;
+        assert_eq!(
+            DagJsonCodec.decode_lenient(json).unwrap(),
+            DagJsonCodec.decode::<Ipld>(json).unwrap()
+        );
+
+        // Cut off in the middle of the "hello" string.
+        let partial = DagJsonCodec.decode_lenient(&json[..21]).unwrap_err();
+        assert_eq!(
+            partial.ipld,
+            Some(Ipld::List(vec![
+                Ipld::Integer(1),
+                Ipld::Map(BTreeMap::from([("a".to_string(), Ipld::Bool(true))])),
+            ]))
+        );
+        assert_eq!(partial.offset, 21);
+
+        let partial = DagJsonCodec
+            .decode_lenient(br#"{"a":[1,2],"a":3}"#)
+            .unwrap_err();
+        assert_eq!(partial.error.to_string(), "duplicate map key");
+        assert_eq!(
+            partial.ipld,
+            Some(Ipld::Map(BTreeMap::from([(
+                "a".to_string(),
+                Ipld::List(vec![Ipld::Integer(1), Ipld::Integer(2)])
+            )])))
+        );
+
+        let partial = DagJsonCodec.decode_lenient(b"x").unwrap_err();
+        assert_eq!(partial.ipld, None);
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(DagJsonCodec.encode(&300u16).unwrap(), b"300");
+        assert_eq!(DagJsonCodec.decode::<u16>(b"300").unwrap(), 300);
+        let err = DagJsonCodec.decode::<u8>(b"300").unwrap_err();
+        assert!(err.downcast_ref::<NumberConversionError>().is_some());
+        assert!(DagJsonCodec.decode::<u64>(b"-1").is_err());
+
+        // Floats don't decode as integers and vice versa, unless converted with another policy.
+        assert!(DagJsonCodec.decode::<i32>(b"2.5").is_err());
+        assert!(DagJsonCodec.decode::<f64>(b"2").is_err());
+        let number: Number = DagJsonCodec.decode(b"2.5").unwrap();
+        assert_eq!(number.to::<i32>(NumberPolicy::Round).unwrap(), 3);
+        assert!(DagJsonCodec.decode::<Number>(b"\"2\"").is_err());
+    }
+
+    #[test]
+    fn std_types() {
+        use libipld_core::codec::assert_roundtrip;
+        use std::net::Ipv4Addr;
+        use std::num::NonZeroU8;
+        use std::time::UNIX_EPOCH;
+
+        let duration = Duration::new(5, 1);
+        assert_eq!(DagJsonCodec.encode(&duration).unwrap(), b"[5,1]");
+        assert_roundtrip(
+            DagJsonCodec,
+            &duration,
+            &Ipld::List(vec![5.into(), 1.into()]),
+        );
+        let time = UNIX_EPOCH + duration;
+        assert_eq!(DagJsonCodec.decode::<SystemTime>(b"[5,1]").unwrap(), time);
+        let path = PathBuf::from("a/b");
+        assert_eq!(DagJsonCodec.encode(&path).unwrap(), br#""a/b""#);
+        let ip = IpAddr::from(Ipv4Addr::new(127, 0, 0, 1));
+        let addr = SocketAddr::new(ip, 80);
+        assert_eq!(
+            DagJsonCodec.encode(&addr).unwrap(),
+            br#"[{"/":{"bytes":"fwAAAQ"}},80]"#
+        );
+        assert_eq!(
+            DagJsonCodec
+                .decode::<SocketAddr>(br#"[{"/":{"bytes":"fwAAAQ"}},80]"#)
+                .unwrap(),
+            addr
+        );
+        assert_eq!(DagJsonCodec.encode(&[1u8, 2, 3]).unwrap(), b"[1,2,3]");
+        assert_eq!(
+            DagJsonCodec.decode::<[u8; 3]>(b"[1,2,3]").unwrap(),
+            [1, 2, 3]
+        );
+        assert!(DagJsonCodec.decode::<[u8; 3]>(b"[1,2]").is_err());
+        assert_eq!(
+            DagJsonCodec.decode::<NonZeroU8>(b"1").unwrap(),
+            NonZeroU8::new(1).unwrap()
+        );
+        assert!(DagJsonCodec.decode::<NonZeroU8>(b"0").is_err());
+        assert!(DagJsonCodec.decode::<Duration>(b"[5,1000000000]").is_err());
+        let err = DagJsonCodec
+            .decode::<IpAddr>(br#"{"/":{"bytes":"fwAA"}}"#)
+            .unwrap_err();
+        assert!(err.downcast_ref::<LengthMismatch>().is_some());
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid() {
+        let uuid = uuid::Uuid::from_bytes([7; 16]);
+        let bytes = DagJsonCodec.encode(&uuid).unwrap();
+        assert_eq!(bytes, br#"{"/":{"bytes":"BwcHBwcHBwcHBwcHBwcHBw"}}"#);
+        assert_eq!(DagJsonCodec.decode::<uuid::Uuid>(&bytes).unwrap(), uuid);
+    }
 }
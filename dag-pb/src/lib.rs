@@ -6,12 +6,14 @@ pub use crate::codec::{PbLink, PbNode};
 
 use core::convert::{TryFrom, TryInto};
 use libipld_core::cid::Cid;
-use libipld_core::codec::{Codec, Decode, Encode, References};
+use libipld_core::codec::{Codec, Decode, Encode, References, Tokens};
 use libipld_core::error::{Result, UnsupportedCodec};
 use libipld_core::ipld::Ipld;
+use libipld_core::token::Token;
 use std::io::{Read, Seek, Write};
 
 mod codec;
+pub mod unixfs;
 
 /// Protobuf codec.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -63,6 +65,16 @@ impl References<DagPbCodec> for Ipld {
     }
 }
 
+impl Tokens<DagPbCodec> for Ipld {
+    fn tokens<R: Read + Seek, F: FnMut(Token) -> Result<()>>(
+        c: DagPbCodec,
+        r: &mut R,
+        f: &mut F,
+    ) -> Result<()> {
+        Self::decode(c, r)?.into_tokens(f)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
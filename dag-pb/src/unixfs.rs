@@ -0,0 +1,518 @@
+//! UnixFS v1 read support.
+//!
+//! UnixFS stores files and directories as dag-pb nodes, with a protobuf [`UnixFsData`] message
+//! in the `Data` field. Large files are split into a tree of nodes whose leaves are either raw
+//! blocks or dag-pb nodes. Large directories are sharded into a HAMT. Blocks are loaded through
+//! a closure returning the encoded bytes of a CID.
+use crate::PbNode;
+use bytes::Bytes;
+use libipld_core::cid::Cid;
+use libipld_core::error::Result;
+use quick_protobuf::sizeofs::{sizeof_len, sizeof_varint};
+use quick_protobuf::{BytesReader, MessageWrite, Writer, WriterBackend};
+use std::ops::Range;
+use thiserror::Error;
+
+/// Multicodec of raw leaves.
+const RAW: u64 = 0x55;
+/// Multicodec of dag-pb nodes.
+const DAG_PB: u64 = 0x70;
+
+/// The data isn't valid UnixFS or uses an unsupported feature.
+#[derive(Debug, Error)]
+#[error("Invalid UnixFS: {0}.")]
+pub struct InvalidUnixFs(pub &'static str);
+
+/// The type of a UnixFS node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataType {
+    /// Raw file data.
+    Raw,
+    /// A directory.
+    Directory,
+    /// A file.
+    File,
+    /// Metadata.
+    Metadata,
+    /// A symbolic link.
+    Symlink,
+    /// A shard of a HAMT sharded directory.
+    HamtShard,
+}
+
+impl DataType {
+    fn from_u64(value: u64) -> Result<Self> {
+        Ok(match value {
+            0 => Self::Raw,
+            1 => Self::Directory,
+            2 => Self::File,
+            3 => Self::Metadata,
+            4 => Self::Symlink,
+            5 => Self::HamtShard,
+            _ => return Err(InvalidUnixFs("unknown data type").into()),
+        })
+    }
+
+    fn to_u64(self) -> u64 {
+        match self {
+            Self::Raw => 0,
+            Self::Directory => 1,
+            Self::File => 2,
+            Self::Metadata => 3,
+            Self::Symlink => 4,
+            Self::HamtShard => 5,
+        }
+    }
+}
+
+/// The UnixFS message stored in the `Data` field of a dag-pb node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnixFsData {
+    /// The node type.
+    pub data_type: DataType,
+    /// Inline file data, or the target of a symlink.
+    pub data: Option<Bytes>,
+    /// Total size of the file.
+    pub filesize: Option<u64>,
+    /// Sizes of the file data in each child, in link order.
+    pub blocksizes: Vec<u64>,
+    /// Hash function of a HAMT shard.
+    pub hash_type: Option<u64>,
+    /// Fanout of a HAMT shard.
+    pub fanout: Option<u64>,
+    /// Unix permission bits.
+    pub mode: Option<u32>,
+}
+
+impl UnixFsData {
+    /// Creates a message of the given type.
+    pub fn new(data_type: DataType) -> Self {
+        Self {
+            data_type,
+            data: None,
+            filesize: None,
+            blocksizes: Vec::new(),
+            hash_type: None,
+            fanout: None,
+            mode: None,
+        }
+    }
+
+    /// Deserializes the message.
+    pub fn from_bytes(buf: &Bytes) -> Result<Self> {
+        let mut r = BytesReader::from_bytes(buf);
+        let mut data_type = None;
+        let mut msg = Self::new(DataType::Raw);
+        while !r.is_eof() {
+            match r.next_tag(buf)? {
+                8 => data_type = Some(DataType::from_u64(r.read_uint64(buf)?)?),
+                18 => msg.data = Some(buf.slice_ref(r.read_bytes(buf)?)),
+                24 => msg.filesize = Some(r.read_uint64(buf)?),
+                32 => msg.blocksizes.push(r.read_uint64(buf)?),
+                // Packed blocksizes.
+                34 => msg
+                    .blocksizes
+                    .extend(r.read_packed(buf, |r, buf| r.read_uint64(buf))?),
+                40 => msg.hash_type = Some(r.read_uint64(buf)?),
+                48 => msg.fanout = Some(r.read_uint64(buf)?),
+                56 => msg.mode = Some(r.read_uint32(buf)?),
+                // Modification time isn't exposed.
+                66 => {
+                    r.read_bytes(buf)?;
+                }
+                _ => return Err(InvalidUnixFs("unexpected field").into()),
+            }
+        }
+        msg.data_type = data_type.ok_or(InvalidUnixFs("missing type"))?;
+        Ok(msg)
+    }
+
+    /// Serializes the message.
+    pub fn to_bytes(&self) -> Bytes {
+        let mut buf = Vec::with_capacity(self.get_size());
+        let mut writer = Writer::new(&mut buf);
+        self.write_message(&mut writer)
+            .expect("protobuf to be valid");
+        buf.into()
+    }
+
+    /// Reads the UnixFS message of a dag-pb node.
+    pub fn from_node(node: &PbNode) -> Result<Self> {
+        match &node.data {
+            Some(data) => Self::from_bytes(data),
+            None => Err(InvalidUnixFs("missing data").into()),
+        }
+    }
+
+    /// Returns the size of the file data in this node and its children.
+    pub fn file_size(&self) -> u64 {
+        self.filesize.unwrap_or_else(|| {
+            self.data
+                .as_ref()
+                .map(|d| d.len() as u64)
+                .unwrap_or_default()
+                .saturating_add(
+                    self.blocksizes
+                        .iter()
+                        .fold(0u64, |sum, size| sum.saturating_add(*size)),
+                )
+        })
+    }
+}
+
+impl MessageWrite for UnixFsData {
+    fn get_size(&self) -> usize {
+        let mut size = 1 + sizeof_varint(self.data_type.to_u64());
+        if let Some(data) = &self.data {
+            size += 1 + sizeof_len(data.len());
+        }
+        if let Some(filesize) = self.filesize {
+            size += 1 + sizeof_varint(filesize);
+        }
+        for blocksize in &self.blocksizes {
+            size += 1 + sizeof_varint(*blocksize);
+        }
+        if let Some(hash_type) = self.hash_type {
+            size += 1 + sizeof_varint(hash_type);
+        }
+        if let Some(fanout) = self.fanout {
+            size += 1 + sizeof_varint(fanout);
+        }
+        if let Some(mode) = self.mode {
+            size += 1 + sizeof_varint(mode as u64);
+        }
+        size
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> quick_protobuf::Result<()> {
+        w.write_with_tag(8, |w| w.write_uint64(self.data_type.to_u64()))?;
+        if let Some(data) = &self.data {
+            w.write_with_tag(18, |w| w.write_bytes(data))?;
+        }
+        if let Some(filesize) = self.filesize {
+            w.write_with_tag(24, |w| w.write_uint64(filesize))?;
+        }
+        for blocksize in &self.blocksizes {
+            w.write_with_tag(32, |w| w.write_uint64(*blocksize))?;
+        }
+        if let Some(hash_type) = self.hash_type {
+            w.write_with_tag(40, |w| w.write_uint64(hash_type))?;
+        }
+        if let Some(fanout) = self.fanout {
+            w.write_with_tag(48, |w| w.write_uint64(fanout))?;
+        }
+        if let Some(mode) = self.mode {
+            w.write_with_tag(56, |w| w.write_uint32(mode))?;
+        }
+        Ok(())
+    }
+}
+
+/// A UnixFS file.
+#[derive(Clone, Debug)]
+pub struct UnixFsFile {
+    node: PbNode,
+    data: UnixFsData,
+}
+
+impl UnixFsFile {
+    /// Creates a file from its root node.
+    pub fn new(node: PbNode) -> Result<Self> {
+        let data = UnixFsData::from_node(&node)?;
+        if !matches!(data.data_type, DataType::File | DataType::Raw) {
+            return Err(InvalidUnixFs("not a file").into());
+        }
+        Ok(Self { node, data })
+    }
+
+    /// Returns the size of the file.
+    pub fn size(&self) -> u64 {
+        self.data.file_size()
+    }
+
+    /// Reads the bytes in `range`, loading only the blocks overlapping it. The range is clamped
+    /// to the size of the file. The sizes in the nodes aren't trusted, the output grows with the
+    /// data actually read.
+    pub fn read_range<F>(&self, range: Range<u64>, mut load: F) -> Result<Vec<u8>>
+    where
+        F: FnMut(&Cid) -> Result<Bytes>,
+    {
+        let end = range.end.min(self.size());
+        let range = range.start.min(end)..end;
+        let mut out = Vec::new();
+        read_node(&self.node, &self.data, 0, &range, &mut load, &mut out)?;
+        Ok(out)
+    }
+
+    /// Reads the whole file.
+    pub fn read_all<F>(&self, load: F) -> Result<Vec<u8>>
+    where
+        F: FnMut(&Cid) -> Result<Bytes>,
+    {
+        self.read_range(0..self.size(), load)
+    }
+}
+
+/// Appends the part of `range` covered by `bytes` starting at `offset`.
+fn read_slice(bytes: &[u8], offset: u64, range: &Range<u64>, out: &mut Vec<u8>) {
+    let start = range.start.saturating_sub(offset).min(bytes.len() as u64) as usize;
+    let end = range.end.saturating_sub(offset).min(bytes.len() as u64) as usize;
+    out.extend_from_slice(&bytes[start..end]);
+}
+
+fn read_node<F>(
+    node: &PbNode,
+    data: &UnixFsData,
+    mut offset: u64,
+    range: &Range<u64>,
+    load: &mut F,
+    out: &mut Vec<u8>,
+) -> Result<()>
+where
+    F: FnMut(&Cid) -> Result<Bytes>,
+{
+    let overflow = || InvalidUnixFs("file size overflow");
+    if let Some(bytes) = &data.data {
+        read_slice(bytes, offset, range, out);
+        offset = offset
+            .checked_add(bytes.len() as u64)
+            .ok_or_else(overflow)?;
+    }
+    if node.links.len() != data.blocksizes.len() {
+        return Err(InvalidUnixFs("blocksizes don't match the links").into());
+    }
+    for (link, size) in node.links.iter().zip(&data.blocksizes) {
+        if offset >= range.end {
+            break;
+        }
+        let end = offset.checked_add(*size).ok_or_else(overflow)?;
+        if end > range.start {
+            let bytes = load(&link.cid)?;
+            match link.cid.codec() {
+                RAW => read_slice(&bytes, offset, range, out),
+                DAG_PB => {
+                    let child = PbNode::from_bytes(bytes)?;
+                    let data = UnixFsData::from_node(&child)?;
+                    read_node(&child, &data, offset, range, load, out)?;
+                }
+                _ => return Err(InvalidUnixFs("unsupported codec").into()),
+            }
+        }
+        offset = end;
+    }
+    Ok(())
+}
+
+/// An entry of a UnixFS directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirEntry {
+    /// The name of the entry.
+    pub name: String,
+    /// The CID of the entry.
+    pub cid: Cid,
+    /// The cumulative size of the entry.
+    pub size: Option<u64>,
+}
+
+/// Lists the entries of a directory, loading the shards of sharded directories with `load`.
+pub fn list_directory<F>(node: &PbNode, mut load: F) -> Result<Vec<DirEntry>>
+where
+    F: FnMut(&Cid) -> Result<Bytes>,
+{
+    let data = UnixFsData::from_node(node)?;
+    let mut entries = Vec::new();
+    match data.data_type {
+        DataType::Directory => {
+            for link in &node.links {
+                entries.push(DirEntry {
+                    name: link.name.clone().unwrap_or_default(),
+                    cid: link.cid,
+                    size: link.size,
+                });
+            }
+        }
+        DataType::HamtShard => list_shard(node, &data, &mut load, &mut entries)?,
+        _ => return Err(InvalidUnixFs("not a directory").into()),
+    }
+    Ok(entries)
+}
+
+fn list_shard<F>(
+    node: &PbNode,
+    data: &UnixFsData,
+    load: &mut F,
+    entries: &mut Vec<DirEntry>,
+) -> Result<()>
+where
+    F: FnMut(&Cid) -> Result<Bytes>,
+{
+    // Link names start with the hex encoded bucket index, links without a name after it point
+    // to child shards.
+    let prefix = match data.fanout {
+        Some(fanout) if fanout > 1 => format!("{:X}", fanout - 1).len(),
+        _ => return Err(InvalidUnixFs("missing fanout").into()),
+    };
+    for link in &node.links {
+        let name = link.name.as_deref().unwrap_or_default();
+        if name.len() < prefix || !name.is_char_boundary(prefix) {
+            return Err(InvalidUnixFs("invalid shard link name").into());
+        }
+        if name.len() == prefix {
+            let child = PbNode::from_bytes(load(&link.cid)?)?;
+            let data = UnixFsData::from_node(&child)?;
+            if data.data_type != DataType::HamtShard {
+                return Err(InvalidUnixFs("shard link doesn't point to a shard").into());
+            }
+            list_shard(&child, &data, load, entries)?;
+        } else {
+            entries.push(DirEntry {
+                name: name[prefix..].to_string(),
+                cid: link.cid,
+                size: link.size,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PbLink;
+    use multihash::{Code, MultihashDigest};
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct Blocks(HashMap<Cid, Bytes>);
+
+    impl Blocks {
+        fn insert(&mut self, codec: u64, bytes: Bytes) -> Cid {
+            let cid = Cid::new_v1(codec, Code::Sha2_256.digest(&bytes));
+            self.0.insert(cid, bytes);
+            cid
+        }
+
+        fn node(&mut self, data: UnixFsData, links: Vec<PbLink>) -> (Cid, PbNode) {
+            let node = PbNode {
+                links,
+                data: Some(data.to_bytes()),
+            };
+            let bytes = node.clone().into_bytes();
+            (self.insert(DAG_PB, Bytes::from(bytes.into_vec())), node)
+        }
+
+        fn load(&self) -> impl FnMut(&Cid) -> Result<Bytes> + '_ {
+            |cid| Ok(self.0[cid].clone())
+        }
+    }
+
+    fn link(cid: Cid, name: Option<&str>) -> PbLink {
+        PbLink {
+            cid,
+            name: name.map(str::to_string),
+            size: None,
+        }
+    }
+
+    #[test]
+    fn test_data_roundtrip() {
+        let mut data = UnixFsData::new(DataType::File);
+        data.filesize = Some(300);
+        data.blocksizes = vec![100, 200];
+        data.mode = Some(0o644);
+        assert_eq!(UnixFsData::from_bytes(&data.to_bytes()).unwrap(), data);
+        // Type 2 (file) followed by packed blocksizes [1, 2].
+        let packed = Bytes::from_static(&[0x08, 0x02, 0x22, 0x02, 0x01, 0x02]);
+        assert_eq!(
+            UnixFsData::from_bytes(&packed).unwrap().blocksizes,
+            vec![1, 2]
+        );
+        assert!(UnixFsData::from_bytes(&Bytes::from_static(&[0x18, 0x01])).is_err());
+    }
+
+    #[test]
+    fn test_read_file() {
+        let mut blocks = Blocks::default();
+        let a = blocks.insert(RAW, Bytes::from_static(b"hello "));
+        let mut inner = UnixFsData::new(DataType::File);
+        inner.data = Some(Bytes::from_static(b"wo"));
+        inner.blocksizes = vec![3];
+        let rld = blocks.insert(RAW, Bytes::from_static(b"rld"));
+        let (b, _) = blocks.node(inner, vec![link(rld, None)]);
+
+        let mut root = UnixFsData::new(DataType::File);
+        root.filesize = Some(11);
+        root.blocksizes = vec![6, 5];
+        let (_, node) = blocks.node(root, vec![link(a, None), link(b, None)]);
+
+        let file = UnixFsFile::new(node).unwrap();
+        assert_eq!(file.size(), 11);
+        assert_eq!(file.read_all(blocks.load()).unwrap(), b"hello world");
+        assert_eq!(file.read_range(4..9, blocks.load()).unwrap(), b"o wor");
+        assert_eq!(file.read_range(9..100, blocks.load()).unwrap(), b"ld");
+
+        // Only the second leaf is loaded.
+        let mut loaded = Vec::new();
+        let mut load = blocks.load();
+        file.read_range(8..10, |cid| {
+            loaded.push(*cid);
+            load(cid)
+        })
+        .unwrap();
+        assert_eq!(loaded, vec![b, rld]);
+    }
+
+    #[test]
+    fn test_hostile_sizes() {
+        let mut blocks = Blocks::default();
+        let mut root = UnixFsData::new(DataType::File);
+        root.data = Some(Bytes::from_static(b"abc"));
+        root.filesize = Some(u64::MAX);
+        let (_, node) = blocks.node(root, vec![]);
+        let file = UnixFsFile::new(node).unwrap();
+        assert_eq!(file.read_all(blocks.load()).unwrap(), b"abc");
+
+        let leaf = blocks.insert(RAW, Bytes::from_static(b"x"));
+        let mut root = UnixFsData::new(DataType::File);
+        root.blocksizes = vec![u64::MAX - 1, 2];
+        let (_, node) = blocks.node(root, vec![link(leaf, None), link(leaf, None)]);
+        let file = UnixFsFile::new(node).unwrap();
+        assert_eq!(file.size(), u64::MAX);
+        let err = file.read_all(blocks.load());
+        assert!(err.unwrap_err().downcast_ref::<InvalidUnixFs>().is_some());
+    }
+
+    #[test]
+    fn test_list_directory() {
+        let mut blocks = Blocks::default();
+        let file = blocks.insert(RAW, Bytes::from_static(b"file"));
+        let (_, dir) = blocks.node(
+            UnixFsData::new(DataType::Directory),
+            vec![link(file, Some("a.txt")), link(file, Some("b.txt"))],
+        );
+        let names: Vec<_> = list_directory(&dir, blocks.load())
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(names, vec!["a.txt", "b.txt"]);
+
+        let mut shard = UnixFsData::new(DataType::HamtShard);
+        shard.fanout = Some(256);
+        let (child, _) = blocks.node(shard.clone(), vec![link(file, Some("1Fc.txt"))]);
+        let (_, root) = blocks.node(
+            shard,
+            vec![link(file, Some("0Aa.txt")), link(child, Some("1F"))],
+        );
+        let names: Vec<_> = list_directory(&root, blocks.load())
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(names, vec!["a.txt", "c.txt"]);
+
+        let (_, file) = blocks.node(UnixFsData::new(DataType::File), vec![]);
+        assert!(list_directory(&file, blocks.load()).is_err());
+        assert!(UnixFsFile::new(root).is_err());
+    }
+}
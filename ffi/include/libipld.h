@@ -0,0 +1,44 @@
+/* C bindings for libipld, link with -lipld_ffi. See ffi/src/lib.rs for the documentation. */
+#ifndef LIBIPLD_H
+#define LIBIPLD_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define IPLD_OK 0
+#define IPLD_ERR_NULL -1
+#define IPLD_ERR_INVALID -2
+#define IPLD_ERR_NOT_FOUND -3
+#define IPLD_ERR_PANIC -4
+
+/* A byte buffer allocated by the library, release it with ipld_buffer_free. */
+typedef struct {
+    uint8_t *data;
+    size_t len;
+} IpldBuffer;
+
+/* A thread safe in-memory block store. */
+typedef struct IpldStore IpldStore;
+
+int32_t ipld_encode(uint64_t codec, const uint8_t *json, size_t len, IpldBuffer *out);
+int32_t ipld_decode(uint64_t codec, const uint8_t *data, size_t len, IpldBuffer *out);
+int32_t ipld_cid(uint64_t codec, uint64_t hash, const uint8_t *data, size_t len, IpldBuffer *out);
+int32_t ipld_cid_to_string(const uint8_t *cid, size_t len, IpldBuffer *out);
+void ipld_buffer_free(IpldBuffer buffer);
+
+IpldStore *ipld_store_new(void);
+void ipld_store_free(IpldStore *store);
+int32_t ipld_store_insert(const IpldStore *store, const uint8_t *cid, size_t cid_len,
+                          const uint8_t *data, size_t len);
+int32_t ipld_store_get(const IpldStore *store, const uint8_t *cid, size_t cid_len,
+                       IpldBuffer *out);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
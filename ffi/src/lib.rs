@@ -0,0 +1,377 @@
+//! C bindings for libipld.
+//!
+//! Values cross the boundary as dag-json: [`ipld_encode`] turns dag-json into any supported
+//! codec and [`ipld_decode`] turns a block back into dag-json. Output is returned in an
+//! [`IpldBuffer`] owned by the caller, which must be released with [`ipld_buffer_free`]. All
+//! functions returning an `i32` return [`IPLD_OK`] or a negative error code. Panics don't unwind
+//! into the caller, they are reported as [`IPLD_ERR_PANIC`]. The C declarations are in
+//! `include/libipld.h`.
+#![deny(missing_docs)]
+#![deny(warnings)]
+
+use libipld::block::Block;
+use libipld::cid::Cid;
+use libipld::codec::Codec;
+use libipld::error::Result;
+use libipld::ipld::Ipld;
+use libipld::json::DagJsonCodec;
+use libipld::multihash::{Code, MultihashDigest};
+use libipld::store::DefaultParams;
+use libipld::IpldCodec;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::panic::AssertUnwindSafe;
+use std::sync::Mutex;
+
+/// Success.
+pub const IPLD_OK: i32 = 0;
+/// A required pointer was null.
+pub const IPLD_ERR_NULL: i32 = -1;
+/// The input couldn't be decoded or encoded, or the codec or hash is unsupported.
+pub const IPLD_ERR_INVALID: i32 = -2;
+/// The block isn't in the store.
+pub const IPLD_ERR_NOT_FOUND: i32 = -3;
+/// The library panicked.
+pub const IPLD_ERR_PANIC: i32 = -4;
+
+/// A byte buffer allocated by the library.
+#[repr(C)]
+pub struct IpldBuffer {
+    /// Pointer to the bytes.
+    pub data: *mut u8,
+    /// Number of bytes.
+    pub len: usize,
+}
+
+impl IpldBuffer {
+    fn new(bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        Self { data, len }
+    }
+}
+
+/// A thread safe in-memory block store.
+pub struct IpldStore {
+    blocks: Mutex<HashMap<Cid, Block<DefaultParams>>>,
+}
+
+/// Runs `f`, catching a panic instead of unwinding across the FFI boundary.
+fn guard<T, F: FnOnce() -> T>(on_panic: T, f: F) -> T {
+    std::panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(on_panic)
+}
+
+/// Borrows `len` bytes at `data`. `data` may only be null if `len` is zero.
+unsafe fn slice<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    if data.is_null() {
+        return (len == 0).then_some(&[]);
+    }
+    Some(std::slice::from_raw_parts(data, len))
+}
+
+/// Writes the result to `out`, returning the status code.
+unsafe fn finish(result: Result<Vec<u8>>, out: *mut IpldBuffer) -> i32 {
+    match result {
+        Ok(bytes) => {
+            *out = IpldBuffer::new(bytes);
+            IPLD_OK
+        }
+        Err(_) => IPLD_ERR_INVALID,
+    }
+}
+
+fn encode(codec: u64, json: &[u8]) -> Result<Vec<u8>> {
+    let ipld: Ipld = DagJsonCodec.decode(json)?;
+    IpldCodec::try_from(codec)?.encode(&ipld)
+}
+
+fn decode(codec: u64, data: &[u8]) -> Result<Vec<u8>> {
+    let ipld: Ipld = IpldCodec::try_from(codec)?.decode(data)?;
+    DagJsonCodec.encode(&ipld)
+}
+
+fn cid(codec: u64, hash: u64, data: &[u8]) -> Result<Cid> {
+    let hash = Code::try_from(hash)?;
+    Ok(Cid::new_v1(codec, hash.digest(data)))
+}
+
+/// Encodes dag-json with the codec.
+///
+/// # Safety
+///
+/// `json` must point to `len` readable bytes and `out` to a writable `IpldBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn ipld_encode(
+    codec: u64,
+    json: *const u8,
+    len: usize,
+    out: *mut IpldBuffer,
+) -> i32 {
+    guard(IPLD_ERR_PANIC, || match slice(json, len) {
+        Some(json) if !out.is_null() => finish(encode(codec, json), out),
+        _ => IPLD_ERR_NULL,
+    })
+}
+
+/// Decodes data encoded with the codec into dag-json.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes and `out` to a writable `IpldBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn ipld_decode(
+    codec: u64,
+    data: *const u8,
+    len: usize,
+    out: *mut IpldBuffer,
+) -> i32 {
+    guard(IPLD_ERR_PANIC, || match slice(data, len) {
+        Some(data) if !out.is_null() => finish(decode(codec, data), out),
+        _ => IPLD_ERR_NULL,
+    })
+}
+
+/// Computes the binary CIDv1 of a block with the codec and multihash code.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes and `out` to a writable `IpldBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn ipld_cid(
+    codec: u64,
+    hash: u64,
+    data: *const u8,
+    len: usize,
+    out: *mut IpldBuffer,
+) -> i32 {
+    guard(IPLD_ERR_PANIC, || match slice(data, len) {
+        Some(data) if !out.is_null() => {
+            finish(cid(codec, hash, data).map(|cid| cid.to_bytes()), out)
+        }
+        _ => IPLD_ERR_NULL,
+    })
+}
+
+/// Formats a binary CID as a string. The string isn't nul terminated.
+///
+/// # Safety
+///
+/// `cid` must point to `len` readable bytes and `out` to a writable `IpldBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn ipld_cid_to_string(
+    cid: *const u8,
+    len: usize,
+    out: *mut IpldBuffer,
+) -> i32 {
+    guard(IPLD_ERR_PANIC, || match slice(cid, len) {
+        Some(cid) if !out.is_null() => finish(
+            Cid::try_from(cid)
+                .map(|cid| cid.to_string().into_bytes())
+                .map_err(Into::into),
+            out,
+        ),
+        _ => IPLD_ERR_NULL,
+    })
+}
+
+/// Releases a buffer returned by the library.
+///
+/// # Safety
+///
+/// `buffer` must have been returned by the library and not been freed before.
+#[no_mangle]
+pub unsafe extern "C" fn ipld_buffer_free(buffer: IpldBuffer) {
+    guard((), || {
+        if !buffer.data.is_null() {
+            drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+                buffer.data,
+                buffer.len,
+            )));
+        }
+    })
+}
+
+/// Creates an empty store. Returns null if the library panicked.
+#[no_mangle]
+pub extern "C" fn ipld_store_new() -> *mut IpldStore {
+    guard(std::ptr::null_mut(), || {
+        Box::into_raw(Box::new(IpldStore {
+            blocks: Mutex::new(HashMap::new()),
+        }))
+    })
+}
+
+/// Releases a store.
+///
+/// # Safety
+///
+/// `store` must have been returned by [`ipld_store_new`] and not been freed before.
+#[no_mangle]
+pub unsafe extern "C" fn ipld_store_free(store: *mut IpldStore) {
+    guard((), || {
+        if !store.is_null() {
+            drop(Box::from_raw(store));
+        }
+    })
+}
+
+/// Inserts a block after verifying that its hash matches the binary CID.
+///
+/// # Safety
+///
+/// `store` must be a live store, `cid` must point to `cid_len` and `data` to `len` readable
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ipld_store_insert(
+    store: *const IpldStore,
+    cid: *const u8,
+    cid_len: usize,
+    data: *const u8,
+    len: usize,
+) -> i32 {
+    guard(IPLD_ERR_PANIC, || {
+        let (store, cid, data) = match (store.as_ref(), slice(cid, cid_len), slice(data, len)) {
+            (Some(store), Some(cid), Some(data)) => (store, cid, data),
+            _ => return IPLD_ERR_NULL,
+        };
+        let block = Cid::try_from(cid)
+            .map_err(Into::into)
+            .and_then(|cid| Block::<DefaultParams>::new(cid, data.to_vec()));
+        match block {
+            Ok(block) => {
+                let mut blocks = store.blocks.lock().unwrap_or_else(|err| err.into_inner());
+                blocks.insert(*block.cid(), block);
+                IPLD_OK
+            }
+            Err(_) => IPLD_ERR_INVALID,
+        }
+    })
+}
+
+/// Copies the data of a block into `out`.
+///
+/// # Safety
+///
+/// `store` must be a live store, `cid` must point to `cid_len` readable bytes and `out` to a
+/// writable `IpldBuffer`.
+#[no_mangle]
+pub unsafe extern "C" fn ipld_store_get(
+    store: *const IpldStore,
+    cid: *const u8,
+    cid_len: usize,
+    out: *mut IpldBuffer,
+) -> i32 {
+    guard(IPLD_ERR_PANIC, || {
+        let (store, cid) = match (store.as_ref(), slice(cid, cid_len)) {
+            (Some(store), Some(cid)) if !out.is_null() => (store, cid),
+            _ => return IPLD_ERR_NULL,
+        };
+        let cid = match Cid::try_from(cid) {
+            Ok(cid) => cid,
+            Err(_) => return IPLD_ERR_INVALID,
+        };
+        let blocks = store.blocks.lock().unwrap_or_else(|err| err.into_inner());
+        match blocks.get(&cid) {
+            Some(block) => {
+                *out = IpldBuffer::new(block.data().to_vec());
+                IPLD_OK
+            }
+            None => IPLD_ERR_NOT_FOUND,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    fn empty() -> IpldBuffer {
+        IpldBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    unsafe fn take(buffer: IpldBuffer) -> Vec<u8> {
+        let bytes = std::slice::from_raw_parts(buffer.data, buffer.len).to_vec();
+        ipld_buffer_free(buffer);
+        bytes
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let json = br#"{"a":[1,"b",{"/":{"bytes":"AQI"}}]}"#;
+        unsafe {
+            let mut cbor = empty();
+            assert_eq!(
+                ipld_encode(0x71, json.as_ptr(), json.len(), &mut cbor),
+                IPLD_OK
+            );
+            let cbor = take(cbor);
+            let mut out = empty();
+            assert_eq!(
+                ipld_decode(0x71, cbor.as_ptr(), cbor.len(), &mut out),
+                IPLD_OK
+            );
+            assert_eq!(take(out), json);
+
+            let mut out = empty();
+
+            assert_eq!(
+                ipld_encode(0x71, b"{".as_ptr(), 1, &mut out),
+                IPLD_ERR_INVALID
+            );
+            assert_eq!(ipld_encode(0x71, ptr::null(), 1, &mut out), IPLD_ERR_NULL);
+        }
+    }
+
+    #[test]
+    fn test_panic() {
+        assert_eq!(guard(IPLD_ERR_PANIC, || panic!("bug")), IPLD_ERR_PANIC);
+        assert_eq!(guard(IPLD_ERR_PANIC, || IPLD_OK), IPLD_OK);
+    }
+
+    #[test]
+    fn test_store() {
+        let data = b"hello";
+        unsafe {
+            let mut cid = empty();
+            assert_eq!(
+                ipld_cid(0x55, 0x12, data.as_ptr(), data.len(), &mut cid),
+                IPLD_OK
+            );
+            let cid = take(cid);
+            let mut text = empty();
+            assert_eq!(
+                ipld_cid_to_string(cid.as_ptr(), cid.len(), &mut text),
+                IPLD_OK
+            );
+            assert_eq!(
+                String::from_utf8(take(text)).unwrap(),
+                "bafkreibm6jg3ux5qumhcn2b3flc3tyu6dmlb4xa7u5bf44yegnrjhc4yeq"
+            );
+
+            let store = ipld_store_new();
+            let mut out = empty();
+            assert_eq!(
+                ipld_store_get(store, cid.as_ptr(), cid.len(), &mut out),
+                IPLD_ERR_NOT_FOUND
+            );
+            assert_eq!(
+                ipld_store_insert(store, cid.as_ptr(), cid.len(), b"bye".as_ptr(), 3),
+                IPLD_ERR_INVALID
+            );
+            assert_eq!(
+                ipld_store_insert(store, cid.as_ptr(), cid.len(), data.as_ptr(), data.len()),
+                IPLD_OK
+            );
+            assert_eq!(
+                ipld_store_get(store, cid.as_ptr(), cid.len(), &mut out),
+                IPLD_OK
+            );
+            assert_eq!(take(out), data);
+            ipld_store_free(store);
+        }
+    }
+}
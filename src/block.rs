@@ -1,14 +1,17 @@
 //! Block validation
 use crate::cid::Cid;
-use crate::codec::{Codec, Decode, Encode, References};
-use crate::error::{BlockTooLarge, InvalidMultihash, Result, UnsupportedMultihash};
+use crate::codec::{Codec, Decode, DecodeRef, Encode, References, Tokens};
+use crate::error::{BlockTooLarge, InvalidMultihash, Result, TypeErrorType, UnsupportedMultihash};
 use crate::ipld::Ipld;
 use crate::multihash::MultihashDigest;
 use crate::store::StoreParams;
+use crate::token::Token;
 use core::borrow::Borrow;
 use core::convert::TryFrom;
 use core::marker::PhantomData;
 use core::ops::Deref;
+use std::io::Cursor;
+use thiserror::Error;
 
 /// Block
 #[derive(Clone)]
@@ -130,6 +133,8 @@ impl<S: StoreParams> Block<S> {
         if data.len() > S::MAX_BLOCK_SIZE {
             return Err(BlockTooLarge(data.len()).into());
         }
+        #[cfg(feature = "telemetry")]
+        crate::telemetry::record_encode(codec.into(), data.len());
         let mh = hcode.digest(&data);
         let cid = Cid::new_v1(codec.into(), mh);
         Ok(Self {
@@ -159,6 +164,117 @@ impl<S: StoreParams> Block<S> {
     /// assert_eq!(ipld, Ipld::String("Hello World!".to_string()));
     /// ```
     pub fn decode<CD: Codec, T: Decode<CD>>(&self) -> Result<T>
+    where
+        S::Codecs: Into<CD>,
+    {
+        self.as_ref_block().decode()
+    }
+
+    /// Returns the decoded ipld.
+    pub fn ipld(&self) -> Result<Ipld>
+    where
+        Ipld: Decode<S::Codecs>,
+    {
+        self.decode::<S::Codecs, Ipld>()
+    }
+
+    /// Returns a structural summary of the block.
+    ///
+    /// The block is streamed through the token decoder of its codec, so codecs with a streaming
+    /// decoder like dag-cbor and dag-json never build the value in memory.
+    pub fn summary(&self) -> Result<BlockSummary>
+    where
+        Ipld: Tokens<S::Codecs>,
+    {
+        let mut builder = SummaryBuilder::default();
+        let codec = S::Codecs::try_from(self.cid.codec())?;
+        Ipld::tokens(codec, &mut Cursor::new(&self.data), &mut |token| {
+            builder.token(token)
+        })?;
+        Ok(BlockSummary {
+            codec: self.cid.codec(),
+            size: self.data.len(),
+            kind: builder.kind.unwrap_or(TypeErrorType::Null),
+            keys: builder.keys,
+            len: builder.len,
+            links: builder.links,
+        })
+    }
+
+    /// Returns the references.
+    pub fn references<E: Extend<Cid>>(&self, set: &mut E) -> Result<()>
+    where
+        Ipld: References<S::Codecs>,
+    {
+        self.as_ref_block().references(set)
+    }
+
+    /// Borrows the block.
+    pub fn as_ref_block(&self) -> BlockRef<'_, S> {
+        BlockRef::new_unchecked(self.cid, &self.data)
+    }
+}
+
+/// A block borrowing its data, e.g. from a memory mapped file or a network buffer.
+///
+/// Offers the same decoding methods as [`Block`] without copying the data.
+pub struct BlockRef<'a, S> {
+    _marker: PhantomData<S>,
+    cid: Cid,
+    data: &'a [u8],
+}
+
+impl<S> Clone for BlockRef<'_, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S> Copy for BlockRef<'_, S> {}
+
+impl<S> core::fmt::Debug for BlockRef<'_, S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("BlockRef")
+            .field("cid", &self.cid)
+            .field("data", &self.data)
+            .finish()
+    }
+}
+
+impl<'a, S: StoreParams> BlockRef<'a, S> {
+    /// Creates a new block. Returns an error if the hash doesn't match
+    /// the data.
+    pub fn new(cid: Cid, data: &'a [u8]) -> Result<Self> {
+        verify_cid::<S::Hashes, 64>(&cid, data)?;
+        Ok(Self::new_unchecked(cid, data))
+    }
+
+    /// Creates a new block without verifying the cid.
+    pub fn new_unchecked(cid: Cid, data: &'a [u8]) -> Self {
+        Self {
+            _marker: PhantomData,
+            cid,
+            data,
+        }
+    }
+
+    /// Returns the cid.
+    pub fn cid(&self) -> &Cid {
+        &self.cid
+    }
+
+    /// Returns the payload.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Copies the data into an owned [`Block`].
+    pub fn to_block(&self) -> Block<S> {
+        Block::new_unchecked(self.cid, self.data.to_vec())
+    }
+
+    /// Decodes a block, see [`Block::decode`].
+    pub fn decode<CD: Codec, T: Decode<CD>>(&self) -> Result<T>
     where
         S::Codecs: Into<CD>,
     {
@@ -166,7 +282,21 @@ impl<S: StoreParams> Block<S> {
             Into::<u64>::into(CD::try_from(self.cid.codec()).unwrap()),
             Into::<u64>::into(S::Codecs::try_from(self.cid.codec()).unwrap()),
         );
-        CD::try_from(self.cid.codec())?.decode(&self.data)
+        #[cfg(feature = "telemetry")]
+        let start = std::time::Instant::now();
+        let res = CD::try_from(self.cid.codec())?.decode(self.data);
+        #[cfg(feature = "telemetry")]
+        crate::telemetry::record_decode(self.cid.codec(), start.elapsed());
+        res
+    }
+
+    /// Decodes a value borrowing from the block data, like an
+    /// [`IpldRef`](crate::ipld_ref::IpldRef).
+    pub fn decode_ref<CD: Codec, T: DecodeRef<'a, CD>>(&self) -> Result<T>
+    where
+        S::Codecs: Into<CD>,
+    {
+        CD::try_from(self.cid.codec())?.decode_ref(self.data)
     }
 
     /// Returns the decoded ipld.
@@ -182,7 +312,87 @@ impl<S: StoreParams> Block<S> {
     where
         Ipld: References<S::Codecs>,
     {
-        S::Codecs::try_from(self.cid.codec())?.references::<Ipld, E>(&self.data, set)
+        S::Codecs::try_from(self.cid.codec())?.references::<Ipld, E>(self.data, set)
+    }
+}
+
+impl<'a, S: StoreParams> From<&'a Block<S>> for BlockRef<'a, S> {
+    fn from(block: &'a Block<S>) -> Self {
+        block.as_ref_block()
+    }
+}
+
+/// A structural summary of a block, see [`Block::summary`].
+#[derive(Clone, Debug)]
+pub struct BlockSummary {
+    /// Codec of the block.
+    pub codec: u64,
+    /// Size of the encoded block in bytes.
+    pub size: usize,
+    /// Kind of the top-level value.
+    pub kind: TypeErrorType,
+    /// Keys of a top-level map, empty for other kinds.
+    pub keys: Vec<String>,
+    /// Number of entries of a top-level list or map.
+    pub len: Option<usize>,
+    /// Number of links anywhere in the block, counting duplicates.
+    pub links: usize,
+}
+
+/// The token stream of a block is malformed, e.g. it ends a list that was never started.
+#[derive(Debug, Error)]
+#[error("Malformed token stream.")]
+pub struct MalformedTokens;
+
+/// Collects a [`BlockSummary`] from the tokens of a block.
+#[derive(Default)]
+struct SummaryBuilder {
+    depth: usize,
+    kind: Option<TypeErrorType>,
+    keys: Vec<String>,
+    len: Option<usize>,
+    links: usize,
+}
+
+impl SummaryBuilder {
+    fn token(&mut self, token: Token) -> Result<()> {
+        let kind = match token {
+            Token::Null => TypeErrorType::Null,
+            Token::Bool(_) => TypeErrorType::Bool,
+            Token::Integer(_) => TypeErrorType::Integer,
+            Token::Float(_) => TypeErrorType::Float,
+            Token::String(_) => TypeErrorType::String,
+            Token::Bytes(_) => TypeErrorType::Bytes,
+            Token::Link(_) => TypeErrorType::Link,
+            Token::ListStart => TypeErrorType::List,
+            Token::MapStart => TypeErrorType::Map,
+            Token::Key(key) => {
+                if self.depth == 1 {
+                    self.keys.push(key);
+                }
+                return Ok(());
+            }
+            Token::ListEnd | Token::MapEnd => {
+                self.depth = self.depth.checked_sub(1).ok_or(MalformedTokens)?;
+                return Ok(());
+            }
+        };
+        match self.depth {
+            0 => {
+                if matches!(kind, TypeErrorType::List | TypeErrorType::Map) {
+                    self.len = Some(0);
+                }
+                self.kind = Some(kind.clone());
+            }
+            1 => *self.len.get_or_insert(0) += 1,
+            _ => {}
+        }
+        match kind {
+            TypeErrorType::Link => self.links += 1,
+            TypeErrorType::List | TypeErrorType::Map => self.depth += 1,
+            _ => {}
+        }
+        Ok(())
     }
 }
 
@@ -193,6 +403,8 @@ mod tests {
     use crate::codec_impl::IpldCodec;
     use crate::ipld;
     use crate::ipld::Ipld;
+    use crate::ipld_ref::IpldRef;
+    use crate::json::DagJsonCodec;
     use crate::multihash::Code;
     use crate::store::DefaultParams;
     use fnv::FnvHashSet;
@@ -230,6 +442,69 @@ mod tests {
         assert!(refs.contains(&b3.cid));
     }
 
+    #[test]
+    fn test_summary() {
+        let leaf = IpldBlock::encode(DagCborCodec, Code::Blake3_256, &ipld!("leaf")).unwrap();
+        let payload = ipld!({
+            "a": &leaf.cid,
+            "b": [&leaf.cid, { "c": &leaf.cid }],
+            "d": 1,
+        });
+        let block = IpldBlock::encode(DagCborCodec, Code::Blake3_256, &payload).unwrap();
+        let summary = block.summary().unwrap();
+        assert_eq!(summary.codec, 0x71);
+        assert_eq!(summary.size, block.data().len());
+        assert_eq!(summary.kind, TypeErrorType::Map);
+        assert_eq!(summary.keys, vec!["a", "b", "d"]);
+        assert_eq!(summary.len, Some(3));
+        assert_eq!(summary.links, 3);
+
+        let block = IpldBlock::encode(DagJsonCodec, Code::Blake3_256, &payload).unwrap();
+        let summary = block.summary().unwrap();
+        assert_eq!(summary.codec, 0x0129);
+        assert_eq!(summary.kind, TypeErrorType::Map);
+        assert_eq!(summary.keys, vec!["a", "b", "d"]);
+        assert_eq!(summary.len, Some(3));
+        assert_eq!(summary.links, 3);
+
+        let list = IpldBlock::encode(DagCborCodec, Code::Blake3_256, &ipld!([[1, 2], {}])).unwrap();
+        let summary = list.summary().unwrap();
+        assert_eq!(summary.kind, TypeErrorType::List);
+        assert_eq!(summary.len, Some(2));
+
+        let summary = leaf.summary().unwrap();
+        assert_eq!(summary.kind, TypeErrorType::String);
+        assert!(summary.keys.is_empty());
+        assert_eq!(summary.len, None);
+        assert_eq!(summary.links, 0);
+
+        let raw = IpldBlock::encode(IpldCodec::Raw, Code::Blake3_256, &ipld!(&b"raw"[..])).unwrap();
+        assert_eq!(raw.summary().unwrap().kind, TypeErrorType::Bytes);
+
+        let mut builder = SummaryBuilder::default();
+        builder.token(Token::ListStart).unwrap();
+        builder.token(Token::ListEnd).unwrap();
+        let err = builder.token(Token::ListEnd).unwrap_err();
+        assert!(err.downcast_ref::<MalformedTokens>().is_some());
+    }
+
+    #[test]
+    fn test_block_ref() {
+        let block = IpldBlock::encode(DagCborCodec, Code::Blake3_256, &ipld!("a")).unwrap();
+        let mut buffer = b"xx".to_vec();
+        buffer.extend(block.data());
+        let data = &buffer[2..];
+
+        let block_ref = BlockRef::<DefaultParams>::new(block.cid, data).unwrap();
+        assert_eq!(block_ref.ipld().unwrap(), ipld!("a"));
+        assert_eq!(
+            block_ref.decode_ref::<DagCborCodec, IpldRef>().unwrap(),
+            IpldRef::String("a")
+        );
+        assert_eq!(block_ref.to_block(), block);
+        assert!(BlockRef::<DefaultParams>::new(block.cid, &buffer).is_err());
+    }
+
     #[test]
     fn test_transmute() {
         let b1 = IpldBlock::encode(DagCborCodec, Code::Blake3_256, &42).unwrap();
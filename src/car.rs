@@ -0,0 +1,476 @@
+//! CAR (content addressable archive) support.
+//!
+//! Implements [CARv1](https://ipld.io/specs/transport/car/carv1/): a varint length prefixed
+//! dag-cbor header listing the roots, followed by varint length prefixed sections each holding a
+//! CID and the block data. The [`v2`] module adds CARv2 and its index.
+use crate::block::Block;
+use crate::cbor::DagCborCodec;
+use crate::cid::Cid;
+use crate::codec::{Codec, References};
+use crate::error::Result;
+use crate::ipld::Ipld;
+use crate::store::StoreParams;
+use std::collections::HashSet;
+use std::io::{Cursor, Read, Write};
+use std::marker::PhantomData;
+use std::sync::mpsc::sync_channel;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+pub mod v2;
+
+/// Space reserved for the CID in a section, on top of the maximum block size.
+const MAX_CID_SIZE: usize = 128;
+
+/// The CAR data is malformed.
+#[derive(Debug, Error)]
+#[error("Invalid CAR: {0}.")]
+pub struct InvalidCar(pub &'static str);
+
+/// Reads an unsigned LEB128 varint. Returns `None` at the end of the stream.
+pub(crate) fn read_varint<R: Read>(r: &mut R) -> Result<Option<u64>> {
+    let mut value = 0u64;
+    for i in 0..10 {
+        let mut byte = [0u8];
+        if r.read(&mut byte)? == 0 {
+            if i == 0 {
+                return Ok(None);
+            }
+            return Err(InvalidCar("truncated varint").into());
+        }
+        let bits = (byte[0] & 0x7f) as u64;
+        if i == 9 && bits > 1 {
+            return Err(InvalidCar("varint overflow").into());
+        }
+        value |= bits << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Err(InvalidCar("varint overflow").into())
+}
+
+/// Writes an unsigned LEB128 varint, returning the number of bytes written.
+pub(crate) fn write_varint<W: Write>(w: &mut W, mut value: u64) -> Result<u64> {
+    let mut buf = [0u8; 10];
+    let mut i = 0;
+    loop {
+        buf[i] = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            break;
+        }
+        buf[i] |= 0x80;
+        i += 1;
+    }
+    w.write_all(&buf[..=i])?;
+    Ok(i as u64 + 1)
+}
+
+/// Reads a varint length prefixed section of at most `max` bytes.
+fn read_section<R: Read>(r: &mut R, max: usize) -> Result<Option<Vec<u8>>> {
+    let len = match read_varint(r)? {
+        Some(len) => len,
+        None => return Ok(None),
+    };
+    if len > max as u64 {
+        return Err(InvalidCar("section too large").into());
+    }
+    let mut buf = Vec::with_capacity(len as usize);
+    r.take(len).read_to_end(&mut buf)?;
+    if buf.len() as u64 != len {
+        return Err(InvalidCar("truncated section").into());
+    }
+    Ok(Some(buf))
+}
+
+/// Reads the header, returning the roots.
+pub(crate) fn read_header<S: StoreParams, R: Read>(r: &mut R) -> Result<Vec<Cid>> {
+    let header = read_section(r, S::MAX_BLOCK_SIZE)?.ok_or(InvalidCar("missing header"))?;
+    let header: Ipld = DagCborCodec.decode(&header)?;
+    if header.get("version").ok() != Some(&Ipld::Integer(1)) {
+        return Err(InvalidCar("unsupported version").into());
+    }
+    match header.get("roots") {
+        Ok(Ipld::List(roots)) => roots
+            .iter()
+            .map(|root| match root {
+                Ipld::Link(cid) => Ok(*cid),
+                _ => Err(InvalidCar("root is not a link").into()),
+            })
+            .collect(),
+        _ => Err(InvalidCar("missing roots").into()),
+    }
+}
+
+/// Writes the header, returning the number of bytes written.
+pub(crate) fn write_header<W: Write>(w: &mut W, roots: &[Cid]) -> Result<u64> {
+    let header = Ipld::Map(
+        [
+            (
+                "roots".to_string(),
+                Ipld::List(roots.iter().copied().map(Ipld::Link).collect()),
+            ),
+            ("version".to_string(), Ipld::Integer(1)),
+        ]
+        .into(),
+    );
+    let header = DagCborCodec.encode(&header)?;
+    let len = write_varint(w, header.len() as u64)?;
+    w.write_all(&header)?;
+    Ok(len + header.len() as u64)
+}
+
+/// Reads a block section without verifying the hash. Returns `None` at the end of the archive.
+fn read_unverified<S: StoreParams, R: Read>(r: &mut R) -> Result<Option<(Cid, Vec<u8>)>> {
+    let section = match read_section(r, S::MAX_BLOCK_SIZE + MAX_CID_SIZE)? {
+        Some(section) => section,
+        None => return Ok(None),
+    };
+    let mut r = Cursor::new(section);
+    let cid = Cid::read_bytes(&mut r)?;
+    let offset = r.position() as usize;
+    let mut data = r.into_inner();
+    data.drain(..offset);
+    Ok(Some((cid, data)))
+}
+
+/// Reads a block section, verifying the hash. Returns `None` at the end of the archive.
+pub(crate) fn read_block<S: StoreParams, R: Read>(r: &mut R) -> Result<Option<Block<S>>> {
+    match read_unverified::<S, R>(r)? {
+        Some((cid, data)) => Ok(Some(Block::new(cid, data)?)),
+        None => Ok(None),
+    }
+}
+
+/// Writes a block section, returning the number of bytes written.
+pub(crate) fn write_block<S: StoreParams, W: Write>(w: &mut W, block: &Block<S>) -> Result<u64> {
+    let cid = block.cid().to_bytes();
+    let len = (cid.len() + block.data().len()) as u64;
+    let prefix = write_varint(w, len)?;
+    w.write_all(&cid)?;
+    w.write_all(block.data())?;
+    Ok(prefix + len)
+}
+
+/// Streams blocks out of a CARv1.
+pub struct CarReader<S, R> {
+    _marker: PhantomData<S>,
+    r: R,
+    roots: Vec<Cid>,
+}
+
+impl<S: StoreParams, R: Read> CarReader<S, R> {
+    /// Reads the header.
+    pub fn new(mut r: R) -> Result<Self> {
+        let roots = read_header::<S, _>(&mut r)?;
+        Ok(Self {
+            _marker: PhantomData,
+            r,
+            roots,
+        })
+    }
+
+    /// Returns the roots listed in the header.
+    pub fn roots(&self) -> &[Cid] {
+        &self.roots
+    }
+
+    /// Reads the next block, verifying its hash. Returns `None` at the end of the archive.
+    pub fn next_block(&mut self) -> Result<Option<Block<S>>> {
+        read_block(&mut self.r)
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+}
+
+impl<S: StoreParams, R: Read> Iterator for CarReader<S, R> {
+    type Item = Result<Block<S>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_block().transpose()
+    }
+}
+
+/// Streams blocks into a CARv1.
+pub struct CarWriter<W> {
+    w: W,
+}
+
+impl<W: Write> CarWriter<W> {
+    /// Writes the header.
+    pub fn new(mut w: W, roots: &[Cid]) -> Result<Self> {
+        write_header(&mut w, roots)?;
+        Ok(Self { w })
+    }
+
+    /// Writes a block.
+    pub fn write<S: StoreParams>(&mut self, block: &Block<S>) -> Result<()> {
+        write_block(&mut self.w, block)?;
+        Ok(())
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.w.flush()?;
+        Ok(self.w)
+    }
+}
+
+/// Exports the dags rooted at `roots` to a CAR, loading blocks with `get`.
+///
+/// Blocks are written in depth-first order, each block once.
+pub fn export<S, W, F>(w: W, roots: &[Cid], mut get: F) -> Result<W>
+where
+    S: StoreParams,
+    W: Write,
+    F: FnMut(&Cid) -> Result<Block<S>>,
+    Ipld: References<S::Codecs>,
+{
+    let mut writer = CarWriter::new(w, roots)?;
+    let mut seen = HashSet::new();
+    let mut stack: Vec<Cid> = roots.iter().rev().copied().collect();
+    while let Some(cid) = stack.pop() {
+        if !seen.insert(cid) {
+            continue;
+        }
+        let block = get(&cid)?;
+        writer.write(&block)?;
+        let mut refs = Vec::new();
+        block.references(&mut refs)?;
+        stack.extend(refs.into_iter().rev().filter(|cid| !seen.contains(cid)));
+    }
+    writer.finish()
+}
+
+/// Imports the blocks of a CAR, passing each verified block to `put`. Returns the roots.
+pub fn import<S, R, F>(r: R, mut put: F) -> Result<Vec<Cid>>
+where
+    S: StoreParams,
+    R: Read,
+    F: FnMut(Block<S>) -> Result<()>,
+{
+    let mut reader = CarReader::<S, R>::new(r)?;
+    let roots = reader.roots().to_vec();
+    while let Some(block) = reader.next_block()? {
+        put(block)?;
+    }
+    Ok(roots)
+}
+
+/// Imports a CAR with a pipeline: one thread reads the archive, a pool of threads verifies the
+/// hashes and the calling thread passes batches of verified blocks to `put`.
+///
+/// The queues between the stages are bounded, so reading pauses when verification or `put` falls
+/// behind. Blocks are passed to `put` in no particular order.
+#[derive(Clone, Copy, Debug)]
+pub struct ParallelImport {
+    threads: usize,
+    batch_size: usize,
+}
+
+impl Default for ParallelImport {
+    fn default() -> Self {
+        Self {
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            batch_size: 64,
+        }
+    }
+}
+
+impl ParallelImport {
+    /// Sets the number of threads verifying hashes. Defaults to the available parallelism.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Sets the number of blocks per batch. Defaults to 64.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Imports the blocks of a CAR, passing batches of verified blocks to `put`. Returns the
+    /// roots.
+    pub fn import<S, R, F>(&self, mut r: R, mut put: F) -> Result<Vec<Cid>>
+    where
+        S: StoreParams,
+        R: Read + Send,
+        F: FnMut(Vec<Block<S>>) -> Result<()>,
+    {
+        let roots = read_header::<S, _>(&mut r)?;
+        let (read_tx, read_rx) = sync_channel::<Vec<(Cid, Vec<u8>)>>(self.threads * 2);
+        let (verified_tx, verified_rx) = sync_channel::<Result<Vec<Block<S>>>>(self.threads * 2);
+        // Shared by the workers. Once they are all gone, the reader can't send anymore and stops.
+        let read_rx = Arc::new(Mutex::new(read_rx));
+        std::thread::scope(|scope| {
+            let reader = scope.spawn(move || -> Result<()> {
+                let mut batch = Vec::with_capacity(self.batch_size);
+                while let Some(section) = read_unverified::<S, _>(&mut r)? {
+                    batch.push(section);
+                    if batch.len() == self.batch_size {
+                        let next = Vec::with_capacity(self.batch_size);
+                        if read_tx.send(std::mem::replace(&mut batch, next)).is_err() {
+                            // Import failed and the workers are gone.
+                            return Ok(());
+                        }
+                    }
+                }
+                if !batch.is_empty() {
+                    read_tx.send(batch).ok();
+                }
+                Ok(())
+            });
+            for _ in 0..self.threads {
+                let read_rx = read_rx.clone();
+                let verified_tx = verified_tx.clone();
+                scope.spawn(move || loop {
+                    let batch = match read_rx.lock().unwrap_or_else(|err| err.into_inner()).recv() {
+                        Ok(batch) => batch,
+                        Err(_) => return,
+                    };
+                    let blocks = batch
+                        .into_iter()
+                        .map(|(cid, data)| Block::new(cid, data))
+                        .collect();
+                    if verified_tx.send(blocks).is_err() {
+                        return;
+                    }
+                });
+            }
+            drop(read_rx);
+            drop(verified_tx);
+
+            // Dropping the receiver on error stops the workers, which in turn stops the reader.
+            let res = verified_rx.into_iter().try_for_each(|blocks| put(blocks?));
+            match reader.join() {
+                Ok(read) => res.and(read),
+                Err(panic) => std::panic::resume_unwind(panic),
+            }
+        })?;
+        Ok(roots)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipld;
+    use crate::multihash::Code;
+    use crate::store::DefaultParams;
+    use std::collections::HashMap;
+
+    type IpldBlock = Block<DefaultParams>;
+
+    fn block(ipld: &Ipld) -> IpldBlock {
+        IpldBlock::encode(DagCborCodec, Code::Blake3_256, ipld).unwrap()
+    }
+
+    #[test]
+    fn test_varint() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut buf = Vec::new();
+            write_varint(&mut buf, value).unwrap();
+            assert_eq!(read_varint(&mut &buf[..]).unwrap(), Some(value));
+        }
+        assert_eq!(read_varint(&mut &[][..]).unwrap(), None);
+        assert!(read_varint(&mut &[0x80][..]).is_err());
+        assert!(read_varint(&mut &[0xff; 10][..]).is_err());
+    }
+
+    #[test]
+    fn test_export_import() {
+        let leaf = block(&ipld!("leaf"));
+        let a = block(&ipld!({ "leaf": leaf.cid() }));
+        let b = block(&ipld!([leaf.cid(), a.cid()]));
+        let root = block(&ipld!({ "a": a.cid(), "b": b.cid() }));
+        let blocks: HashMap<Cid, IpldBlock> = [&leaf, &a, &b, &root]
+            .into_iter()
+            .map(|block| (*block.cid(), block.clone()))
+            .collect();
+
+        let car = export(Vec::new(), &[*root.cid()], |cid| {
+            blocks
+                .get(cid)
+                .cloned()
+                .ok_or_else(|| crate::error::BlockNotFound(*cid).into())
+        })
+        .unwrap();
+
+        let mut imported = Vec::new();
+        let roots = import::<DefaultParams, _, _>(&car[..], |block| {
+            imported.push(block);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(roots, vec![*root.cid()]);
+        assert_eq!(imported, vec![root, a, leaf, b]);
+    }
+
+    #[test]
+    fn test_invalid_block() {
+        let good = block(&ipld!(1));
+        let bad = IpldBlock::new_unchecked(*good.cid(), vec![0x02]);
+        let mut writer = CarWriter::new(Vec::new(), &[*good.cid()]).unwrap();
+        writer.write(&bad).unwrap();
+        let car = writer.finish().unwrap();
+        let mut reader = CarReader::<DefaultParams, _>::new(&car[..]).unwrap();
+        assert_eq!(reader.roots(), &[*good.cid()]);
+        assert!(reader.next_block().is_err());
+
+        assert!(CarReader::<DefaultParams, _>::new(&car[..3]).is_err());
+    }
+
+    #[test]
+    fn test_parallel_import() {
+        let blocks: Vec<IpldBlock> = (0..100).map(|i| block(&ipld!({ "i": i }))).collect();
+        let mut writer = CarWriter::new(Vec::new(), &[*blocks[0].cid()]).unwrap();
+        for block in &blocks {
+            writer.write(block).unwrap();
+        }
+        let car = writer.finish().unwrap();
+
+        let mut imported = HashSet::new();
+        let roots = ParallelImport::default()
+            .threads(4)
+            .batch_size(7)
+            .import::<DefaultParams, _, _>(&car[..], |batch| {
+                assert!(batch.len() <= 7);
+                imported.extend(batch.into_iter().map(|block| *block.cid()));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(roots, vec![*blocks[0].cid()]);
+        assert_eq!(imported, blocks.iter().map(|block| *block.cid()).collect());
+
+        // Stops when `put` fails.
+        let mut calls = 0;
+        let res = ParallelImport::default()
+            .threads(2)
+            .batch_size(1)
+            .import::<DefaultParams, _, _>(&car[..], |_| {
+                calls += 1;
+                Err(InvalidCar("full").into())
+            });
+        assert!(res.is_err());
+        assert_eq!(calls, 1);
+
+        // Fails on a block with a bad hash.
+        let bad = IpldBlock::new_unchecked(*blocks[0].cid(), vec![0x02]);
+        let mut writer = CarWriter::new(Vec::new(), &[]).unwrap();
+        writer.write(&blocks[1]).unwrap();
+        writer.write(&bad).unwrap();
+        let car = writer.finish().unwrap();
+        let res = ParallelImport::default().import::<DefaultParams, _, _>(&car[..], |_| Ok(()));
+        assert!(res.is_err());
+
+        // Fails on a truncated archive.
+        let res = ParallelImport::default()
+            .import::<DefaultParams, _, _>(&car[..car.len() - 1], |_| Ok(()));
+        assert!(res.is_err());
+    }
+}
@@ -0,0 +1,421 @@
+//! CARv2 support.
+//!
+//! A [CARv2](https://ipld.io/specs/transport/car/carv2/) wraps a CARv1 data payload between a
+//! fixed size header and an optional index, which maps multihash digests to the offsets of the
+//! block sections in the payload. The index allows looking up single blocks without reading the
+//! whole archive.
+use super::InvalidCar;
+use super::{read_block, read_header, read_varint, write_block, write_header, write_varint};
+use crate::block::Block;
+use crate::cid::Cid;
+use crate::error::Result;
+use crate::multihash::Multihash;
+use crate::store::StoreParams;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+
+/// The fixed bytes starting every CARv2: a CARv1 header announcing version 2.
+pub const PRAGMA: [u8; 11] = [
+    0x0a, 0xa1, 0x67, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x02,
+];
+
+/// Size of the header following the pragma.
+const HEADER_SIZE: u64 = 40;
+
+/// Multicodec of an index sorted by digest.
+const INDEX_SORTED: u64 = 0x0400;
+
+/// Multicodec of an index sorted by multihash code and digest.
+const MULTIHASH_INDEX_SORTED: u64 = 0x0401;
+
+/// Largest digest size of the supported multihashes.
+const MAX_DIGEST_SIZE: u64 = 64;
+
+fn read_array<R: Read, const N: usize>(r: &mut R) -> Result<[u8; N]> {
+    let mut buf = [0; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Reads the buckets of a `IndexSorted` index, passing every digest and offset to `f`.
+fn read_sorted_index<R: Read, F: FnMut(Vec<u8>, u64) -> Result<()>>(
+    r: &mut R,
+    mut f: F,
+) -> Result<()> {
+    let buckets = i32::from_le_bytes(read_array(r)?);
+    for _ in 0..buckets {
+        let width = u32::from_le_bytes(read_array(r)?) as u64;
+        let len = u64::from_le_bytes(read_array(r)?);
+        // The width is the digest size plus the 8 byte offset.
+        if width <= 8 || width > 8 + MAX_DIGEST_SIZE || len % width != 0 {
+            return Err(InvalidCar("invalid index bucket").into());
+        }
+        for _ in 0..len / width {
+            let mut digest = vec![0; width as usize - 8];
+            r.read_exact(&mut digest)?;
+            let offset = u64::from_le_bytes(read_array(r)?);
+            f(digest, offset)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads blocks from a CARv2, looking them up through the index.
+pub struct CarV2Reader<S, R> {
+    _marker: PhantomData<S>,
+    r: R,
+    roots: Vec<Cid>,
+    /// Stream position of the data payload.
+    data_start: u64,
+    data_size: u64,
+    /// Section offsets by multihash. Blocks with different codecs can share a multihash.
+    index: HashMap<Vec<u8>, Vec<u64>>,
+}
+
+impl<S: StoreParams, R: Read + Seek> CarV2Reader<S, R> {
+    /// Reads the header, the roots and the index, starting at the current position of the
+    /// reader. CARs without an index are indexed by scanning the data payload once.
+    pub fn new(mut r: R) -> Result<Self> {
+        let start = r.stream_position()?;
+        if read_array::<_, 11>(&mut r)? != PRAGMA {
+            return Err(InvalidCar("not a CARv2").into());
+        }
+        let _characteristics: [u8; 16] = read_array(&mut r)?;
+        let data_offset = u64::from_le_bytes(read_array(&mut r)?);
+        let data_size = u64::from_le_bytes(read_array(&mut r)?);
+        let index_offset = u64::from_le_bytes(read_array(&mut r)?);
+
+        let data_start = start
+            .checked_add(data_offset)
+            .ok_or(InvalidCar("invalid data offset"))?;
+        r.seek(SeekFrom::Start(data_start))?;
+        let roots = read_header::<S, _>(&mut r)?;
+
+        let mut reader = Self {
+            _marker: PhantomData,
+            r,
+            roots,
+            data_start,
+            data_size,
+            index: HashMap::new(),
+        };
+        if index_offset == 0 {
+            reader.scan()?;
+        } else {
+            let index_start = start
+                .checked_add(index_offset)
+                .ok_or(InvalidCar("invalid index offset"))?;
+            reader.read_index(index_start)?;
+        }
+        Ok(reader)
+    }
+
+    fn read_index(&mut self, start: u64) -> Result<()> {
+        self.r.seek(SeekFrom::Start(start))?;
+        match read_varint(&mut self.r)? {
+            Some(INDEX_SORTED) => {
+                // This index doesn't record the hash functions, get them from the CIDs.
+                let mut offsets = Vec::new();
+                read_sorted_index(&mut self.r, |_, offset| {
+                    offsets.push(offset);
+                    Ok(())
+                })?;
+                for offset in offsets {
+                    let cid = self.read_cid(offset)?;
+                    self.insert(cid.hash().to_bytes(), offset);
+                }
+                Ok(())
+            }
+            Some(MULTIHASH_INDEX_SORTED) => {
+                let codes = i32::from_le_bytes(read_array(&mut self.r)?);
+                let mut entries = Vec::new();
+                for _ in 0..codes {
+                    let code = u64::from_le_bytes(read_array(&mut self.r)?);
+                    read_sorted_index(&mut self.r, |digest, offset| {
+                        let hash = Multihash::wrap(code, &digest)?;
+                        entries.push((hash.to_bytes(), offset));
+                        Ok(())
+                    })?;
+                }
+                for (hash, offset) in entries {
+                    self.insert(hash, offset);
+                }
+                Ok(())
+            }
+            _ => Err(InvalidCar("unsupported index").into()),
+        }
+    }
+
+    fn scan(&mut self) -> Result<()> {
+        let mut offset = self.r.stream_position()? - self.data_start;
+        while offset < self.data_size {
+            let len = read_varint(&mut self.r)?.ok_or(InvalidCar("truncated data"))?;
+            let start = self.r.stream_position()?;
+            let cid = Cid::read_bytes(&mut self.r)?;
+            self.insert(cid.hash().to_bytes(), offset);
+            let end = start
+                .checked_add(len)
+                .ok_or(InvalidCar("invalid section"))?;
+            self.r.seek(SeekFrom::Start(end))?;
+            offset = end - self.data_start;
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, hash: Vec<u8>, offset: u64) {
+        let offsets = self.index.entry(hash).or_default();
+        if !offsets.contains(&offset) {
+            offsets.push(offset);
+        }
+    }
+
+    /// Reads the CID of the section at `offset` in the data payload.
+    fn read_cid(&mut self, offset: u64) -> Result<Cid> {
+        let start = self
+            .data_start
+            .checked_add(offset)
+            .ok_or(InvalidCar("invalid offset"))?;
+        self.r.seek(SeekFrom::Start(start))?;
+        read_varint(&mut self.r)?.ok_or(InvalidCar("truncated data"))?;
+        Ok(Cid::read_bytes(&mut self.r)?)
+    }
+
+    /// Returns the offset of the section holding the block with this CID.
+    fn find(&mut self, cid: &Cid) -> Result<Option<u64>> {
+        let offsets = match self.index.get(&cid.hash().to_bytes()) {
+            Some(offsets) => offsets.clone(),
+            None => return Ok(None),
+        };
+        for offset in offsets {
+            if self.read_cid(offset)? == *cid {
+                return Ok(Some(offset));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the roots listed in the header.
+    pub fn roots(&self) -> &[Cid] {
+        &self.roots
+    }
+
+    /// Returns true if the archive contains a block with this CID. Only the CIDs of the sections
+    /// with a matching multihash are read.
+    pub fn contains(&mut self, cid: &Cid) -> Result<bool> {
+        Ok(self.find(cid)?.is_some())
+    }
+
+    /// Reads the block with this CID, verifying its hash.
+    pub fn get(&mut self, cid: &Cid) -> Result<Option<Block<S>>> {
+        let offset = match self.find(cid)? {
+            Some(offset) => offset,
+            None => return Ok(None),
+        };
+        self.r.seek(SeekFrom::Start(self.data_start + offset))?;
+        let block = read_block::<S, _>(&mut self.r)?.ok_or(InvalidCar("truncated data"))?;
+        Ok(Some(block))
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+}
+
+/// Writes a CARv2 and its index.
+pub struct CarV2Writer<W> {
+    w: W,
+    start: u64,
+    data_size: u64,
+    /// Section offsets by multihash code and digest.
+    index: BTreeMap<u64, BTreeMap<Vec<u8>, Vec<u64>>>,
+}
+
+impl<W: Write + Seek> CarV2Writer<W> {
+    /// Writes the pragma, a placeholder header and the CARv1 header.
+    pub fn new(mut w: W, roots: &[Cid]) -> Result<Self> {
+        let start = w.stream_position()?;
+        w.write_all(&PRAGMA)?;
+        w.write_all(&[0; HEADER_SIZE as usize])?;
+        let data_size = write_header(&mut w, roots)?;
+        Ok(Self {
+            w,
+            start,
+            data_size,
+            index: BTreeMap::new(),
+        })
+    }
+
+    /// Writes a block and adds it to the index.
+    pub fn write<S: StoreParams>(&mut self, block: &Block<S>) -> Result<()> {
+        let hash = block.cid().hash();
+        self.index
+            .entry(hash.code())
+            .or_default()
+            .entry(hash.digest().to_vec())
+            .or_default()
+            .push(self.data_size);
+        self.data_size += write_block(&mut self.w, block)?;
+        Ok(())
+    }
+
+    /// Writes the index, fills in the header and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        let w = &mut self.w;
+        let data_offset = PRAGMA.len() as u64 + HEADER_SIZE;
+        let index_offset = data_offset + self.data_size;
+
+        write_varint(w, MULTIHASH_INDEX_SORTED)?;
+        w.write_all(&(self.index.len() as i32).to_le_bytes())?;
+        for (code, digests) in &self.index {
+            w.write_all(&code.to_le_bytes())?;
+            let mut buckets: BTreeMap<usize, Vec<(&Vec<u8>, u64)>> = BTreeMap::new();
+            for (digest, offsets) in digests {
+                let bucket = buckets.entry(digest.len()).or_default();
+                bucket.extend(offsets.iter().map(|offset| (digest, *offset)));
+            }
+            w.write_all(&(buckets.len() as i32).to_le_bytes())?;
+            for (len, entries) in buckets {
+                let width = len as u64 + 8;
+                w.write_all(&(width as u32).to_le_bytes())?;
+                w.write_all(&(width * entries.len() as u64).to_le_bytes())?;
+                for (digest, offset) in entries {
+                    w.write_all(digest)?;
+                    w.write_all(&offset.to_le_bytes())?;
+                }
+            }
+        }
+        let end = w.stream_position()?;
+
+        w.seek(SeekFrom::Start(self.start + PRAGMA.len() as u64))?;
+        w.write_all(&[0; 16])?;
+        w.write_all(&data_offset.to_le_bytes())?;
+        w.write_all(&self.data_size.to_le_bytes())?;
+        w.write_all(&index_offset.to_le_bytes())?;
+        w.seek(SeekFrom::Start(end))?;
+        w.flush()?;
+        Ok(self.w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cbor::DagCborCodec;
+    use crate::ipld;
+    use crate::multihash::Code;
+    use crate::store::DefaultParams;
+    use std::io::Cursor;
+
+    type IpldBlock = Block<DefaultParams>;
+
+    fn blocks() -> Vec<IpldBlock> {
+        (0..10)
+            .map(|i| IpldBlock::encode(DagCborCodec, Code::Blake3_256, &ipld!({ "i": i })).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let blocks = blocks();
+        let roots = [*blocks[0].cid()];
+        let mut writer = CarV2Writer::new(Cursor::new(Vec::new()), &roots).unwrap();
+        for block in &blocks {
+            writer.write(block).unwrap();
+        }
+        let car = writer.finish().unwrap().into_inner();
+        assert_eq!(&car[..11], &PRAGMA);
+
+        let mut reader = CarV2Reader::<DefaultParams, _>::new(Cursor::new(&car)).unwrap();
+        assert_eq!(reader.roots(), &roots);
+        for block in blocks.iter().rev() {
+            assert!(reader.contains(block.cid()).unwrap());
+            assert_eq!(reader.get(block.cid()).unwrap().as_ref(), Some(block));
+        }
+        let missing = IpldBlock::encode(DagCborCodec, Code::Blake3_256, &ipld!(0)).unwrap();
+        assert_eq!(reader.get(missing.cid()).unwrap(), None);
+
+        // The data payload is a valid CARv1.
+        let data_offset = u64::from_le_bytes(car[27..35].try_into().unwrap()) as usize;
+        let data_size = u64::from_le_bytes(car[35..43].try_into().unwrap()) as usize;
+        let v1 = super::super::CarReader::<DefaultParams, _>::new(
+            &car[data_offset..data_offset + data_size],
+        )
+        .unwrap();
+        assert_eq!(v1.collect::<Result<Vec<_>>>().unwrap(), blocks);
+    }
+
+    #[test]
+    fn test_without_index() {
+        let blocks = blocks();
+        let mut writer = CarV2Writer::new(Cursor::new(Vec::new()), &[]).unwrap();
+        for block in &blocks {
+            writer.write(block).unwrap();
+        }
+        let mut car = writer.finish().unwrap().into_inner();
+        // Drop the index.
+        let data_size = u64::from_le_bytes(car[35..43].try_into().unwrap()) as usize;
+        car.truncate(51 + data_size);
+        car[43..51].copy_from_slice(&[0; 8]);
+
+        let mut reader = CarV2Reader::<DefaultParams, _>::new(Cursor::new(&car)).unwrap();
+        assert_eq!(
+            reader.get(blocks[5].cid()).unwrap().as_ref(),
+            Some(&blocks[5])
+        );
+    }
+
+    #[test]
+    fn test_shared_multihash() {
+        // The same data as raw and dag-cbor blocks, the multihash is the same.
+        let cbor = &blocks()[0];
+        let raw =
+            IpldBlock::new(Cid::new_v1(0x55, *cbor.cid().hash()), cbor.data().to_vec()).unwrap();
+        let json = Cid::new_v1(0x0129, *cbor.cid().hash());
+        let mut writer = CarV2Writer::new(Cursor::new(Vec::new()), &[]).unwrap();
+        writer.write(cbor).unwrap();
+        writer.write(&raw).unwrap();
+        let car = writer.finish().unwrap().into_inner();
+
+        let mut reader = CarV2Reader::<DefaultParams, _>::new(Cursor::new(&car)).unwrap();
+        for block in [cbor, &raw] {
+            assert!(reader.contains(block.cid()).unwrap());
+            assert_eq!(reader.get(block.cid()).unwrap().as_ref(), Some(block));
+        }
+        assert!(!reader.contains(&json).unwrap());
+        assert_eq!(reader.get(&json).unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid_index_width() {
+        let mut index = Vec::new();
+        index.extend(1i32.to_le_bytes());
+        index.extend(u32::MAX.to_le_bytes());
+        index.extend((u32::MAX as u64).to_le_bytes());
+        let err = read_sorted_index(&mut Cursor::new(&index), |_, _| Ok(())).unwrap_err();
+        assert!(err.downcast_ref::<InvalidCar>().is_some());
+    }
+
+    #[test]
+    fn test_embedded() {
+        let blocks = blocks();
+        let mut w = Cursor::new(b"prefix".to_vec());
+        w.set_position(6);
+        let mut writer = CarV2Writer::new(w, &[*blocks[0].cid()]).unwrap();
+        for block in &blocks {
+            writer.write(block).unwrap();
+        }
+        let car = writer.finish().unwrap().into_inner();
+        assert_eq!(&car[..6], b"prefix");
+
+        let mut r = Cursor::new(&car);
+        r.set_position(6);
+        let mut reader = CarV2Reader::<DefaultParams, _>::new(r).unwrap();
+        assert_eq!(reader.roots(), &[*blocks[0].cid()]);
+        assert_eq!(
+            reader.get(blocks[3].cid()).unwrap().as_ref(),
+            Some(&blocks[3])
+        );
+    }
+}
@@ -2,7 +2,7 @@
 #[cfg(feature = "dag-cbor")]
 use crate::cbor::DagCborCodec;
 use crate::cid::Cid;
-use crate::codec::{Codec, Decode, Encode, References};
+use crate::codec::{Codec, Decode, Encode, References, Tokens};
 use crate::error::{Result, UnsupportedCodec};
 use crate::ipld::Ipld;
 #[cfg(feature = "dag-json")]
@@ -10,6 +10,7 @@ use crate::json::DagJsonCodec;
 #[cfg(feature = "dag-pb")]
 use crate::pb::DagPbCodec;
 use crate::raw::RawCodec;
+use crate::token::Token;
 use core::convert::TryFrom;
 use std::io::{Read, Seek, Write};
 
@@ -162,6 +163,24 @@ impl References<IpldCodec> for Ipld {
     }
 }
 
+impl Tokens<IpldCodec> for Ipld {
+    fn tokens<R: Read + Seek, F: FnMut(Token) -> Result<()>>(
+        c: IpldCodec,
+        r: &mut R,
+        f: &mut F,
+    ) -> Result<()> {
+        match c {
+            IpldCodec::Raw => <Self as Tokens<RawCodec>>::tokens(RawCodec, r, f),
+            #[cfg(feature = "dag-cbor")]
+            IpldCodec::DagCbor => <Self as Tokens<DagCborCodec>>::tokens(DagCborCodec, r, f),
+            #[cfg(feature = "dag-json")]
+            IpldCodec::DagJson => <Self as Tokens<DagJsonCodec>>::tokens(DagJsonCodec, r, f),
+            #[cfg(feature = "dag-pb")]
+            IpldCodec::DagPb => <Self as Tokens<DagPbCodec>>::tokens(DagPbCodec, r, f),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
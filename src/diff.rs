@@ -0,0 +1,245 @@
+//! Structural diff of ipld.
+//!
+//! [`diff`] compares two values and returns the changes by path. [`diff_dag`] does the same
+//! for two dags, following links through a loader closure while skipping subtrees whose links
+//! are equal, so only the blocks that differ are loaded.
+//!
+//! Paths are JSON pointers like in [`crate::patch`], and every change converts to a patch
+//! [`Operation`]. Applying the changes from `a` to `b` to `a` in order returns `b`.
+use crate::cid::Cid;
+use crate::dag::Blocks;
+use crate::error::Result;
+use crate::ipld::Ipld;
+use crate::patch::{escape, Operation};
+
+/// A difference between two values, at the JSON pointer of the value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    /// A map entry or list item that only exists in the new value.
+    Added(String, Ipld),
+    /// A map entry or list item that only exists in the old value.
+    Removed(String, Ipld),
+    /// The value at the path changed from the first to the second value.
+    Modified(String, Ipld, Ipld),
+}
+
+impl Change {
+    /// Returns the path of the change.
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Added(path, _) | Self::Removed(path, _) | Self::Modified(path, _, _) => path,
+        }
+    }
+}
+
+impl From<Change> for Operation {
+    fn from(change: Change) -> Self {
+        match change {
+            Change::Added(path, value) => Self::Add { path, value },
+            Change::Removed(path, _) => Self::Remove { path },
+            Change::Modified(path, _, value) => Self::Replace { path, value },
+        }
+    }
+}
+
+struct Differ<'a> {
+    blocks: Blocks<'a>,
+    /// The JSON pointer of the values being compared.
+    path: String,
+    changes: Vec<Change>,
+}
+
+impl Differ<'_> {
+    fn path(&self, segment: Option<&str>) -> String {
+        let mut path = self.path.clone();
+        if let Some(segment) = segment {
+            path.push('/');
+            path.push_str(&escape(segment));
+        }
+        path
+    }
+
+    fn child(&mut self, segment: &str, a: &Ipld, b: &Ipld) -> Result<()> {
+        let len = self.path.len();
+        self.path = self.path(Some(segment));
+        let res = self.diff(a, b);
+        self.path.truncate(len);
+        res
+    }
+
+    fn diff(&mut self, a: &Ipld, b: &Ipld) -> Result<()> {
+        if a == b {
+            return Ok(());
+        }
+        match (a, b) {
+            (Ipld::Map(a), Ipld::Map(b)) => {
+                for (key, value) in a {
+                    match b.get(key) {
+                        Some(other) => self.child(key, value, other)?,
+                        None => {
+                            let path = self.path(Some(key));
+                            self.changes.push(Change::Removed(path, value.clone()));
+                        }
+                    }
+                }
+                for (key, value) in b {
+                    if !a.contains_key(key) {
+                        let path = self.path(Some(key));
+                        self.changes.push(Change::Added(path, value.clone()));
+                    }
+                }
+            }
+            (Ipld::List(a), Ipld::List(b)) => {
+                for (i, (a, b)) in a.iter().zip(b).enumerate() {
+                    self.child(&i.to_string(), a, b)?;
+                }
+                // Removed from the back, so that the indices of the other items stay the same.
+                for (i, value) in a.iter().enumerate().skip(b.len()).rev() {
+                    let path = self.path(Some(&i.to_string()));
+                    self.changes.push(Change::Removed(path, value.clone()));
+                }
+                for (i, value) in b.iter().enumerate().skip(a.len()) {
+                    let path = self.path(Some(&i.to_string()));
+                    self.changes.push(Change::Added(path, value.clone()));
+                }
+            }
+            (Ipld::Link(a), Ipld::Link(b)) if self.blocks.is_loading() => {
+                if let (Some(a), Some(b)) = (self.blocks.load(a)?, self.blocks.load(b)?) {
+                    self.diff(&a, &b)?;
+                }
+            }
+            _ => {
+                let path = self.path(None);
+                self.changes
+                    .push(Change::Modified(path, a.clone(), b.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the changes from `a` to `b`.
+///
+/// Maps are compared by key and lists by index, so inserting an item at the start of a list shows
+/// up as a modification of every item and an addition at the end. Links are compared by CID.
+pub fn diff(a: &Ipld, b: &Ipld) -> Vec<Change> {
+    let mut differ = Differ {
+        blocks: Blocks::default(),
+        path: String::new(),
+        changes: Vec::new(),
+    };
+    // Can't fail without a loader.
+    differ.diff(a, b).ok();
+    differ.changes
+}
+
+/// Returns the changes from the dag rooted at `a` to the one rooted at `b`, see [`diff`].
+///
+/// When both sides of a comparison are links to different blocks, they are loaded with `load`
+/// and compared instead, so paths run through links like in [`crate::patch::apply_dag`]. Links to
+/// the same block are skipped without loading them.
+pub fn diff_dag<L>(a: &Ipld, b: &Ipld, mut load: L) -> Result<Vec<Change>>
+where
+    L: FnMut(&Cid) -> Result<Ipld>,
+{
+    let mut differ = Differ {
+        blocks: Blocks::load_only(&mut load),
+        path: String::new(),
+        changes: Vec::new(),
+    };
+    differ.diff(a, b)?;
+    Ok(differ.changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipld;
+    use crate::patch;
+    use crate::test_util::Dag;
+
+    #[test]
+    fn test_diff() {
+        let a = ipld!({
+            "same": 1,
+            "changed": { "x": true },
+            "removed": null,
+            "list": [1, 2, 3],
+        });
+        let b = ipld!({
+            "same": 1,
+            "changed": { "x": false },
+            "added": "a",
+            "list": [1, 4],
+        });
+        assert_eq!(
+            diff(&a, &b),
+            vec![
+                Change::Modified("/changed/x".into(), ipld!(true), ipld!(false)),
+                Change::Modified("/list/1".into(), ipld!(2), ipld!(4)),
+                Change::Removed("/list/2".into(), ipld!(3)),
+                Change::Removed("/removed".into(), ipld!(null)),
+                Change::Added("/added".into(), ipld!("a")),
+            ]
+        );
+        assert!(diff(&a, &a).is_empty());
+        assert_eq!(
+            diff(&ipld!(1), &ipld!("1")),
+            vec![Change::Modified("".into(), ipld!(1), ipld!("1"))]
+        );
+    }
+
+    #[test]
+    fn test_diff_patch() {
+        let a = ipld!({
+            "a/b": { "~": 1, "": [1, 2, 3, 4] },
+            "list": [1],
+        });
+        let b = ipld!({
+            "a/b": { "~": 2, "": [1] },
+            "list": [1, 2, 3],
+        });
+        let changes = diff(&a, &b);
+        assert_eq!(changes[0], Change::Removed("/a~1b//3".into(), ipld!(4)));
+        assert!(changes.iter().any(|change| change.path() == "/a~1b/~0"));
+        let ops: Vec<Operation> = changes.into_iter().map(Operation::from).collect();
+        assert_eq!(patch::apply(&a, &ops).unwrap(), b);
+    }
+
+    #[test]
+    fn test_diff_dag() {
+        let dag = Dag::default();
+        let shared = dag.insert(ipld!({ "big": "subtree" }));
+        let old = dag.insert(ipld!({ "n": 1 }));
+        let new = dag.insert(ipld!({ "n": 2 }));
+        let a = ipld!({ "shared": shared, "child": old });
+        let b = ipld!({ "shared": shared, "child": new });
+
+        let mut loaded = Vec::new();
+        let mut load = dag.load();
+        let changes = diff_dag(&a, &b, |cid| {
+            loaded.push(*cid);
+            load(cid)
+        })
+        .unwrap();
+        assert_eq!(
+            changes,
+            vec![Change::Modified("/child/n".into(), ipld!(1), ipld!(2))]
+        );
+        assert_eq!(loaded, vec![old, new]);
+        let ops: Vec<Operation> = changes.into_iter().map(Operation::from).collect();
+        assert_eq!(
+            patch::apply_dag(&a, &ops, dag.load(), dag.store()).unwrap(),
+            b
+        );
+
+        assert_eq!(
+            diff(&a, &b),
+            vec![Change::Modified(
+                "/child".into(),
+                Ipld::Link(old),
+                Ipld::Link(new)
+            )]
+        );
+    }
+}
@@ -3,15 +3,28 @@
 #![deny(warnings)]
 
 pub mod block;
+#[cfg(feature = "dag-cbor")]
+pub mod car;
 pub mod codec_impl;
+pub mod diff;
+pub mod merge;
 pub mod path;
 pub mod prelude;
+pub mod schema;
+pub mod selector;
 pub mod store;
+#[cfg(feature = "telemetry")]
+pub mod telemetry;
+#[cfg(test)]
+mod test_util;
+pub mod transcode;
 
 #[cfg(feature = "dag-cbor")]
 pub use libipld_cbor as cbor;
 #[cfg(all(feature = "dag-cbor", feature = "derive"))]
 pub use libipld_cbor_derive::DagCbor;
+#[cfg(all(feature = "dag-cbor", feature = "dag-json", feature = "derive"))]
+pub use libipld_cbor_derive::DagJson;
 pub use libipld_core::*;
 #[cfg(feature = "dag-json")]
 pub use libipld_json as json;
@@ -26,5 +39,5 @@ pub use error::Result;
 pub use ipld::Ipld;
 pub use link::Link;
 pub use multihash::Multihash;
-pub use path::{DagPath, Path};
+pub use path::{DagPath, InvalidDagPath, Path};
 pub use store::DefaultParams;
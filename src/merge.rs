@@ -0,0 +1,257 @@
+//! Three-way merge of ipld.
+//!
+//! [`merge`] combines the changes two sides made to a common base. Changes to different map
+//! entries or list items merge cleanly, changes to the same value are a [`Conflict`] decided by a
+//! resolver like [`take_left`], [`take_right`] or a custom closure. [`merge_dag`] merges dags,
+//! loading and storing blocks through closures.
+use crate::cid::Cid;
+use crate::dag::Blocks;
+use crate::error::Result;
+use crate::ipld::Ipld;
+use crate::path::Path;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Both sides changed the value at the path in different ways. `None` means the value doesn't
+/// exist on that side.
+#[derive(Clone, Copy, Debug)]
+pub struct Conflict<'a> {
+    /// Path of the value.
+    pub path: &'a Path,
+    /// The common base.
+    pub base: Option<&'a Ipld>,
+    /// The left side.
+    pub left: Option<&'a Ipld>,
+    /// The right side.
+    pub right: Option<&'a Ipld>,
+}
+
+/// Resolves conflicts by taking the left side.
+pub fn take_left(conflict: &Conflict) -> Result<Option<Ipld>> {
+    Ok(conflict.left.cloned())
+}
+
+/// Resolves conflicts by taking the right side.
+pub fn take_right(conflict: &Conflict) -> Result<Option<Ipld>> {
+    Ok(conflict.right.cloned())
+}
+
+struct Merger<'a, R> {
+    resolve: R,
+    blocks: Blocks<'a>,
+    path: Vec<String>,
+}
+
+impl<R> Merger<'_, R>
+where
+    R: FnMut(&Conflict) -> Result<Option<Ipld>>,
+{
+    fn child(
+        &mut self,
+        segment: String,
+        base: Option<&Ipld>,
+        left: Option<&Ipld>,
+        right: Option<&Ipld>,
+    ) -> Result<Option<Ipld>> {
+        self.path.push(segment);
+        let res = self.merge(base, left, right);
+        self.path.pop();
+        res
+    }
+
+    fn merge(
+        &mut self,
+        base: Option<&Ipld>,
+        left: Option<&Ipld>,
+        right: Option<&Ipld>,
+    ) -> Result<Option<Ipld>> {
+        if left == right || right == base {
+            return Ok(left.cloned());
+        }
+        if left == base {
+            return Ok(right.cloned());
+        }
+        match (base, left, right) {
+            (None | Some(Ipld::Map(_)), Some(Ipld::Map(left)), Some(Ipld::Map(right))) => {
+                let empty = BTreeMap::new();
+                let base = match base {
+                    Some(Ipld::Map(base)) => base,
+                    _ => &empty,
+                };
+                let keys: BTreeSet<&String> =
+                    base.keys().chain(left.keys()).chain(right.keys()).collect();
+                let mut merged = BTreeMap::new();
+                for key in keys {
+                    let value =
+                        self.child(key.clone(), base.get(key), left.get(key), right.get(key))?;
+                    if let Some(value) = value {
+                        merged.insert(key.clone(), value);
+                    }
+                }
+                Ok(Some(Ipld::Map(merged)))
+            }
+            (Some(Ipld::List(base)), Some(Ipld::List(left)), Some(Ipld::List(right)))
+                if base.len() == left.len() && left.len() == right.len() =>
+            {
+                let mut merged = Vec::with_capacity(base.len());
+                for (i, ((base, left), right)) in base.iter().zip(left).zip(right).enumerate() {
+                    merged.extend(self.child(
+                        i.to_string(),
+                        Some(base),
+                        Some(left),
+                        Some(right),
+                    )?);
+                }
+                Ok(Some(Ipld::List(merged)))
+            }
+            (None | Some(Ipld::Link(_)), Some(Ipld::Link(left)), Some(Ipld::Link(right)))
+                if self.blocks.is_loading() =>
+            {
+                let base = match base {
+                    Some(Ipld::Link(base)) => self.blocks.load(base)?,
+                    _ => None,
+                };
+                let left = self.blocks.load(left)?;
+                let right = self.blocks.load(right)?;
+                match self.merge(base.as_ref(), left.as_ref(), right.as_ref())? {
+                    Some(merged) => Ok(Some(Ipld::Link(self.blocks.store(merged)?))),
+                    None => Ok(None),
+                }
+            }
+            _ => {
+                let path = Path::from(self.path.clone());
+                (self.resolve)(&Conflict {
+                    path: &path,
+                    base,
+                    left,
+                    right,
+                })
+            }
+        }
+    }
+}
+
+/// Merges the changes from `base` to `left` and from `base` to `right`.
+///
+/// Maps are merged by key. Lists are merged by index if none of the sides changed the length,
+/// otherwise they conflict like any other value changed on both sides. `resolve` returns the value
+/// to use for a conflict, `None` to remove it, or an error to abort the merge. If the root ends up
+/// removed, the result is null.
+pub fn merge<R>(base: &Ipld, left: &Ipld, right: &Ipld, resolve: R) -> Result<Ipld>
+where
+    R: FnMut(&Conflict) -> Result<Option<Ipld>>,
+{
+    let mut merger = Merger {
+        resolve,
+        blocks: Blocks::default(),
+        path: Vec::new(),
+    };
+    let merged = merger.merge(Some(base), Some(left), Some(right))?;
+    Ok(merged.unwrap_or(Ipld::Null))
+}
+
+/// Merges the dags rooted at `base`, `left` and `right`, see [`merge`].
+///
+/// When both sides changed a link, the linked blocks are loaded with `load` and merged, and the
+/// merged block is passed to `store`, which returns its CID. Subtrees only one side changed are
+/// taken as they are, without loading them.
+pub fn merge_dag<R, L, S>(
+    base: &Ipld,
+    left: &Ipld,
+    right: &Ipld,
+    resolve: R,
+    mut load: L,
+    mut store: S,
+) -> Result<Ipld>
+where
+    R: FnMut(&Conflict) -> Result<Option<Ipld>>,
+    L: FnMut(&Cid) -> Result<Ipld>,
+    S: FnMut(Ipld) -> Result<Cid>,
+{
+    let mut merger = Merger {
+        resolve,
+        blocks: Blocks::new(&mut load, &mut store),
+        path: Vec::new(),
+    };
+    let merged = merger.merge(Some(base), Some(left), Some(right))?;
+    Ok(merged.unwrap_or(Ipld::Null))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipld;
+    use crate::test_util::Dag;
+
+    #[test]
+    fn test_merge() {
+        let base = ipld!({ "a": 1, "b": 1, "c": 1, "list": [1, 2] });
+        let left = ipld!({ "a": 2, "b": 1, "list": [3, 2], "l": true });
+        let right = ipld!({ "a": 1, "b": 2, "c": 1, "list": [1, 4], "r": true });
+        let merged = merge(&base, &left, &right, |_| panic!("conflict")).unwrap();
+        assert_eq!(
+            merged,
+            ipld!({ "a": 2, "b": 2, "list": [3, 4], "l": true, "r": true })
+        );
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let base = ipld!({ "a": 1, "b": [1] });
+        let left = ipld!({ "a": 2, "b": [1, 2] });
+        let right = ipld!({ "a": 3, "b": [] });
+        assert_eq!(
+            merge(&base, &left, &right, take_left).unwrap(),
+            ipld!({ "a": 2, "b": [1, 2] })
+        );
+        assert_eq!(
+            merge(&base, &left, &right, take_right).unwrap(),
+            ipld!({ "a": 3, "b": [] })
+        );
+
+        let mut paths = Vec::new();
+        let merged = merge(&base, &left, &right, |conflict| {
+            paths.push(conflict.path.to_string());
+            match (conflict.left, conflict.right) {
+                (Some(Ipld::Integer(l)), Some(Ipld::Integer(r))) => Ok(Some(Ipld::Integer(l + r))),
+                _ => Ok(None),
+            }
+        })
+        .unwrap();
+        assert_eq!(merged, ipld!({ "a": 5 }));
+        assert_eq!(paths, vec!["a", "b"]);
+
+        assert!(
+            merge(&base, &left, &right, |_| Err(crate::error::Error::msg(
+                "conflict"
+            )))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_merge_dag() {
+        let dag = Dag::default();
+        let base_child = dag.insert(ipld!({ "x": 1, "y": 1 }));
+        let left_child = dag.insert(ipld!({ "x": 2, "y": 1 }));
+        let right_child = dag.insert(ipld!({ "x": 1, "y": 2 }));
+        let merged_child = dag.insert(ipld!({ "x": 2, "y": 2 }));
+        let base = ipld!({ "child": base_child });
+        let left = ipld!({ "child": left_child });
+        let right = ipld!({ "child": right_child });
+
+        let mut stored = Vec::new();
+        let merged = merge_dag(&base, &left, &right, take_left, dag.load(), |ipld| {
+            stored.push(ipld.clone());
+            dag.store()(ipld)
+        })
+        .unwrap();
+        assert_eq!(merged, ipld!({ "child": merged_child }));
+        assert_eq!(stored, vec![ipld!({ "x": 2, "y": 2 })]);
+
+        // Without loading, the changed links conflict.
+        assert_eq!(
+            merge(&base, &left, &right, take_right).unwrap(),
+            ipld!({ "child": right_child })
+        );
+    }
+}
@@ -1,5 +1,9 @@
 //! Path
 use crate::cid::Cid;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
 
 /// Represents a path in an ipld dag.
 #[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
@@ -59,17 +63,17 @@ impl ToString for Path {
 
 /// Path in a dag.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
-pub struct DagPath<'a>(&'a Cid, Path);
+pub struct DagPath(Cid, Path);
 
-impl<'a> DagPath<'a> {
+impl DagPath {
     /// Create a new dag path.
-    pub fn new<T: Into<Path>>(cid: &'a Cid, path: T) -> Self {
-        Self(cid, path.into())
+    pub fn new<T: Into<Path>>(cid: &Cid, path: T) -> Self {
+        Self(*cid, path.into())
     }
 
     /// Returns the root of the path.
     pub fn root(&self) -> &Cid {
-        self.0
+        &self.0
     }
 
     /// Returns the ipld path.
@@ -78,12 +82,56 @@ impl<'a> DagPath<'a> {
     }
 }
 
-impl<'a> From<&'a Cid> for DagPath<'a> {
-    fn from(cid: &'a Cid) -> Self {
+impl From<Cid> for DagPath {
+    fn from(cid: Cid) -> Self {
         Self(cid, Default::default())
     }
 }
 
+impl From<&Cid> for DagPath {
+    fn from(cid: &Cid) -> Self {
+        Self::from(*cid)
+    }
+}
+
+/// The string is not a valid dag path.
+#[derive(Debug, Error)]
+#[error("Invalid dag path: {0}.")]
+pub struct InvalidDagPath(pub String);
+
+/// Parses a CID followed by `/` separated segments, like `bafy.../a/b/3`. The path may be prefixed
+/// with `/ipfs/` or `/ipld/`.
+impl FromStr for DagPath {
+    type Err = InvalidDagPath;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s
+            .strip_prefix("/ipfs/")
+            .or_else(|| s.strip_prefix("/ipld/"))
+            .unwrap_or(s);
+        let (root, path) = rest.split_once('/').unwrap_or((rest, ""));
+        if root.is_empty() {
+            return Err(InvalidDagPath(format!("missing root cid in {:?}", s)));
+        }
+        let cid = Cid::try_from(root)
+            .map_err(|err| InvalidDagPath(format!("invalid root cid {:?}: {}", root, err)))?;
+        Ok(Self(cid, Path::from(path)))
+    }
+}
+
+/// Formats the path as the root CID followed by the `/` separated segments. The segments are
+/// written as they are, without escaping or percent-encoding, so the output only parses back to
+/// the same path if no segment is empty or contains a `/`.
+impl fmt::Display for DagPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)?;
+        for segment in self.1.iter() {
+            write!(f, "/{}", segment)?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -117,4 +165,40 @@ mod tests {
     fn test_to_string() {
         assert_eq!(Path::from(vec!["0", "foo", "2"]).to_string(), "0/foo/2");
     }
+
+    #[test]
+    fn test_parse_dag_path() {
+        let cid = "bafyreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku";
+        let root = Cid::try_from(cid).unwrap();
+        let expected = DagPath::new(&root, "a/b/3");
+        for s in [
+            format!("{}/a/b/3", cid),
+            format!("/ipfs/{}/a/b/3", cid),
+            format!("/ipld/{}/a//b/3/", cid),
+        ] {
+            assert_eq!(s.parse::<DagPath>().unwrap(), expected);
+        }
+        assert_eq!(expected.to_string(), format!("{}/a/b/3", cid));
+        assert_eq!(cid.parse::<DagPath>().unwrap(), DagPath::from(&root));
+        assert_eq!(DagPath::from(&root).to_string(), cid);
+
+        // Segments aren't escaped, so a `/` in a segment splits it when parsing.
+        let slash = DagPath::new(&root, vec!["a/b"]);
+        assert_eq!(slash.to_string(), format!("{}/a/b", cid));
+        assert_ne!(slash.to_string().parse::<DagPath>().unwrap(), slash);
+    }
+
+    #[test]
+    fn test_parse_dag_path_errors() {
+        for s in [
+            "",
+            "/",
+            "/ipfs/",
+            "/a/b",
+            "notacid/a",
+            "/ipns/example.com/a",
+        ] {
+            assert!(s.parse::<DagPath>().is_err(), "{:?}", s);
+        }
+    }
 }
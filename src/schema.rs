@@ -0,0 +1,633 @@
+//! Schema inference.
+//!
+//! Samples existing data and infers an IPLD schema describing it. Maps become structs, fields
+//! missing from some samples become `optional`, `null` values make a type `nullable` and values
+//! of different kinds at the same position become a kinded union. The result can be printed in
+//! the schema DSL or as Rust types using `#[derive(DagCbor)]`.
+use crate::block::Block;
+use crate::codec::Decode;
+use crate::error::Result;
+use crate::ipld::Ipld;
+use crate::store::StoreParams;
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+
+/// Maps with more keys than this are inferred as maps rather than structs.
+const MAX_STRUCT_FIELDS: usize = 32;
+
+/// An inferred type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    /// No sample contained a value.
+    Any,
+    /// Null.
+    Null,
+    /// Boolean.
+    Bool,
+    /// Integer.
+    Int,
+    /// Float. Integers and floats at the same position are widened to floats.
+    Float,
+    /// String.
+    String,
+    /// Bytes.
+    Bytes,
+    /// Link.
+    Link,
+    /// A list with elements of the given type.
+    List(Box<Type>),
+    /// A map from strings to values of the given type.
+    Map(Box<Type>),
+    /// A struct.
+    Struct(BTreeMap<String, Field>),
+    /// A value of the given type or null.
+    Nullable(Box<Type>),
+    /// A kinded union, at most one member per kind.
+    Union(Vec<Type>),
+}
+
+/// An inferred struct field.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    /// The type of the field.
+    pub ty: Type,
+    /// Whether the field was missing from some samples.
+    pub optional: bool,
+}
+
+/// The kind of a type, used to tell union members apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Any,
+    Null,
+    Bool,
+    Number,
+    String,
+    Bytes,
+    Link,
+    List,
+    Map,
+}
+
+impl Type {
+    /// Infers the type of a single value.
+    pub fn of(ipld: &Ipld) -> Self {
+        match ipld {
+            Ipld::Null => Self::Null,
+            Ipld::Bool(_) => Self::Bool,
+            Ipld::Integer(_) => Self::Int,
+            Ipld::Float(_) => Self::Float,
+            Ipld::String(_) => Self::String,
+            Ipld::Bytes(_) => Self::Bytes,
+            Ipld::Link(_) => Self::Link,
+            Ipld::List(list) => Self::List(Box::new(
+                list.iter().map(Self::of).fold(Self::Any, Self::merge),
+            )),
+            Ipld::Map(map) => {
+                if map.len() > MAX_STRUCT_FIELDS || !map.keys().all(|key| is_field_name(key)) {
+                    Self::Map(Box::new(
+                        map.values().map(Self::of).fold(Self::Any, Self::merge),
+                    ))
+                } else {
+                    Self::Struct(
+                        map.iter()
+                            .map(|(key, value)| {
+                                let field = Field {
+                                    ty: Self::of(value),
+                                    optional: false,
+                                };
+                                (key.clone(), field)
+                            })
+                            .collect(),
+                    )
+                }
+            }
+        }
+    }
+
+    /// Merges two inferred types into one describing both.
+    pub fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Any, ty) | (ty, Self::Any) => ty,
+            (Self::Null, Self::Null) => Self::Null,
+            (Self::Null, Self::Nullable(ty)) | (Self::Nullable(ty), Self::Null) => {
+                Self::Nullable(ty)
+            }
+            (Self::Null, ty) | (ty, Self::Null) => Self::Nullable(Box::new(ty)),
+            (Self::Nullable(a), Self::Nullable(b)) => Self::Nullable(Box::new(a.merge(*b))),
+            (Self::Nullable(a), b) | (b, Self::Nullable(a)) => Self::Nullable(Box::new(a.merge(b))),
+            (Self::Union(members), ty) | (ty, Self::Union(members)) => {
+                members.into_iter().fold(ty, Self::merge_member)
+            }
+            (a, b) if a.kind() == b.kind() => a.merge_same_kind(b),
+            (a, b) => Self::Union(vec![a, b]),
+        }
+    }
+
+    /// Adds a member to a union, merging it with the member of the same kind.
+    fn merge_member(self, member: Self) -> Self {
+        let mut members = match self {
+            Self::Union(members) => members,
+            Self::Any => return member,
+            ty => vec![ty],
+        };
+        match members.iter().position(|m| m.kind() == member.kind()) {
+            Some(i) => {
+                let existing = std::mem::replace(&mut members[i], Self::Any);
+                members[i] = existing.merge_same_kind(member);
+            }
+            None => members.push(member),
+        }
+        Self::Union(members)
+    }
+
+    fn merge_same_kind(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Int, Self::Int) => Self::Int,
+            (Self::Int | Self::Float, Self::Int | Self::Float) => Self::Float,
+            (Self::List(a), Self::List(b)) => Self::List(Box::new(a.merge(*b))),
+            (Self::Struct(mut a), Self::Struct(mut b)) => {
+                for (key, field) in a.iter_mut() {
+                    match b.remove(key) {
+                        Some(other) => {
+                            let ty = std::mem::replace(&mut field.ty, Self::Any);
+                            field.ty = ty.merge(other.ty);
+                            field.optional |= other.optional;
+                        }
+                        None => field.optional = true,
+                    }
+                }
+                for (key, mut field) in b {
+                    field.optional = true;
+                    a.insert(key, field);
+                }
+                if a.len() > MAX_STRUCT_FIELDS {
+                    Self::Map(Box::new(
+                        a.into_values().map(|f| f.ty).fold(Self::Any, Self::merge),
+                    ))
+                } else {
+                    Self::Struct(a)
+                }
+            }
+            (Self::Map(a), Self::Map(b)) => Self::Map(Box::new(a.merge(*b))),
+            (Self::Map(value), Self::Struct(fields)) | (Self::Struct(fields), Self::Map(value)) => {
+                Self::Map(Box::new(
+                    fields.into_values().map(|f| f.ty).fold(*value, Self::merge),
+                ))
+            }
+            (a, _) => a,
+        }
+    }
+
+    fn kind(&self) -> Kind {
+        match self {
+            Self::Any => Kind::Any,
+            Self::Null => Kind::Null,
+            Self::Bool => Kind::Bool,
+            Self::Int | Self::Float => Kind::Number,
+            Self::String => Kind::String,
+            Self::Bytes => Kind::Bytes,
+            Self::Link => Kind::Link,
+            Self::List(_) => Kind::List,
+            Self::Map(_) | Self::Struct(_) => Kind::Map,
+            Self::Nullable(ty) => ty.kind(),
+            Self::Union(_) => Kind::Any,
+        }
+    }
+}
+
+/// Returns true if the map key looks like a struct field rather than data.
+fn is_field_name(key: &str) -> bool {
+    let mut chars = key.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Collects samples and infers their schema.
+#[derive(Clone, Debug)]
+pub struct SchemaInference {
+    root: Type,
+    samples: usize,
+}
+
+impl Default for SchemaInference {
+    fn default() -> Self {
+        Self {
+            root: Type::Any,
+            samples: 0,
+        }
+    }
+}
+
+impl SchemaInference {
+    /// Creates a new schema inference.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a sample.
+    pub fn add(&mut self, ipld: &Ipld) {
+        let root = std::mem::replace(&mut self.root, Type::Any);
+        self.root = root.merge(Type::of(ipld));
+        self.samples += 1;
+    }
+
+    /// Decodes a block and adds it as a sample.
+    pub fn add_block<S: StoreParams>(&mut self, block: &Block<S>) -> Result<()>
+    where
+        Ipld: Decode<S::Codecs>,
+    {
+        self.add(&block.ipld()?);
+        Ok(())
+    }
+
+    /// Returns the number of samples added.
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Returns the inferred type of the samples.
+    pub fn root(&self) -> &Type {
+        &self.root
+    }
+
+    /// Returns the inferred schema, naming the root type `name`.
+    pub fn schema(&self, name: &str) -> Schema {
+        let mut schema = Schema::default();
+        schema.name(&self.root, name);
+        schema
+    }
+}
+
+/// A reference to a type from a field, list, map or union.
+#[derive(Clone, Debug, PartialEq)]
+enum TypeRef {
+    Any,
+    Bool,
+    Int,
+    Float,
+    String,
+    Bytes,
+    Link,
+    List(Box<TypeRef>),
+    Map(Box<TypeRef>),
+    Nullable(Box<TypeRef>),
+    Named(String),
+}
+
+impl TypeRef {
+    fn from_type(ty: &Type, schema: &mut Schema, hint: &str) -> Self {
+        match ty {
+            Type::Any | Type::Null => Self::Any,
+            Type::Bool => Self::Bool,
+            Type::Int => Self::Int,
+            Type::Float => Self::Float,
+            Type::String => Self::String,
+            Type::Bytes => Self::Bytes,
+            Type::Link => Self::Link,
+            Type::List(ty) => Self::List(Box::new(Self::from_type(
+                ty,
+                schema,
+                &format!("{}Element", hint),
+            ))),
+            Type::Map(ty) => Self::Map(Box::new(Self::from_type(
+                ty,
+                schema,
+                &format!("{}Value", hint),
+            ))),
+            Type::Nullable(ty) => Self::Nullable(Box::new(Self::from_type(ty, schema, hint))),
+            Type::Struct(_) | Type::Union(_) => Self::Named(schema.name(ty, hint)),
+        }
+    }
+
+    fn dsl(&self) -> String {
+        match self {
+            Self::Any => "Any".into(),
+            Self::Bool => "Bool".into(),
+            Self::Int => "Int".into(),
+            Self::Float => "Float".into(),
+            Self::String => "String".into(),
+            Self::Bytes => "Bytes".into(),
+            Self::Link => "Link".into(),
+            Self::List(ty) => format!("[{}]", ty.dsl()),
+            Self::Map(ty) => format!("{{String:{}}}", ty.dsl()),
+            Self::Nullable(ty) => format!("nullable {}", ty.dsl()),
+            Self::Named(name) => name.clone(),
+        }
+    }
+
+    fn rust(&self) -> String {
+        match self {
+            Self::Any => "Ipld".into(),
+            Self::Bool => "bool".into(),
+            Self::Int => "i64".into(),
+            Self::Float => "f64".into(),
+            Self::String => "String".into(),
+            Self::Bytes => "Box<[u8]>".into(),
+            Self::Link => "Cid".into(),
+            Self::List(ty) => format!("Vec<{}>", ty.rust()),
+            Self::Map(ty) => format!("BTreeMap<String, {}>", ty.rust()),
+            Self::Nullable(ty) => format!("Option<{}>", ty.rust()),
+            Self::Named(name) => name.clone(),
+        }
+    }
+
+    /// The kind keyword used in kinded union representations.
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Any | Self::Nullable(_) => "any",
+            Self::Bool => "bool",
+            Self::Int => "int",
+            Self::Float => "float",
+            Self::String => "string",
+            Self::Bytes => "bytes",
+            Self::Link => "link",
+            Self::List(_) => "list",
+            Self::Map(_) => "map",
+            Self::Named(_) => "map",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TypeBody {
+    Alias(TypeRef),
+    Struct(Vec<(String, TypeRef, bool)>),
+    Union(Vec<(String, TypeRef)>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct TypeDef {
+    name: String,
+    body: TypeBody,
+}
+
+/// An inferred schema.
+///
+/// `Display` renders the schema DSL, [`Schema::to_rust`] renders Rust types.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Schema {
+    types: Vec<TypeDef>,
+}
+
+impl Schema {
+    /// Defines a named type for `ty` and returns its (unique) name.
+    fn name(&mut self, ty: &Type, hint: &str) -> String {
+        let name = self.unique_name(hint);
+        // Reserve the name before recursing, so nested types get a different one.
+        let index = self.types.len();
+        self.types.push(TypeDef {
+            name: name.clone(),
+            body: TypeBody::Alias(TypeRef::Any),
+        });
+        let body = match ty {
+            Type::Struct(fields) => TypeBody::Struct(
+                fields
+                    .iter()
+                    .map(|(key, field)| {
+                        let ty = TypeRef::from_type(&field.ty, self, &pascal_case(key));
+                        (key.clone(), ty, field.optional)
+                    })
+                    .collect(),
+            ),
+            Type::Union(members) => TypeBody::Union(
+                members
+                    .iter()
+                    .map(|member| {
+                        let ty = match member {
+                            Type::List(_) | Type::Map(_) => {
+                                let hint = format!("{}{}", name, kind_name(member));
+                                let ty_ref = TypeRef::from_type(member, self, &hint);
+                                let alias = self.unique_name(&hint);
+                                self.types.push(TypeDef {
+                                    name: alias.clone(),
+                                    body: TypeBody::Alias(ty_ref),
+                                });
+                                TypeRef::Named(alias)
+                            }
+                            member => TypeRef::from_type(
+                                member,
+                                self,
+                                &format!("{}{}", name, kind_name(member)),
+                            ),
+                        };
+                        (kind_name(member).to_string(), ty)
+                    })
+                    .collect(),
+            ),
+            ty => TypeBody::Alias(TypeRef::from_type(ty, self, &name)),
+        };
+        self.types[index].body = body;
+        name
+    }
+
+    fn unique_name(&self, hint: &str) -> String {
+        let mut name = hint.to_string();
+        let mut i = 2;
+        while self.types.iter().any(|def| def.name == name) {
+            name = format!("{}{}", hint, i);
+            i += 1;
+        }
+        name
+    }
+
+    /// Renders the schema as Rust type definitions using `#[derive(DagCbor)]`.
+    pub fn to_rust(&self) -> String {
+        let mut out = String::new();
+        for (i, def) in self.types.iter().enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            // Writing to a `String` can't fail.
+            let _ = match &def.body {
+                TypeBody::Alias(ty) => writeln!(out, "pub type {} = {};", def.name, ty.rust()),
+                TypeBody::Struct(fields) => {
+                    let _ = writeln!(out, "#[derive(Clone, Debug, DagCbor, PartialEq)]");
+                    let _ = writeln!(out, "pub struct {} {{", def.name);
+                    for (key, ty, optional) in fields {
+                        let ident = rust_ident(key);
+                        match (ident != *key, *optional) {
+                            (true, true) => {
+                                let _ = writeln!(
+                                    out,
+                                    "    #[ipld(rename = {:?}, default = None)]",
+                                    key
+                                );
+                            }
+                            (true, false) => {
+                                let _ = writeln!(out, "    #[ipld(rename = {:?})]", key);
+                            }
+                            (false, true) => {
+                                let _ = writeln!(out, "    #[ipld(default = None)]");
+                            }
+                            (false, false) => {}
+                        }
+                        let ty = match (optional, ty) {
+                            (true, TypeRef::Nullable(_)) | (false, _) => ty.rust(),
+                            (true, ty) => format!("Option<{}>", ty.rust()),
+                        };
+                        let _ = writeln!(out, "    pub {}: {},", ident, ty);
+                    }
+                    writeln!(out, "}}")
+                }
+                TypeBody::Union(members) => {
+                    let _ = writeln!(out, "#[derive(Clone, Debug, DagCbor, PartialEq)]");
+                    let _ = writeln!(out, "#[ipld(repr = \"kinded\")]");
+                    let _ = writeln!(out, "pub enum {} {{", def.name);
+                    for (kind, ty) in members {
+                        let _ = writeln!(out, "    #[ipld(repr = \"value\")]");
+                        let _ = writeln!(out, "    {}({}),", kind, ty.rust());
+                    }
+                    writeln!(out, "}}")
+                }
+            };
+        }
+        out
+    }
+}
+
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, def) in self.types.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            match &def.body {
+                TypeBody::Alias(ty) => writeln!(f, "type {} {}", def.name, ty.dsl())?,
+                TypeBody::Struct(fields) => {
+                    writeln!(f, "type {} struct {{", def.name)?;
+                    for (key, ty, optional) in fields {
+                        let optional = if *optional { "optional " } else { "" };
+                        writeln!(f, "  {} {}{}", key, optional, ty.dsl())?;
+                    }
+                    writeln!(f, "}}")?;
+                }
+                TypeBody::Union(members) => {
+                    writeln!(f, "type {} union {{", def.name)?;
+                    for (_, ty) in members {
+                        writeln!(f, "  | {} {}", ty.dsl(), ty.kind())?;
+                    }
+                    writeln!(f, "}} representation kinded")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn kind_name(ty: &Type) -> &'static str {
+    match ty {
+        Type::Any | Type::Null | Type::Nullable(_) | Type::Union(_) => "Any",
+        Type::Bool => "Bool",
+        Type::Int => "Int",
+        Type::Float => "Float",
+        Type::String => "String",
+        Type::Bytes => "Bytes",
+        Type::Link => "Link",
+        Type::List(_) => "List",
+        Type::Map(_) | Type::Struct(_) => "Map",
+    }
+}
+
+fn pascal_case(key: &str) -> String {
+    key.split(['_', '-'])
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            let mut chars = s.chars();
+            chars
+                .next()
+                .map(|c| c.to_ascii_uppercase())
+                .into_iter()
+                .chain(chars)
+                .collect::<String>()
+        })
+        .collect()
+}
+
+fn rust_ident(key: &str) -> String {
+    let mut ident = String::with_capacity(key.len());
+    for (i, c) in key.chars().enumerate() {
+        if c == '-' {
+            ident.push('_');
+        } else if c.is_ascii_uppercase() {
+            if i > 0 {
+                ident.push('_');
+            }
+            ident.push(c.to_ascii_lowercase());
+        } else {
+            ident.push(c);
+        }
+    }
+    const KEYWORDS: &[&str] = &[
+        "as", "async", "await", "box", "break", "const", "continue", "crate", "dyn", "else",
+        "enum", "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod",
+        "move", "mut", "pub", "ref", "return", "self", "static", "struct", "super", "trait",
+        "true", "try", "type", "unsafe", "use", "where", "while", "yield",
+    ];
+    if KEYWORDS.contains(&ident.as_str()) {
+        ident.push('_');
+    }
+    ident
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipld;
+
+    #[test]
+    fn test_infer_struct() {
+        let mut inference = SchemaInference::new();
+        inference.add(&ipld!({ "name": "a", "age": 1, "tags": ["x"] }));
+        inference.add(&ipld!({ "name": "b", "age": 2.5, "tags": [], "nick": null }));
+        inference.add(&ipld!({ "name": "c", "age": 3, "tags": ["y"], "nick": "c" }));
+        assert_eq!(inference.samples(), 3);
+        assert_eq!(
+            inference.schema("Person").to_string(),
+            "type Person struct {\n  age Float\n  name String\n  nick optional nullable String\n  tags [String]\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_infer_nested() {
+        let mut inference = SchemaInference::new();
+        inference.add(&ipld!({ "friend": { "name": "a" }, "value": 1 }));
+        inference.add(&ipld!({ "friend": { "name": "b" }, "value": "one" }));
+        let schema = inference.schema("Root");
+        assert_eq!(
+            schema.to_string(),
+            "type Root struct {\n  friend Friend\n  value Value\n}\n\n\
+             type Friend struct {\n  name String\n}\n\n\
+             type Value union {\n  | Int int\n  | String string\n} representation kinded\n"
+        );
+        let rust = schema.to_rust();
+        assert!(
+            rust.contains("pub struct Root {\n    pub friend: Friend,\n    pub value: Value,\n}")
+        );
+        assert!(rust.contains("#[ipld(repr = \"kinded\")]\npub enum Value {"));
+        assert!(rust.contains("    Int(i64),\n"));
+    }
+
+    #[test]
+    fn test_infer_map() {
+        let mut inference = SchemaInference::new();
+        inference.add(&ipld!({ "bafy1": 1, "2": 2 }));
+        assert_eq!(inference.root(), &Type::Map(Box::new(Type::Int)),);
+        assert_eq!(
+            inference.schema("Counts").to_string(),
+            "type Counts {String:Int}\n"
+        );
+    }
+
+    #[test]
+    fn test_rust_fields() {
+        let mut inference = SchemaInference::new();
+        inference.add(&ipld!({ "camelCase": true, "type": "t" }));
+        inference.add(&ipld!({ "type": "u" }));
+        let rust = inference.schema("Root").to_rust();
+        assert!(rust.contains(
+            "    #[ipld(rename = \"camelCase\", default = None)]\n    pub camel_case: Option<bool>,\n"
+        ));
+        assert!(rust.contains("    #[ipld(rename = \"type\")]\n    pub type_: String,\n"));
+    }
+}
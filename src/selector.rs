@@ -0,0 +1,972 @@
+//! IPLD selectors.
+//!
+//! A [`Selector`] describes which parts of a dag to visit, see the
+//! [selector spec](https://ipld.io/specs/selectors/). Selectors are data themselves and are
+//! converted from and to [`Ipld`], so they can be decoded with any codec, or built with the
+//! builder methods like [`Selector::fields`]. [`walk`] executes a selector, following links
+//! through a loader closure. [`walk_from`] and [`select_page`] continue a walk from a
+//! [`ResumeToken`], to serve the results in pages.
+use crate::cid::Cid;
+use crate::error::Result;
+use crate::ipld::Ipld;
+use crate::path::Path;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::ControlFlow;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// The selector is malformed or uses an unsupported feature.
+#[derive(Debug, Error)]
+#[error("Invalid selector: {0}.")]
+pub struct InvalidSelector(pub String);
+
+fn invalid<T>(msg: &str) -> Result<T> {
+    Err(InvalidSelector(msg.to_string()).into())
+}
+
+/// How deep an [`Selector::ExploreRecursive`] recurses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecursionLimit {
+    /// No limit.
+    None,
+    /// Recurse at most this many times.
+    Depth(u64),
+}
+
+/// An IPLD selector.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Selector {
+    /// Matches the current node.
+    Matcher,
+    /// Explores all list items or map values.
+    ExploreAll {
+        /// Selector applied to the children.
+        next: Box<Selector>,
+    },
+    /// Explores the named map fields, or list items if the names are indices.
+    ExploreFields {
+        /// Selectors applied to the fields.
+        fields: BTreeMap<String, Selector>,
+    },
+    /// Explores a list item.
+    ExploreIndex {
+        /// The index of the item.
+        index: usize,
+        /// Selector applied to the item.
+        next: Box<Selector>,
+    },
+    /// Explores a range of list items.
+    ExploreRange {
+        /// The first index, inclusive.
+        start: usize,
+        /// The last index, exclusive.
+        end: usize,
+        /// Selector applied to the items.
+        next: Box<Selector>,
+    },
+    /// Applies `sequence` repeatedly, every [`Selector::ExploreRecursiveEdge`] in it starting over.
+    ExploreRecursive {
+        /// The recursion limit.
+        limit: RecursionLimit,
+        /// The selector to repeat.
+        sequence: Box<Selector>,
+    },
+    /// Marks where an [`Selector::ExploreRecursive`] repeats.
+    ExploreRecursiveEdge,
+    /// Applies all selectors.
+    ExploreUnion(Vec<Selector>),
+}
+
+/// Builders for the common selectors.
+///
+/// The explore builders match the children they explore. [`Selector::then`] continues from
+/// there and [`Selector::recursive`] repeats the selector, e.g.
+/// `Selector::fields(["a", "b"]).recursive(3)` matches the root and everything reachable through
+/// up to three levels of `a` and `b` fields.
+impl Selector {
+    /// Selects the whole dag: every node, following all links.
+    pub fn explore_all_recursively() -> Self {
+        Self::all().recursive_unlimited()
+    }
+
+    /// Matches the current node.
+    pub fn matcher() -> Self {
+        Self::Matcher
+    }
+
+    /// Matches all list items or map values.
+    pub fn all() -> Self {
+        Self::ExploreAll {
+            next: Box::new(Self::Matcher),
+        }
+    }
+
+    /// Matches the named map fields.
+    pub fn fields<I, S>(fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self::ExploreFields {
+            fields: fields
+                .into_iter()
+                .map(|field| (field.into(), Self::Matcher))
+                .collect(),
+        }
+    }
+
+    /// Matches a list item.
+    pub fn index(index: usize) -> Self {
+        Self::ExploreIndex {
+            index,
+            next: Box::new(Self::Matcher),
+        }
+    }
+
+    /// Matches the list items from `start` to `end`, exclusive.
+    pub fn range(start: usize, end: usize) -> Self {
+        Self::ExploreRange {
+            start,
+            end,
+            next: Box::new(Self::Matcher),
+        }
+    }
+
+    /// Applies all the selectors.
+    pub fn union<I: IntoIterator<Item = Selector>>(selectors: I) -> Self {
+        Self::ExploreUnion(selectors.into_iter().collect())
+    }
+
+    /// Applies `next` where this selector matches, instead of matching.
+    pub fn then(self, next: Selector) -> Self {
+        self.replace_matchers(&next)
+    }
+
+    /// Repeats this selector up to `depth` times, matching the current node and every node this
+    /// selector matches along the way.
+    pub fn recursive(self, depth: u64) -> Self {
+        self.recurse(RecursionLimit::Depth(depth))
+    }
+
+    /// Like [`Selector::recursive`], without a limit.
+    pub fn recursive_unlimited(self) -> Self {
+        self.recurse(RecursionLimit::None)
+    }
+
+    fn recurse(self, limit: RecursionLimit) -> Self {
+        Self::ExploreRecursive {
+            limit,
+            sequence: Box::new(Self::ExploreUnion(vec![
+                Self::Matcher,
+                self.replace_matchers(&Self::ExploreRecursiveEdge),
+            ])),
+        }
+    }
+
+    /// Replaces the matchers with `with`, except those of nested recursive selectors, which
+    /// belong to their own recursion.
+    fn replace_matchers(self, with: &Selector) -> Self {
+        let replace = |next: Box<Selector>| Box::new(next.replace_matchers(with));
+        match self {
+            Self::Matcher => with.clone(),
+            Self::ExploreAll { next } => Self::ExploreAll {
+                next: replace(next),
+            },
+            Self::ExploreFields { fields } => Self::ExploreFields {
+                fields: fields
+                    .into_iter()
+                    .map(|(key, next)| (key, next.replace_matchers(with)))
+                    .collect(),
+            },
+            Self::ExploreIndex { index, next } => Self::ExploreIndex {
+                index,
+                next: replace(next),
+            },
+            Self::ExploreRange { start, end, next } => Self::ExploreRange {
+                start,
+                end,
+                next: replace(next),
+            },
+            Self::ExploreUnion(selectors) => Self::ExploreUnion(
+                selectors
+                    .into_iter()
+                    .map(|selector| selector.replace_matchers(with))
+                    .collect(),
+            ),
+            selector @ (Self::ExploreRecursive { .. } | Self::ExploreRecursiveEdge) => selector,
+        }
+    }
+
+    /// Parses a selector envelope, `{"selector": ...}`, the form go and js peers send selectors
+    /// in.
+    pub fn from_envelope(ipld: &Ipld) -> Result<Self> {
+        match ipld {
+            Ipld::Map(map) if map.len() == 1 => Self::try_from(field(map, "selector")?),
+            _ => invalid("expected a selector envelope"),
+        }
+    }
+
+    /// Wraps the selector in an envelope, see [`Selector::from_envelope`].
+    pub fn to_envelope(&self) -> Ipld {
+        Ipld::Map([("selector".to_string(), self.into())].into())
+    }
+
+    /// Parses a dag-json encoded selector, either bare or in an envelope.
+    #[cfg(feature = "dag-json")]
+    pub fn from_json(bytes: &[u8]) -> Result<Self> {
+        use crate::codec::Codec;
+        let ipld: Ipld = crate::json::DagJsonCodec.decode(bytes)?;
+        match &ipld {
+            Ipld::Map(map) if map.contains_key("selector") => Self::from_envelope(&ipld),
+            _ => Self::try_from(&ipld),
+        }
+    }
+
+    /// Encodes the selector as dag-json, in an envelope.
+    #[cfg(feature = "dag-json")]
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        use crate::codec::Codec;
+        crate::json::DagJsonCodec.encode(&self.to_envelope())
+    }
+}
+
+/// Parses a dag-json selector, see [`Selector::from_json`].
+#[cfg(feature = "dag-json")]
+impl FromStr for Selector {
+    type Err = crate::error::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::from_json(s.as_bytes())
+    }
+}
+
+fn field<'a>(map: &'a BTreeMap<String, Ipld>, key: &str) -> Result<&'a Ipld> {
+    match map.get(key) {
+        Some(value) => Ok(value),
+        None => invalid(&format!("missing field `{}`", key)),
+    }
+}
+
+fn index(ipld: &Ipld) -> Result<usize> {
+    match ipld {
+        Ipld::Integer(i) => usize::try_from(*i).or_else(|_| invalid("index out of range")),
+        _ => invalid("index is not an integer"),
+    }
+}
+
+fn next(map: &BTreeMap<String, Ipld>) -> Result<Box<Selector>> {
+    Ok(Box::new(Selector::try_from(field(map, ">")?)?))
+}
+
+impl TryFrom<&Ipld> for Selector {
+    type Error = crate::error::Error;
+
+    fn try_from(ipld: &Ipld) -> Result<Self> {
+        let (kind, body) = match ipld {
+            Ipld::Map(map) if map.len() == 1 => map.iter().next().unwrap(),
+            _ => return invalid("expected a map with a single key"),
+        };
+        if let ("|", Ipld::List(selectors)) = (kind.as_str(), body) {
+            return Ok(Self::ExploreUnion(
+                selectors
+                    .iter()
+                    .map(Self::try_from)
+                    .collect::<Result<_>>()?,
+            ));
+        }
+        let body = match body {
+            Ipld::Map(body) => body,
+            _ => return invalid("expected a map"),
+        };
+        Ok(match kind.as_str() {
+            "." => {
+                if !body.is_empty() {
+                    return invalid("matcher subsets aren't supported");
+                }
+                Self::Matcher
+            }
+            "a" => Self::ExploreAll { next: next(body)? },
+            "f" => match field(body, "f>")? {
+                Ipld::Map(fields) => Self::ExploreFields {
+                    fields: fields
+                        .iter()
+                        .map(|(key, value)| Ok((key.clone(), Self::try_from(value)?)))
+                        .collect::<Result<_>>()?,
+                },
+                _ => return invalid("expected a map of fields"),
+            },
+            "i" => Self::ExploreIndex {
+                index: index(field(body, "i")?)?,
+                next: next(body)?,
+            },
+            "r" => Self::ExploreRange {
+                start: index(field(body, "^")?)?,
+                end: index(field(body, "$")?)?,
+                next: next(body)?,
+            },
+            "R" => {
+                if body.contains_key("!") {
+                    return invalid("stop conditions aren't supported");
+                }
+                let limit = match field(body, "l")? {
+                    Ipld::Map(limit) if limit.contains_key("none") => RecursionLimit::None,
+                    Ipld::Map(limit) => {
+                        RecursionLimit::Depth(index(field(limit, "depth")?)? as u64)
+                    }
+                    _ => return invalid("expected a recursion limit"),
+                };
+                Self::ExploreRecursive {
+                    limit,
+                    sequence: Box::new(Self::try_from(field(body, ":>")?)?),
+                }
+            }
+            "@" => Self::ExploreRecursiveEdge,
+            _ => return invalid(&format!("unknown selector `{}`", kind)),
+        })
+    }
+}
+
+impl From<&Selector> for Ipld {
+    fn from(selector: &Selector) -> Self {
+        fn map<const N: usize>(entries: [(&str, Ipld); N]) -> Ipld {
+            Ipld::Map(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v))
+                    .collect(),
+            )
+        }
+        match selector {
+            Selector::Matcher => map([(".", map([]))]),
+            Selector::ExploreAll { next } => map([("a", map([(">", next.as_ref().into())]))]),
+            Selector::ExploreFields { fields } => map([(
+                "f",
+                map([(
+                    "f>",
+                    Ipld::Map(
+                        fields
+                            .iter()
+                            .map(|(key, selector)| (key.clone(), selector.into()))
+                            .collect(),
+                    ),
+                )]),
+            )]),
+            Selector::ExploreIndex { index, next } => map([(
+                "i",
+                map([
+                    ("i", Ipld::Integer(*index as i128)),
+                    (">", next.as_ref().into()),
+                ]),
+            )]),
+            Selector::ExploreRange { start, end, next } => map([(
+                "r",
+                map([
+                    ("^", Ipld::Integer(*start as i128)),
+                    ("$", Ipld::Integer(*end as i128)),
+                    (">", next.as_ref().into()),
+                ]),
+            )]),
+            Selector::ExploreRecursive { limit, sequence } => {
+                let limit = match limit {
+                    RecursionLimit::None => map([("none", map([]))]),
+                    RecursionLimit::Depth(depth) => map([("depth", Ipld::Integer(*depth as i128))]),
+                };
+                map([("R", map([("l", limit), (":>", sequence.as_ref().into())]))])
+            }
+            Selector::ExploreRecursiveEdge => map([("@", map([]))]),
+            Selector::ExploreUnion(selectors) => {
+                map([("|", Ipld::List(selectors.iter().map(Ipld::from).collect()))])
+            }
+        }
+    }
+}
+
+/// The innermost [`Selector::ExploreRecursive`] being executed.
+#[derive(Clone, Copy, PartialEq)]
+struct Recursion<'a> {
+    sequence: &'a Selector,
+    limit: RecursionLimit,
+}
+
+/// A selector applied to a node, together with the recursion it's part of.
+type State<'a> = (&'a Selector, Option<Recursion<'a>>);
+
+/// The children of a node reached by a set of selectors, by their position in the node, with
+/// their path segment and the selectors applied to them.
+type Children<'n, 'a> = BTreeMap<usize, (String, &'n Ipld, Vec<State<'a>>)>;
+
+/// Flattens unions and recursions into the selectors that match or explore the node itself,
+/// dropping duplicates.
+fn expand<'a>(
+    selector: &'a Selector,
+    recursion: Option<Recursion<'a>>,
+    states: &mut Vec<State<'a>>,
+) -> Result<()> {
+    match selector {
+        Selector::ExploreUnion(selectors) => {
+            for selector in selectors {
+                expand(selector, recursion, states)?;
+            }
+        }
+        Selector::ExploreRecursive { limit, sequence } => {
+            let recursion = Recursion {
+                sequence,
+                limit: *limit,
+            };
+            expand(sequence, Some(recursion), states)?;
+        }
+        Selector::ExploreRecursiveEdge => {
+            let recursion = match recursion {
+                Some(recursion) => recursion,
+                None => return invalid("recursive edge outside of a recursive selector"),
+            };
+            let limit = match recursion.limit {
+                RecursionLimit::None => RecursionLimit::None,
+                RecursionLimit::Depth(0) => return Ok(()),
+                RecursionLimit::Depth(depth) => RecursionLimit::Depth(depth - 1),
+            };
+            let recursion = Recursion { limit, ..recursion };
+            expand(recursion.sequence, Some(recursion), states)?;
+        }
+        selector => {
+            if !states.contains(&(selector, recursion)) {
+                states.push((selector, recursion));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn add_child<'n, 'a>(
+    children: &mut Children<'n, 'a>,
+    position: usize,
+    segment: impl FnOnce() -> String,
+    node: &'n Ipld,
+    state: State<'a>,
+) {
+    let (_, _, states) = children
+        .entry(position)
+        .or_insert_with(|| (segment(), node, Vec::new()));
+    states.push(state);
+}
+
+struct Walker<'a, L, V> {
+    load: &'a mut L,
+    visit: &'a mut V,
+    path: Vec<String>,
+    /// The positions of the children leading to the current node, see [`ResumeToken`].
+    position: Vec<usize>,
+    resume: Option<&'a [usize]>,
+    stopped: bool,
+}
+
+impl<L, V> Walker<'_, L, V>
+where
+    L: FnMut(&Cid) -> Result<Ipld>,
+    V: FnMut(&Path, &Ipld, bool, &[usize]) -> Result<ControlFlow<()>>,
+{
+    /// Returns the position of the first child to walk. While the walk is on its way to the
+    /// resume position, the children before it were already walked.
+    fn first_child(&self) -> usize {
+        match self.resume {
+            Some(resume)
+                if resume.len() > self.position.len() && resume.starts_with(&self.position) =>
+            {
+                resume[self.position.len()]
+            }
+            _ => 0,
+        }
+    }
+
+    /// Walks `node`, applying all of `states` to it.
+    ///
+    /// Every node is visited once, however many of the selectors reach it. A child explored by
+    /// several selectors, e.g. by overlapping branches of a union, is walked once with all of them.
+    fn walk<'a>(&mut self, node: &Ipld, states: &[State<'a>]) -> Result<()> {
+        if self.stopped {
+            return Ok(());
+        }
+        // Links are traversed transparently.
+        if let Ipld::Link(cid) = node {
+            let node = (self.load)(cid)?;
+            return self.walk(&node, states);
+        }
+        let mut expanded = Vec::new();
+        for &(selector, recursion) in states {
+            expand(selector, recursion, &mut expanded)?;
+        }
+        // The nodes on the way to the resume position were visited before.
+        let resumed = matches!(self.resume, Some(resume) if resume.starts_with(&self.position));
+        if !resumed {
+            let matched = expanded
+                .iter()
+                .any(|(selector, _)| matches!(selector, Selector::Matcher));
+            let path = Path::from(self.path.clone());
+            if (self.visit)(&path, node, matched, &self.position)?.is_break() {
+                self.stopped = true;
+                return Ok(());
+            }
+        }
+
+        let first = self.first_child();
+        let mut children = Children::new();
+        for (selector, recursion) in expanded {
+            match (selector, node) {
+                (Selector::ExploreAll { next }, Ipld::List(list)) => {
+                    for (i, item) in list.iter().enumerate().skip(first) {
+                        add_child(&mut children, i, || i.to_string(), item, (next, recursion));
+                    }
+                }
+                (Selector::ExploreAll { next }, Ipld::Map(map)) => {
+                    for (i, (key, value)) in map.iter().enumerate().skip(first) {
+                        add_child(&mut children, i, || key.clone(), value, (next, recursion));
+                    }
+                }
+                (Selector::ExploreFields { fields }, Ipld::List(list)) => {
+                    for (key, next) in fields {
+                        let i = match key.parse::<usize>() {
+                            Ok(i) if i >= first => i,
+                            _ => continue,
+                        };
+                        if let Some(item) = list.get(i) {
+                            add_child(&mut children, i, || i.to_string(), item, (next, recursion));
+                        }
+                    }
+                }
+                (Selector::ExploreFields { fields }, Ipld::Map(map)) => {
+                    for (i, (key, value)) in map.iter().enumerate().skip(first) {
+                        if let Some(next) = fields.get(key) {
+                            add_child(&mut children, i, || key.clone(), value, (next, recursion));
+                        }
+                    }
+                }
+                (Selector::ExploreIndex { index, next }, Ipld::List(list)) => {
+                    if let Some(item) = list.get(*index).filter(|_| *index >= first) {
+                        let state = (next.as_ref(), recursion);
+                        add_child(&mut children, *index, || index.to_string(), item, state);
+                    }
+                }
+                (Selector::ExploreRange { start, end, next }, Ipld::List(list)) => {
+                    let start = (*start).max(first);
+                    for (i, item) in list.iter().enumerate().take(*end).skip(start) {
+                        add_child(&mut children, i, || i.to_string(), item, (next, recursion));
+                    }
+                }
+                _ => {}
+            }
+        }
+        for (i, (segment, child, states)) in children {
+            self.path.push(segment);
+            self.position.push(i);
+            let res = self.walk(child, &states);
+            self.position.pop();
+            self.path.pop();
+            res?;
+        }
+        Ok(())
+    }
+}
+
+/// Walks the dag rooted at `root` as directed by `selector`.
+///
+/// Links are followed by calling `load`. `visit` is called with the path, the node and whether
+/// the selector matched it, once for every node the selector reaches, even if several branches of
+/// a union reach it.
+pub fn walk<L, V>(root: &Ipld, selector: &Selector, mut load: L, mut visit: V) -> Result<()>
+where
+    L: FnMut(&Cid) -> Result<Ipld>,
+    V: FnMut(&Path, &Ipld, bool) -> Result<()>,
+{
+    let mut visit = |path: &Path, node: &Ipld, matched: bool, _: &[usize]| {
+        visit(path, node, matched)?;
+        Ok(ControlFlow::Continue(()))
+    };
+    let mut walker = Walker {
+        load: &mut load,
+        visit: &mut visit,
+        path: Vec::new(),
+        position: Vec::new(),
+        resume: None,
+        stopped: false,
+    };
+    walker.walk(root, &[(selector, None)])
+}
+
+/// Returns the paths and nodes matched by `selector`.
+pub fn select<L>(root: &Ipld, selector: &Selector, load: L) -> Result<Vec<(Path, Ipld)>>
+where
+    L: FnMut(&Cid) -> Result<Ipld>,
+{
+    let mut matched = Vec::new();
+    walk(root, selector, load, |path, node, is_match| {
+        if is_match {
+            matched.push((path.clone(), node.clone()));
+        }
+        Ok(())
+    })?;
+    Ok(matched)
+}
+
+/// The position of a node in a selector walk, to continue the walk after it later.
+///
+/// Tokens are only meaningful for the root and selector of the walk they came from. They are
+/// opaque, but convert to and from strings, so they can be handed to clients.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ResumeToken(Vec<usize>);
+
+impl fmt::Display for ResumeToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("r")?;
+        for choice in &self.0 {
+            write!(f, ".{}", choice)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ResumeToken {
+    type Err = crate::error::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.split('.');
+        if parts.next() != Some("r") {
+            return invalid("invalid resume token");
+        }
+        parts
+            .map(|choice| choice.parse().or_else(|_| invalid("invalid resume token")))
+            .collect::<Result<_>>()
+            .map(Self)
+    }
+}
+
+/// Like [`walk`], but starts after the node `resume` was handed out for, and stops when `visit`
+/// breaks.
+///
+/// `visit` gets the [`ResumeToken`] of every node. The subtrees before the resume position aren't
+/// walked again, only the blocks on the way to it are loaded.
+pub fn walk_from<L, V>(
+    root: &Ipld,
+    selector: &Selector,
+    resume: Option<&ResumeToken>,
+    mut load: L,
+    mut visit: V,
+) -> Result<()>
+where
+    L: FnMut(&Cid) -> Result<Ipld>,
+    V: FnMut(&Path, &Ipld, bool, &ResumeToken) -> Result<ControlFlow<()>>,
+{
+    let mut visit = |path: &Path, node: &Ipld, matched: bool, position: &[usize]| {
+        visit(path, node, matched, &ResumeToken(position.to_vec()))
+    };
+    let mut walker = Walker {
+        load: &mut load,
+        visit: &mut visit,
+        path: Vec::new(),
+        position: Vec::new(),
+        resume: resume.map(|resume| &resume.0[..]),
+        stopped: false,
+    };
+    walker.walk(root, &[(selector, None)])
+}
+
+/// A page of matches and the token to get the next page with, returned by [`select_page`].
+pub type Page = (Vec<(Path, Ipld)>, Option<ResumeToken>);
+
+/// Returns up to `limit` matches of `selector` after `resume`, see [`select`], and the token to
+/// get the next page with. The token is `None` if there are no more matches. A `limit` of 0 is
+/// treated as 1, so every page makes progress.
+pub fn select_page<L>(
+    root: &Ipld,
+    selector: &Selector,
+    resume: Option<&ResumeToken>,
+    limit: usize,
+    load: L,
+) -> Result<Page>
+where
+    L: FnMut(&Cid) -> Result<Ipld>,
+{
+    let limit = limit.max(1);
+    let mut matched = Vec::new();
+    let mut last = None;
+    let mut more = false;
+    walk_from(
+        root,
+        selector,
+        resume,
+        load,
+        |path, node, is_match, token| {
+            if !is_match {
+                return Ok(ControlFlow::Continue(()));
+            }
+            if matched.len() == limit {
+                more = true;
+                return Ok(ControlFlow::Break(()));
+            }
+            matched.push((path.clone(), node.clone()));
+            last = Some(token.clone());
+            Ok(ControlFlow::Continue(()))
+        },
+    )?;
+    Ok((matched, if more { last } else { None }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cbor::DagCborCodec;
+    use crate::codec::Codec;
+    use crate::ipld;
+    use crate::test_util::Dag;
+
+    fn paths(matched: Vec<(Path, Ipld)>) -> Vec<String> {
+        matched
+            .into_iter()
+            .map(|(path, _)| path.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let ipld = ipld!({
+            "R": {
+                "l": { "depth": 3 },
+                ":>": { "|": [
+                    { ".": {} },
+                    { "f": { "f>": {
+                        "a": { "i": { "i": 1, ">": { "@": {} } } },
+                        "b": { "r": { "^": 0, "$": 2, ">": { "a": { ">": { ".": {} } } } } },
+                    } } },
+                ] },
+            }
+        });
+        let selector = Selector::try_from(&ipld).unwrap();
+        assert_eq!(Ipld::from(&selector), ipld);
+        let bytes = DagCborCodec.encode(&ipld).unwrap();
+        let decoded: Ipld = DagCborCodec.decode(&bytes).unwrap();
+        assert_eq!(Selector::try_from(&decoded).unwrap(), selector);
+        assert!(Selector::try_from(&ipld!({ "x": {} })).is_err());
+    }
+
+    #[test]
+    fn test_select_fields_across_links() {
+        let dag = Dag::default();
+        let leaf = dag.insert(ipld!({ "name": "leaf", "size": 1 }));
+        let root = ipld!({ "children": [leaf, { "name": "inline" }], "name": "root" });
+        let selector = Selector::ExploreFields {
+            fields: [(
+                "children".to_string(),
+                Selector::ExploreAll {
+                    next: Box::new(Selector::ExploreFields {
+                        fields: [("name".to_string(), Selector::Matcher)].into(),
+                    }),
+                },
+            )]
+            .into(),
+        };
+        let matched = select(&root, &selector, dag.load()).unwrap();
+        assert_eq!(
+            matched,
+            vec![
+                (Path::from("children/0/name"), ipld!("leaf")),
+                (Path::from("children/1/name"), ipld!("inline")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_explore_recursive() {
+        let dag = Dag::default();
+        let mut cid = dag.insert(ipld!({ "depth": 0 }));
+        for depth in 1..5 {
+            cid = dag.insert(ipld!({ "depth": depth, "parent": cid }));
+        }
+        let root = Ipld::Link(cid);
+
+        let all = select(&root, &Selector::explore_all_recursively(), dag.load()).unwrap();
+        assert_eq!(all.len(), 10);
+
+        let parents = Selector::ExploreRecursive {
+            limit: RecursionLimit::Depth(2),
+            sequence: Box::new(Selector::ExploreUnion(vec![
+                Selector::Matcher,
+                Selector::ExploreFields {
+                    fields: [("parent".to_string(), Selector::ExploreRecursiveEdge)].into(),
+                },
+            ])),
+        };
+        assert_eq!(
+            paths(select(&root, &parents, dag.load()).unwrap()),
+            vec!["", "parent", "parent/parent"]
+        );
+    }
+
+    #[test]
+    fn test_builders() {
+        let selector = Selector::fields(["a", "b"]).recursive(3);
+        assert_eq!(
+            selector,
+            Selector::ExploreRecursive {
+                limit: RecursionLimit::Depth(3),
+                sequence: Box::new(Selector::ExploreUnion(vec![
+                    Selector::Matcher,
+                    Selector::ExploreFields {
+                        fields: [
+                            ("a".to_string(), Selector::ExploreRecursiveEdge),
+                            ("b".to_string(), Selector::ExploreRecursiveEdge),
+                        ]
+                        .into(),
+                    },
+                ])),
+            }
+        );
+        let root = ipld!({ "a": { "b": { "a": { "a": {} } } }, "c": 1 });
+        assert_eq!(
+            paths(select(&root, &selector, |_| unreachable!()).unwrap()),
+            vec!["", "a", "a/b", "a/b/a"]
+        );
+
+        let selector = Selector::fields(["a"]).then(Selector::index(1).then(Selector::all()));
+        let root = ipld!({ "a": [[0], [1, 2]], "b": [[3]] });
+        assert_eq!(
+            paths(select(&root, &selector, |_| unreachable!()).unwrap()),
+            vec!["a/1/0", "a/1/1"]
+        );
+        assert_eq!(
+            Selector::union([Selector::matcher(), Selector::range(0, 1)]),
+            Selector::ExploreUnion(vec![
+                Selector::Matcher,
+                Selector::ExploreRange {
+                    start: 0,
+                    end: 1,
+                    next: Box::new(Selector::Matcher)
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let json =
+            r#"{"selector":{"R":{":>":{"|":[{".":{}},{"a":{">":{"@":{}}}}]},"l":{"none":{}}}}}"#;
+        let selector: Selector = json.parse().unwrap();
+        assert_eq!(selector, Selector::explore_all_recursively());
+        assert_eq!(selector.to_json().unwrap(), json.as_bytes());
+        assert_eq!(
+            Selector::from_json(br#"{"f":{"f>":{"a":{".":{}}}}}"#).unwrap(),
+            Selector::fields(["a"])
+        );
+        assert!(Selector::from_json(br#"{"selector":{"x":{}}}"#).is_err());
+        assert!(Selector::from_envelope(&ipld!({ "other": {} })).is_err());
+    }
+
+    #[test]
+    fn test_select_page() {
+        let dag = Dag::default();
+        let a = dag.insert(ipld!({ "x": [1, 2], "y": 3 }));
+        let b = dag.insert(ipld!([4, { "z": 5 }]));
+        let root = ipld!({ "a": a, "b": b, "c": 6 });
+        let selector = Selector::explore_all_recursively();
+        let all = select(&root, &selector, dag.load()).unwrap();
+        assert_eq!(all.len(), 11);
+
+        for limit in 1..=all.len() + 1 {
+            let mut pages = Vec::new();
+            let mut token: Option<ResumeToken> = None;
+            loop {
+                // Roundtrip the token through its string form, as a client would.
+                let resume = token.map(|token| token.to_string().parse().unwrap());
+                let (page, next) =
+                    select_page(&root, &selector, resume.as_ref(), limit, dag.load()).unwrap();
+                assert!(page.len() <= limit);
+                pages.extend(page);
+                match next {
+                    Some(next) => token = Some(next),
+                    None => break,
+                }
+            }
+            assert_eq!(pages, all);
+        }
+
+        // Resuming after `b` doesn't load `a` again.
+        let (page, next) = select_page(&root, &selector, None, 7, dag.load()).unwrap();
+        assert_eq!(page.last().unwrap().0, Path::from("b"));
+        let mut loaded = Vec::new();
+        let mut load = dag.load();
+        let (page, _) = select_page(&root, &selector, next.as_ref(), 1, |cid| {
+            loaded.push(*cid);
+            load(cid)
+        })
+        .unwrap();
+        assert_eq!(page, vec![(Path::from("b/0"), ipld!(4))]);
+        assert_eq!(loaded, vec![b]);
+
+        assert!("x.1".parse::<ResumeToken>().is_err());
+        assert!("r.a".parse::<ResumeToken>().is_err());
+        assert_eq!("r".parse::<ResumeToken>().unwrap(), ResumeToken::default());
+    }
+
+    #[test]
+    fn test_overlapping_union() {
+        let root = ipld!({ "a": [0, 1], "b": 2 });
+        let selector = Selector::union([
+            Selector::all(),
+            Selector::fields(["a"]).then(Selector::range(0, 2)),
+            Selector::fields(["a"]).then(Selector::index(1)),
+        ]);
+        let mut visited = Vec::new();
+        walk(
+            &root,
+            &selector,
+            |_| unreachable!(),
+            |path, _, matched| {
+                visited.push((path.to_string(), matched));
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            visited,
+            vec![
+                ("".to_string(), false),
+                ("a".to_string(), true),
+                ("a/0".to_string(), true),
+                ("a/1".to_string(), true),
+                ("b".to_string(), true),
+            ]
+        );
+
+        let twice = Selector::union([
+            Selector::explore_all_recursively(),
+            Selector::explore_all_recursively(),
+        ]);
+        assert_eq!(
+            paths(select(&root, &twice, |_| unreachable!()).unwrap()),
+            vec!["", "a", "a/0", "a/1", "b"]
+        );
+    }
+
+    #[test]
+    fn test_range_and_index() {
+        let root = ipld!([0, 1, 2, 3, 4]);
+        let range = Selector::ExploreRange {
+            start: 3,
+            end: 10,
+            next: Box::new(Selector::Matcher),
+        };
+        assert_eq!(
+            select(&root, &range, |_| unreachable!()).unwrap(),
+            vec![(Path::from("3"), ipld!(3)), (Path::from("4"), ipld!(4))]
+        );
+        let index = Selector::ExploreIndex {
+            index: 1,
+            next: Box::new(Selector::Matcher),
+        };
+        assert_eq!(
+            paths(select(&root, &index, |_| unreachable!()).unwrap()),
+            vec!["1"]
+        );
+        assert!(select(&root, &Selector::ExploreRecursiveEdge, |_| unreachable!()).is_err());
+    }
+}
@@ -0,0 +1,184 @@
+//! Codec telemetry.
+//!
+//! [`Block::encode`](crate::block::Block::encode) and [`Block::decode`](crate::block::Block::decode)
+//! record the encoded block sizes, codec usage and decode durations. [`metrics`] returns a
+//! snapshot, which can be rendered in the prometheus text format.
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Number of histogram buckets. Bucket `i` counts values up to `2^i`.
+pub const BUCKETS: usize = 33;
+
+/// A histogram with exponential buckets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    /// Bucket `i` counts the values in `(2^(i-1), 2^i]`, the first bucket counts `0` and `1`.
+    /// Values larger than the last bucket are only included in `count` and `sum`.
+    pub buckets: [u64; BUCKETS],
+    /// Number of values.
+    pub count: u64,
+    /// Sum of the values.
+    pub sum: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Self {
+            buckets: [0; BUCKETS],
+            count: 0,
+            sum: 0,
+        }
+    }
+
+    fn record(&mut self, value: u64) {
+        let bucket = (u64::BITS - value.saturating_sub(1).leading_zeros()) as usize;
+        if let Some(bucket) = self.buckets.get_mut(bucket) {
+            *bucket += 1;
+        }
+        self.count += 1;
+        self.sum = self.sum.saturating_add(value);
+    }
+
+    fn write_prometheus(&self, out: &mut String, name: &str) {
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        let mut cumulative = 0;
+        for (i, count) in self.buckets.iter().enumerate() {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "{}_bucket{{le=\"{}\"}} {}",
+                name,
+                1u64 << i,
+                cumulative
+            );
+        }
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, self.count);
+        let _ = writeln!(out, "{}_sum {}", name, self.sum);
+        let _ = writeln!(out, "{}_count {}", name, self.count);
+    }
+}
+
+/// A snapshot of the collected metrics.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// Sizes of encoded blocks in bytes.
+    pub block_sizes: Histogram,
+    /// Decode durations in microseconds.
+    pub decode_durations: Histogram,
+    /// Number of encoded blocks by codec.
+    pub encodes: BTreeMap<u64, u64>,
+    /// Number of decoded blocks by codec.
+    pub decodes: BTreeMap<u64, u64>,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            block_sizes: Histogram::new(),
+            decode_durations: Histogram::new(),
+            encodes: BTreeMap::new(),
+            decodes: BTreeMap::new(),
+        }
+    }
+
+    /// Renders the metrics in the prometheus text format.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        self.block_sizes
+            .write_prometheus(&mut out, "ipld_block_size_bytes");
+        self.decode_durations
+            .write_prometheus(&mut out, "ipld_decode_duration_microseconds");
+        for (name, counts) in [("encode", &self.encodes), ("decode", &self.decodes)] {
+            let _ = writeln!(out, "# TYPE ipld_{}_total counter", name);
+            for (codec, count) in counts {
+                let _ = writeln!(
+                    out,
+                    "ipld_{}_total{{codec=\"0x{:x}\"}} {}",
+                    name, codec, count
+                );
+            }
+        }
+        out
+    }
+}
+
+static METRICS: Mutex<Metrics> = Mutex::new(Metrics::new());
+
+fn with_metrics<F: FnOnce(&mut Metrics)>(f: F) {
+    // A panic while holding the lock can't leave the counters in an invalid state.
+    let mut metrics = METRICS.lock().unwrap_or_else(|err| err.into_inner());
+    f(&mut metrics)
+}
+
+pub(crate) fn record_encode(codec: u64, size: usize) {
+    with_metrics(|metrics| {
+        metrics.block_sizes.record(size as u64);
+        *metrics.encodes.entry(codec).or_default() += 1;
+    })
+}
+
+pub(crate) fn record_decode(codec: u64, duration: Duration) {
+    with_metrics(|metrics| {
+        metrics.decode_durations.record(duration.as_micros() as u64);
+        *metrics.decodes.entry(codec).or_default() += 1;
+    })
+}
+
+/// Returns a snapshot of the metrics collected so far.
+pub fn metrics() -> Metrics {
+    let mut snapshot = Metrics::default();
+    with_metrics(|metrics| snapshot = metrics.clone());
+    snapshot
+}
+
+/// Resets all metrics.
+pub fn reset() {
+    with_metrics(|metrics| *metrics = Metrics::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::cbor::DagCborCodec;
+    use crate::ipld::Ipld;
+    use crate::multihash::Code;
+    use crate::store::DefaultParams;
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::default();
+        for value in [0, 1, 2, 3, 4, 1000, u64::MAX] {
+            histogram.record(value);
+        }
+        assert_eq!(&histogram.buckets[..4], &[2, 1, 2, 0]);
+        assert_eq!(histogram.buckets[10], 1);
+        assert_eq!(histogram.buckets.iter().sum::<u64>(), 6);
+        assert_eq!(histogram.count, 7);
+    }
+
+    #[test]
+    fn test_block_metrics() {
+        // Other tests encode blocks concurrently, so only check the counters grew.
+        let before = metrics();
+        let block =
+            Block::<DefaultParams>::encode(DagCborCodec, Code::Blake3_256, "hello").unwrap();
+        block.decode::<DagCborCodec, Ipld>().unwrap();
+        let after = metrics();
+        assert!(after.block_sizes.count > before.block_sizes.count);
+        assert!(after.encodes[&0x71] > before.encodes.get(&0x71).copied().unwrap_or_default());
+        assert!(after.decodes[&0x71] > before.decodes.get(&0x71).copied().unwrap_or_default());
+
+        let text = after.to_prometheus();
+        assert!(text.contains("# TYPE ipld_block_size_bytes histogram\n"));
+        assert!(text.contains("ipld_encode_total{codec=\"0x71\"} "));
+    }
+}
@@ -0,0 +1,33 @@
+//! Helpers shared by the tests.
+use crate::cbor::DagCborCodec;
+use crate::cid::Cid;
+use crate::codec::Codec;
+use crate::error::Result;
+use crate::ipld::Ipld;
+use crate::multihash::{Code, MultihashDigest};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// An in-memory dag of dag-cbor blocks, which can be loaded from and stored to at the same time.
+#[derive(Default)]
+pub struct Dag(RefCell<HashMap<Cid, Ipld>>);
+
+impl Dag {
+    /// Adds a block and returns its CID.
+    pub fn insert(&self, ipld: Ipld) -> Cid {
+        let bytes = DagCborCodec.encode(&ipld).unwrap();
+        let cid = Cid::new_v1(0x71, Code::Blake3_256.digest(&bytes));
+        self.0.borrow_mut().insert(cid, ipld);
+        cid
+    }
+
+    /// Returns a loader for the blocks.
+    pub fn load(&self) -> impl FnMut(&Cid) -> Result<Ipld> + '_ {
+        |cid| Ok(self.0.borrow()[cid].clone())
+    }
+
+    /// Returns a storer for the blocks.
+    pub fn store(&self) -> impl FnMut(Ipld) -> Result<Cid> + '_ {
+        |ipld| Ok(self.insert(ipld))
+    }
+}
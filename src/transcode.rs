@@ -0,0 +1,275 @@
+//! Transcoding between codecs.
+//!
+//! Not every value survives every codec. Some values fail to encode, others encode fine but
+//! decode as something else. [`audit`] reports both kinds of loss up front, so callers can decide
+//! whether to go ahead, fix up the data or give up.
+use crate::block::Block;
+use crate::codec::{Codec, Decode};
+use crate::codec_impl::IpldCodec;
+use crate::error::Result;
+use crate::ipld::Ipld;
+use crate::path::Path;
+use crate::store::StoreParams;
+
+/// Why a value doesn't survive transcoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LossKind {
+    /// A NaN or infinite float, which neither dag-cbor nor dag-json can encode.
+    NonFiniteFloat,
+    /// An integer outside of the 64-bit range. dag-cbor refuses to encode it, dag-json decodes
+    /// it as a float.
+    BigInteger,
+    /// A map with a single `"/"` key holding a string or a `{"bytes": "..."}` map. dag-json refuses
+    /// to encode it, as it would decode as a link or bytes.
+    ReservedKey,
+    /// The codec can't represent the value at all, for example anything but bytes in raw or a
+    /// value that doesn't follow the dag-pb schema.
+    Unrepresentable,
+}
+
+impl LossKind {
+    /// Returns true if encoding fails, rather than silently changing the value.
+    pub fn is_fatal(self, codec: IpldCodec) -> bool {
+        match self {
+            Self::BigInteger => !is_json(codec),
+            Self::NonFiniteFloat | Self::ReservedKey | Self::Unrepresentable => true,
+        }
+    }
+}
+
+/// Returns true for dag-json, which decodes big integers as floats instead of refusing them.
+fn is_json(codec: IpldCodec) -> bool {
+    #[cfg(feature = "dag-json")]
+    {
+        codec == IpldCodec::DagJson
+    }
+    #[cfg(not(feature = "dag-json"))]
+    {
+        let _ = codec;
+        false
+    }
+}
+
+/// A value that doesn't survive transcoding.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Loss {
+    /// The path to the value.
+    pub path: Path,
+    /// What's lost.
+    pub kind: LossKind,
+}
+
+/// The values of a dag that don't survive transcoding to a codec.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TranscodeReport {
+    /// The codec the report is for.
+    pub codec: IpldCodec,
+    /// The lossy values.
+    pub losses: Vec<Loss>,
+}
+
+impl TranscodeReport {
+    /// Returns true if the value round trips unchanged.
+    pub fn is_lossless(&self) -> bool {
+        self.losses.is_empty()
+    }
+
+    /// Returns true if encoding fails.
+    pub fn is_fatal(&self) -> bool {
+        self.losses
+            .iter()
+            .any(|loss| loss.kind.is_fatal(self.codec))
+    }
+}
+
+/// Reports the values of `ipld` that don't survive encoding with `codec`.
+pub fn audit(ipld: &Ipld, codec: IpldCodec) -> TranscodeReport {
+    let mut losses = Vec::new();
+    match codec {
+        IpldCodec::Raw => {
+            if !matches!(ipld, Ipld::Bytes(_)) {
+                losses.push(Loss {
+                    path: Path::default(),
+                    kind: LossKind::Unrepresentable,
+                });
+            }
+        }
+        #[cfg(feature = "dag-pb")]
+        IpldCodec::DagPb => {
+            if codec.encode(ipld).is_err() {
+                losses.push(Loss {
+                    path: Path::default(),
+                    kind: LossKind::Unrepresentable,
+                });
+            }
+        }
+        #[allow(unreachable_patterns)]
+        _ => audit_value(ipld, codec, &mut Vec::new(), &mut losses),
+    }
+    TranscodeReport { codec, losses }
+}
+
+fn audit_value(ipld: &Ipld, codec: IpldCodec, path: &mut Vec<String>, losses: &mut Vec<Loss>) {
+    let mut lose = |kind| {
+        losses.push(Loss {
+            path: Path::from(path.clone()),
+            kind,
+        })
+    };
+    match ipld {
+        Ipld::Float(f) if !f.is_finite() => lose(LossKind::NonFiniteFloat),
+        Ipld::Integer(i) => {
+            let min = if is_json(codec) {
+                i64::MIN as i128
+            } else {
+                -(u64::MAX as i128) - 1
+            };
+            if *i < min || *i > u64::MAX as i128 {
+                lose(LossKind::BigInteger);
+            }
+        }
+        Ipld::List(list) => {
+            for (i, item) in list.iter().enumerate() {
+                path.push(i.to_string());
+                audit_value(item, codec, path, losses);
+                path.pop();
+            }
+        }
+        Ipld::Map(map) => {
+            #[cfg(feature = "dag-json")]
+            if is_json(codec) && crate::json::is_ambiguous(map) {
+                lose(LossKind::ReservedKey);
+            }
+            for (key, value) in map {
+                path.push(key.clone());
+                audit_value(value, codec, path, losses);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Transcodes a block to `codec`, returning the new block and the report of lossy values.
+///
+/// Fails if the block can't be decoded or the value can't be encoded with `codec`. Callers that
+/// need lossless transcoding check [`TranscodeReport::is_lossless`].
+pub fn transcode<S>(
+    block: &Block<S>,
+    codec: IpldCodec,
+    hcode: S::Hashes,
+) -> Result<(Block<S>, TranscodeReport)>
+where
+    S: StoreParams,
+    IpldCodec: Into<S::Codecs>,
+    Ipld: Decode<S::Codecs>,
+{
+    let ipld = block.ipld()?;
+    let report = audit(&ipld, codec);
+    let block = Block::encode(codec, hcode, &ipld)?;
+    Ok((block, report))
+}
+
+/// Decodes `bytes` with `from` and encodes them with `to`, returning the report of lossy values.
+pub fn transcode_bytes(
+    bytes: &[u8],
+    from: IpldCodec,
+    to: IpldCodec,
+) -> Result<(Vec<u8>, TranscodeReport)> {
+    let ipld: Ipld = from.decode(bytes)?;
+    let report = audit(&ipld, to);
+    let bytes = to.encode(&ipld)?;
+    Ok((bytes, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipld;
+    use crate::multihash::Code;
+    use crate::store::DefaultParams;
+
+    fn kinds(report: &TranscodeReport) -> Vec<(String, LossKind)> {
+        report
+            .losses
+            .iter()
+            .map(|loss| (loss.path.to_string(), loss.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_audit_json() {
+        let ipld = ipld!({
+            "nan": f64::NAN,
+            "big": [u64::MAX, Ipld::Integer(u64::MAX as i128 + 1)],
+            "link": { "/": "not a cid" },
+            "bytes": { "/": { "bytes": "AQ" } },
+            "ok": { "/": "a", "b": 1 },
+            "number": { "/": 1 },
+        });
+        let report = audit(&ipld, IpldCodec::DagJson);
+        assert_eq!(
+            kinds(&report),
+            vec![
+                ("big/1".into(), LossKind::BigInteger),
+                ("bytes".into(), LossKind::ReservedKey),
+                ("link".into(), LossKind::ReservedKey),
+                ("nan".into(), LossKind::NonFiniteFloat),
+            ]
+        );
+        assert!(report.is_fatal());
+        assert!(IpldCodec::DagJson
+            .encode(&ipld!({ "link": { "/": "not a cid" } }))
+            .is_err());
+
+        let report = audit(&ipld!([f64::NAN]), IpldCodec::DagJson);
+        assert!(report.is_fatal());
+        assert!(IpldCodec::DagJson.encode(&ipld!([f64::NAN])).is_err());
+
+        let report = audit(&ipld!({ "big": u64::MAX as i128 + 1 }), IpldCodec::DagJson);
+        assert!(!report.is_lossless());
+        assert!(!report.is_fatal());
+
+        let (bytes, _) = transcode_bytes(
+            &IpldCodec::DagCbor
+                .encode(&ipld!({ "big": Ipld::Integer(i64::MIN as i128 - 1) }))
+                .unwrap(),
+            IpldCodec::DagCbor,
+            IpldCodec::DagJson,
+        )
+        .unwrap();
+        let ipld: Ipld = IpldCodec::DagJson.decode(&bytes).unwrap();
+        assert!(matches!(ipld.get("big").unwrap(), Ipld::Float(_)));
+    }
+
+    #[test]
+    fn test_audit_cbor() {
+        let ipld = ipld!([f64::INFINITY, u64::MAX, Ipld::Integer(u64::MAX as i128 + 1)]);
+        let report = audit(&ipld, IpldCodec::DagCbor);
+        assert_eq!(
+            kinds(&report),
+            vec![
+                ("0".into(), LossKind::NonFiniteFloat),
+                ("2".into(), LossKind::BigInteger),
+            ]
+        );
+        assert!(report.is_fatal());
+    }
+
+    #[test]
+    fn test_transcode() {
+        let block = Block::<DefaultParams>::encode(
+            IpldCodec::DagJson,
+            Code::Blake3_256,
+            &ipld!({ "bytes": vec![1u8, 2, 3] }),
+        )
+        .unwrap();
+        let (cbor, report) = transcode(&block, IpldCodec::DagCbor, Code::Blake3_256).unwrap();
+        assert!(report.is_lossless());
+        assert_eq!(cbor.ipld().unwrap(), block.ipld().unwrap());
+
+        let report = audit(&cbor.ipld().unwrap(), IpldCodec::Raw);
+        assert_eq!(kinds(&report), vec![("".into(), LossKind::Unrepresentable)]);
+        assert!(transcode(&cbor, IpldCodec::Raw, Code::Blake3_256).is_err());
+    }
+}
@@ -0,0 +1,218 @@
+//! The wasm-bindgen bindings.
+use js_sys::{Array, BigInt, Object, Reflect, Uint8Array};
+use libipld::block::Block;
+use libipld::cbor::DagCborCodec;
+use libipld::cid::Cid;
+use libipld::codec::Codec;
+use libipld::ipld::Ipld;
+use libipld::json::DagJsonCodec;
+use libipld::multihash::Code;
+use libipld::store::DefaultParams;
+use libipld::IpldCodec;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::{is_safe_integer, number_to_ipld};
+
+fn js_error<E: std::fmt::Display>(err: E) -> JsError {
+    JsError::new(&err.to_string())
+}
+
+/// Converts a javascript value to ipld.
+pub fn to_ipld(value: &JsValue) -> Result<Ipld, JsError> {
+    if value.is_null() || value.is_undefined() {
+        return Ok(Ipld::Null);
+    }
+    if let Some(b) = value.as_bool() {
+        return Ok(Ipld::Bool(b));
+    }
+    if let Some(f) = value.as_f64() {
+        return Ok(number_to_ipld(f));
+    }
+    if let Some(big) = value.dyn_ref::<BigInt>() {
+        let digits = String::from(
+            big.to_string(10)
+                .map_err(|_| JsError::new("invalid bigint"))?,
+        );
+        return Ok(Ipld::Integer(digits.parse().map_err(js_error)?));
+    }
+    if let Some(s) = value.as_string() {
+        return Ok(Ipld::String(s));
+    }
+    if let Some(bytes) = value.dyn_ref::<Uint8Array>() {
+        return Ok(Ipld::Bytes(bytes.to_vec()));
+    }
+    if Array::is_array(value) {
+        let array: &Array = value.unchecked_ref();
+        return array
+            .iter()
+            .map(|item| to_ipld(&item))
+            .collect::<Result<_, _>>()
+            .map(Ipld::List);
+    }
+    if let Some(object) = value.dyn_ref::<Object>() {
+        let mut map = BTreeMap::new();
+        for entry in Object::entries(object).iter() {
+            let entry: Array = entry.unchecked_into();
+            let key = entry
+                .get(0)
+                .as_string()
+                .ok_or_else(|| JsError::new("object key is not a string"))?;
+            map.insert(key, to_ipld(&entry.get(1))?);
+        }
+        if map.len() == 1 {
+            if let Some(Ipld::String(cid)) = map.get("/") {
+                return Ok(Ipld::Link(Cid::try_from(cid.as_str()).map_err(js_error)?));
+            }
+        }
+        return Ok(Ipld::Map(map));
+    }
+    Err(JsError::new("unsupported javascript value"))
+}
+
+/// Converts ipld to a javascript value.
+pub fn from_ipld(ipld: &Ipld) -> Result<JsValue, JsError> {
+    Ok(match ipld {
+        Ipld::Null => JsValue::NULL,
+        Ipld::Bool(b) => JsValue::from_bool(*b),
+        Ipld::Integer(i) if is_safe_integer(*i) => JsValue::from_f64(*i as f64),
+        Ipld::Integer(i) => BigInt::new(&JsValue::from_str(&i.to_string()))
+            .map_err(|_| JsError::new("invalid bigint"))?
+            .into(),
+        Ipld::Float(f) => JsValue::from_f64(*f),
+        Ipld::String(s) => JsValue::from_str(s),
+        Ipld::Bytes(b) => Uint8Array::from(&b[..]).into(),
+        Ipld::List(list) => list
+            .iter()
+            .map(from_ipld)
+            .collect::<Result<Array, _>>()?
+            .into(),
+        Ipld::Map(map) => {
+            let object = Object::new();
+            for (key, value) in map {
+                Reflect::set(&object, &JsValue::from_str(key), &from_ipld(value)?)
+                    .map_err(|_| JsError::new("failed to set property"))?;
+            }
+            object.into()
+        }
+        Ipld::Link(cid) => {
+            let object = Object::new();
+            Reflect::set(&object, &JsValue::from_str("/"), &cid.to_string().into())
+                .map_err(|_| JsError::new("failed to set property"))?;
+            object.into()
+        }
+    })
+}
+
+/// A CID.
+#[wasm_bindgen(js_name = Cid)]
+pub struct JsCid(Cid);
+
+#[wasm_bindgen(js_class = Cid)]
+impl JsCid {
+    /// Parses a CID from its string representation.
+    pub fn parse(s: &str) -> Result<JsCid, JsError> {
+        Ok(Self(Cid::try_from(s).map_err(js_error)?))
+    }
+
+    /// Reads a CID from its binary representation.
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<JsCid, JsError> {
+        Ok(Self(Cid::try_from(bytes).map_err(js_error)?))
+    }
+
+    /// Returns the binary representation.
+    pub fn bytes(&self) -> Vec<u8> {
+        self.0.to_bytes()
+    }
+
+    /// Returns the codec.
+    pub fn codec(&self) -> u64 {
+        self.0.codec()
+    }
+
+    /// Returns the string representation.
+    #[wasm_bindgen(js_name = toString)]
+    pub fn to_string_js(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+/// Encodes a javascript value with dag-cbor.
+#[wasm_bindgen(js_name = encodeDagCbor)]
+pub fn encode_dag_cbor(value: &JsValue) -> Result<Vec<u8>, JsError> {
+    DagCborCodec.encode(&to_ipld(value)?).map_err(js_error)
+}
+
+/// Decodes dag-cbor into a javascript value.
+#[wasm_bindgen(js_name = decodeDagCbor)]
+pub fn decode_dag_cbor(bytes: &[u8]) -> Result<JsValue, JsError> {
+    from_ipld(&DagCborCodec.decode(bytes).map_err(js_error)?)
+}
+
+/// Encodes a javascript value with dag-json.
+#[wasm_bindgen(js_name = encodeDagJson)]
+pub fn encode_dag_json(value: &JsValue) -> Result<Vec<u8>, JsError> {
+    DagJsonCodec.encode(&to_ipld(value)?).map_err(js_error)
+}
+
+/// Decodes dag-json into a javascript value.
+#[wasm_bindgen(js_name = decodeDagJson)]
+pub fn decode_dag_json(bytes: &[u8]) -> Result<JsValue, JsError> {
+    from_ipld(&DagJsonCodec.decode(bytes).map_err(js_error)?)
+}
+
+/// An in-memory block store.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct MemStore {
+    blocks: HashMap<Cid, Block<DefaultParams>>,
+}
+
+#[wasm_bindgen]
+impl MemStore {
+    /// Creates an empty store.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> MemStore {
+        Self::default()
+    }
+
+    /// Encodes a value with the codec (`0x71` for dag-cbor, `0x0129` for dag-json) and a
+    /// sha2-256 hash, and stores it. Returns the CID.
+    pub fn put(&mut self, codec: u64, value: &JsValue) -> Result<JsCid, JsError> {
+        let codec = IpldCodec::try_from(codec).map_err(js_error)?;
+        let block = Block::encode(codec, Code::Sha2_256, &to_ipld(value)?).map_err(js_error)?;
+        let cid = *block.cid();
+        self.blocks.insert(cid, block);
+        Ok(JsCid(cid))
+    }
+
+    /// Stores an encoded block after verifying its hash.
+    #[wasm_bindgen(js_name = putBytes)]
+    pub fn put_bytes(&mut self, cid: &JsCid, bytes: Vec<u8>) -> Result<(), JsError> {
+        let block = Block::new(cid.0, bytes).map_err(js_error)?;
+        self.blocks.insert(cid.0, block);
+        Ok(())
+    }
+
+    /// Returns the decoded block, or `undefined` if it's not in the store.
+    pub fn get(&self, cid: &JsCid) -> Result<JsValue, JsError> {
+        match self.blocks.get(&cid.0) {
+            Some(block) => from_ipld(&block.ipld().map_err(js_error)?),
+            None => Ok(JsValue::UNDEFINED),
+        }
+    }
+
+    /// Returns the encoded block, or `undefined` if it's not in the store.
+    #[wasm_bindgen(js_name = getBytes)]
+    pub fn get_bytes(&self, cid: &JsCid) -> Option<Vec<u8>> {
+        self.blocks.get(&cid.0).map(|block| block.data().to_vec())
+    }
+
+    /// Returns true if the block is in the store.
+    pub fn has(&self, cid: &JsCid) -> bool {
+        self.blocks.contains_key(&cid.0)
+    }
+}
@@ -0,0 +1,63 @@
+//! wasm-bindgen bindings for libipld.
+//!
+//! Exposes CIDs, the dag-cbor and dag-json codecs and an in-memory block store to JavaScript.
+//! The bindings are only compiled with the `wasm-bindings` feature.
+//!
+//! Ipld values map to plain JavaScript values: integers become numbers, or a `BigInt` when they
+//! don't fit into a safe integer, bytes become an `Uint8Array` and links become a
+//! `{ "/": "<cid>" }` object, like in dag-json.
+#![deny(missing_docs)]
+#![deny(warnings)]
+// The helpers are only used by the bindings, but tested without them.
+#![cfg_attr(not(feature = "wasm-bindings"), allow(dead_code))]
+
+use libipld::ipld::Ipld;
+
+#[cfg(feature = "wasm-bindings")]
+mod bindings;
+
+#[cfg(feature = "wasm-bindings")]
+pub use bindings::*;
+
+/// Largest integer a javascript number represents exactly.
+const MAX_SAFE_INTEGER: u128 = (1 << 53) - 1;
+
+/// Returns true if a javascript number represents the integer exactly.
+fn is_safe_integer(i: i128) -> bool {
+    i.unsigned_abs() <= MAX_SAFE_INTEGER
+}
+
+/// Converts a javascript number. Integral numbers in the safe range become integers.
+fn number_to_ipld(f: f64) -> Ipld {
+    if f.fract() == 0.0 && f.abs() <= MAX_SAFE_INTEGER as f64 {
+        Ipld::Integer(f as i128)
+    } else {
+        Ipld::Float(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safe_integer() {
+        assert!(is_safe_integer(0));
+        assert!(is_safe_integer((1 << 53) - 1));
+        assert!(is_safe_integer(-(1 << 53) + 1));
+        assert!(!is_safe_integer(1 << 53));
+        assert!(!is_safe_integer(-(1 << 53)));
+        assert!(!is_safe_integer(i128::MAX));
+        assert!(!is_safe_integer(i128::MIN));
+    }
+
+    #[test]
+    fn test_number_to_ipld() {
+        assert_eq!(number_to_ipld(42.0), Ipld::Integer(42));
+        assert_eq!(number_to_ipld(-0.0), Ipld::Integer(0));
+        assert_eq!(number_to_ipld(1.5), Ipld::Float(1.5));
+        assert_eq!(number_to_ipld(2f64.powi(53)), Ipld::Float(2f64.powi(53)));
+        assert_eq!(number_to_ipld(f64::INFINITY), Ipld::Float(f64::INFINITY));
+        assert!(matches!(number_to_ipld(f64::NAN), Ipld::Float(f) if f.is_nan()));
+    }
+}